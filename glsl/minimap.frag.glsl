@@ -0,0 +1,15 @@
+#version 330
+
+uniform sampler2D map;
+
+// Markers are drawn in a solid color. The map itself is textured, with textured set to 1.
+uniform vec4 color;
+uniform float textured;
+
+in vec2 vUV;
+
+out vec4 outColor;
+
+void main() {
+  outColor = mix(color, texture(map, vUV), textured);
+}
\ No newline at end of file
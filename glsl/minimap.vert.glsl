@@ -0,0 +1,19 @@
+#version 330
+
+// The map's corners, in normalized device coords.
+uniform vec2 mapMin;
+uniform vec2 mapMax;
+
+in vec2 position;
+
+out vec2 vUV;
+
+void main() {
+  gl_Position = vec4(position, 0.0, 1.0);
+  
+  // The top row of the map texture is the world's top, where y is smallest.
+  vUV = vec2(
+    (position.x - mapMin.x) / (mapMax.x - mapMin.x),
+    (mapMax.y - position.y) / (mapMax.y - mapMin.y)
+  );
+}
\ No newline at end of file
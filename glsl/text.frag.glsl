@@ -0,0 +1,15 @@
+#version 330
+
+// Glyphs are white, with their coverage in the alpha channel. Solid rectangles have negative
+// texture coords.
+uniform sampler2D font;
+
+in vec2 vUV;
+in vec4 vColor;
+
+out vec4 outColor;
+
+void main() {
+  float coverage = vUV.x < 0.0 ? 1.0 : texture(font, vUV).a;
+  outColor = vec4(vColor.rgb, vColor.a * coverage);
+}
\ No newline at end of file
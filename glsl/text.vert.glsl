@@ -0,0 +1,23 @@
+#version 330
+
+// In pixels.
+uniform vec2 screenSize;
+
+// In pixels from the top left.
+in vec2 position;
+in vec2 uv;
+in vec4 color;
+
+out vec2 vUV;
+out vec4 vColor;
+
+void main() {
+  gl_Position = vec4(
+    position.x / screenSize.x * 2.0 - 1.0,
+    1.0 - position.y / screenSize.y * 2.0,
+    0.0, 1.0
+  );
+  
+  vUV = uv;
+  vColor = color;
+}
\ No newline at end of file
@@ -1,9 +1,35 @@
 #version 330
 
+// Tint from the time of day.
+uniform vec3 ambient;
+
+uniform sampler2D atlas;
+
+// The tiles under the cursor and selected, in world coords. Negative when there aren't any.
+uniform vec2 hovered;
+uniform vec2 selected;
+
+// How much to darken the level being drawn, for levels below the player's. 1 for their own.
+uniform float dim;
+
+in vec2 vPosition;
 in vec3 vColor;
+in vec2 vUV;
+in float vLight;
 
 out vec4 outColor;
 
 void main() {
-  outColor = vec4(vColor, 1.0);
+  // Lamps light things up, but never darker than the daylight would.
+  vec3 lit = max(ambient, vec3(vLight, vLight * 0.9, vLight * 0.7));
+  vec4 color = texture(atlas, vUV) * vec4(vColor * lit * dim, 1.0);
+  
+  // Highlights show up the same by day or night. Tiles are centred on their coords.
+  vec2 tile = floor(vPosition + 0.5);
+  if (tile == selected) {
+    color.rgb = mix(color.rgb, vec3(1.0, 0.9, 0.2), 0.5);
+  } else if (tile == hovered) {
+    color.rgb = mix(color.rgb, vec3(1.0, 1.0, 1.0), 0.25);
+  }
+  outColor = color;
 }
\ No newline at end of file
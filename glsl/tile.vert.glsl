@@ -5,11 +5,19 @@ uniform mat4 projection;
 
 in vec2 position;
 in vec3 color;
+in vec2 uv;
+in float light;
 
+out vec2 vPosition;
 out vec3 vColor;
+out vec2 vUV;
+out float vLight;
 
 void main() {
   gl_Position = projection * model * vec4(position, 0.0, 1.0);
   
+  vPosition = position;
   vColor = color;
+  vUV = uv;
+  vLight = light;
 }
\ No newline at end of file
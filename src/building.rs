@@ -0,0 +1,42 @@
+// A building's footprint, in world coords, walls included. Every level has the same one.
+#[derive(Debug, Clone)]
+pub struct Building {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+    
+    // Where the front door is.
+    pub door_x: usize,
+    pub door_y: usize,
+    
+    // Where the safe is, if there is one, as (x, y, z).
+    pub safe: Option<(usize, usize, usize)>,
+    
+    // How many levels tall it is, counting the ground floor.
+    pub levels: usize,
+    
+    // Whether defeating a lock inside calls the police.
+    pub alarmed: bool,
+    
+    // Where the guards inside are posted, on the ground floor.
+    pub spawns: Vec<(usize, usize)>,
+    
+    // The name of the prefab it was built from, if it wasn't made up on the spot.
+    pub prefab: Option<String>
+}
+
+impl Building {
+    // Inclusive of the walls.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+    
+    pub fn width(&self) -> usize {
+        self.max_x - self.min_x + 1
+    }
+    
+    pub fn height(&self) -> usize {
+        self.max_y - self.min_y + 1
+    }
+}
\ No newline at end of file
@@ -1,12 +1,41 @@
 use cgmath::{Vector2, Matrix4, ortho};
 
+// An axis-aligned rectangle in world coords.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rect {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32
+}
+
+// Limits on zoom. At the minimum, the view spans about 1000 tiles of a 1280 pixel wide window,
+// and at the maximum about 10.
+pub const MIN_ZOOM: f32 = 2.5;
+pub const MAX_ZOOM: f32 = 250.0;
+
+// How quickly the camera closes in on its target. Each second, it covers all but e^-SMOOTHING of
+// the distance left.
+pub const SMOOTHING: f32 = 10.0;
+
+// Translation and zoom ease toward their targets as update is called. The matrices always reflect
+// where the camera is now, not where it's heading.
 pub struct Camera {
     pub translation: Vector2<f32>,
     pub zoom: f32,
     pub model_view: Matrix4<f32>,
     pub projection: Matrix4<f32>,
     pub width: u16,
-    pub height: u16
+    pub height: u16,
+    
+    pub target_translation: Vector2<f32>,
+    pub target_zoom: f32,
+    
+    // The view never shows anything outside this, where it's big enough.
+    pub bounds: Option<Rect>,
+    
+    // The world coords of whatever the camera is keeping centred, if anything.
+    follow: Option<(f32, f32)>
 }
 
 impl Camera {
@@ -14,7 +43,9 @@ impl Camera {
         let mut cam = Camera {
             translation: Vector2 {x: 0f32, y: 0f32}, zoom: zoom,
             model_view: Matrix4::identity(), projection: Matrix4::identity(),
-            width: width, height: height
+            width: width, height: height,
+            target_translation: Vector2 {x: 0f32, y: 0f32}, target_zoom: zoom,
+            bounds: None, follow: None
         };
         cam.rebuild_model_view();
         cam.rebuild_projection();
@@ -44,17 +75,280 @@ impl Camera {
         }
     }
     
-    pub fn translate(&mut self, amount: Vector2<f32>) {
-        self.translation = self.translation + amount;
+    // Eases toward the target. Call once per frame with the real time since the last.
+    pub fn update(&mut self, dt: f32) {
+        if let Some((x, y)) = self.follow {
+            self.target_translation = Vector2::new(-x, -y);
+        }
+        self.clamp_target();
+        
+        let t = 1.0 - (-SMOOTHING * dt).exp();
+        self.translation = Vector2::new(
+            self.translation.x + (self.target_translation.x - self.translation.x) * t,
+            self.translation.y + (self.target_translation.y - self.translation.y) * t
+        );
+        
+        // Ease zoom by ratio, so zooming in and out feel the same.
+        self.zoom = self.zoom * (self.target_zoom / self.zoom).powf(t);
+        
+        self.rebuild_model_view();
+        self.rebuild_projection();
+    }
+    
+    // Moves to the target at once.
+    pub fn snap(&mut self) {
+        self.update(0.0);
+        self.translation = self.target_translation;
+        self.zoom = self.target_zoom;
         self.rebuild_model_view();
+        self.rebuild_projection();
+    }
+    
+    // Keeps the view on (x, y), in world coords, until the camera's panned by hand. Call again
+    // whenever the followed thing moves.
+    pub fn follow(&mut self, x: f32, y: f32) {
+        self.follow = Some((x, y));
+    }
+    
+    // Centres the view on (x, y), in world coords, at once. Stops following.
+    pub fn jump_to(&mut self, x: f32, y: f32) {
+        self.follow = None;
+        self.target_translation = Vector2::new(-x, -y);
+        self.snap();
+    }
+    
+    pub fn unfollow(&mut self) {
+        self.follow = None;
+    }
+    
+    pub fn is_following(&self) -> bool {
+        self.follow.is_some()
+    }
+    
+    // Pans the target. Stops following.
+    pub fn translate(&mut self, amount: Vector2<f32>) {
+        self.follow = None;
+        self.target_translation = self.target_translation + amount;
+        self.clamp_target();
+    }
+    
+    // Keeps the target view inside the bounds. A view wider or taller than the bounds is centred
+    // on them instead.
+    fn clamp_target(&mut self) {
+        let bounds = match self.bounds {
+            Some(ref bounds) => { bounds.clone() },
+            None             => { return; }
+        };
+        let half_w = self.width  as f32 / self.target_zoom;
+        let half_h = self.height as f32 / self.target_zoom;
+        let center_x = clamp_center(-self.target_translation.x, half_w, bounds.min_x, bounds.max_x);
+        let center_y = clamp_center(-self.target_translation.y, half_h, bounds.min_y, bounds.max_y);
+        self.target_translation = Vector2::new(-center_x, -center_y);
+    }
+    
+    // The part of the world on screen. Inverts the model view and projection matrices: the
+    // projection spans width / zoom units either side of the origin, and the model view shifts
+    // the world by the translation.
+    pub fn visible_rect(&self) -> Rect {
+        let half_w = self.width  as f32 / self.zoom;
+        let half_h = self.height as f32 / self.zoom;
+        Rect {
+            min_x: -half_w - self.translation.x,
+            min_y: -half_h - self.translation.y,
+            max_x:  half_w - self.translation.x,
+            max_y:  half_h - self.translation.y
+        }
     }
     
     pub fn zoom(&self) -> f32 {
         self.zoom
     }
     
+    // Zooms the target, within MIN_ZOOM and MAX_ZOOM.
     pub fn zoom_by(&mut self, multiplier: f32) {
-        self.zoom = self.zoom * multiplier;
-        self.rebuild_projection();
+        let zoom = self.target_zoom * multiplier;
+        self.target_zoom = if zoom < MIN_ZOOM {
+            MIN_ZOOM
+        } else if zoom > MAX_ZOOM {
+            MAX_ZOOM
+        } else {
+            zoom
+        };
+        self.clamp_target();
+    }
+    
+    // Zooms so that once the camera gets there, the point under the screen coords (sx, sy) is
+    // still where it is on screen. When following, zooms on whatever's followed instead.
+    pub fn zoom_at(&mut self, multiplier: f32, sx: f32, sy: f32) {
+        if self.follow.is_some() {
+            self.zoom_by(multiplier);
+            return;
+        }
+        let (x, y) = self.screen_to_target_world(sx, sy);
+        self.zoom_by(multiplier);
+        let (after_x, after_y) = self.screen_to_target_world(sx, sy);
+        self.translate(Vector2::new(after_x - x, after_y - y));
+    }
+    
+    // Moves the world along with the cursor, for dragging. dx and dy are in pixels. Takes effect
+    // at once, since easing would leave the world lagging behind the cursor.
+    pub fn pan_by_pixels(&mut self, dx: f32, dy: f32) {
+        let per_pixel = 2.0 / self.zoom;
+        self.translate(Vector2::new(dx * per_pixel, dy * per_pixel));
+        self.translation = self.target_translation;
+        self.rebuild_model_view();
+    }
+    
+    // Screen coords are in pixels from the window's top left, like the cursor position. The
+    // screen spans 2 * width / zoom world units, so each pixel is 2 / zoom units.
+    pub fn screen_to_world(&self, sx: f32, sy: f32) -> (f32, f32) {
+        let per_pixel = 2.0 / self.zoom;
+        (
+            (sx - self.width  as f32 / 2.0) * per_pixel - self.translation.x,
+            (sy - self.height as f32 / 2.0) * per_pixel - self.translation.y
+        )
+    }
+    
+    // Like screen_to_world, but for where the camera's heading.
+    fn screen_to_target_world(&self, sx: f32, sy: f32) -> (f32, f32) {
+        let per_pixel = 2.0 / self.target_zoom;
+        (
+            (sx - self.width  as f32 / 2.0) * per_pixel - self.target_translation.x,
+            (sy - self.height as f32 / 2.0) * per_pixel - self.target_translation.y
+        )
+    }
+    
+    pub fn world_to_screen(&self, x: f32, y: f32) -> (f32, f32) {
+        let per_pixel = 2.0 / self.zoom;
+        (
+            (x + self.translation.x) / per_pixel + self.width  as f32 / 2.0,
+            (y + self.translation.y) / per_pixel + self.height as f32 / 2.0
+        )
+    }
+    
+    // The tile under the screen coords. Tiles are centred on their coords. May be outside the
+    // world.
+    pub fn screen_to_tile(&self, sx: f32, sy: f32) -> (i32, i32) {
+        let (x, y) = self.screen_to_world(sx, sy);
+        ((x + 0.5).floor() as i32, (y + 0.5).floor() as i32)
+    }
+}
+
+// Where to centre a view half_extent either side of center so it stays within min and max.
+fn clamp_center(center: f32, half_extent: f32, min: f32, max: f32) -> f32 {
+    if 2.0 * half_extent >= max - min {
+        (min + max) / 2.0
+    } else if center - half_extent < min {
+        min + half_extent
+    } else if center + half_extent > max {
+        max - half_extent
+    } else {
+        center
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Vector2, Matrix4};
+    
+    use super::{Camera, Rect, MIN_ZOOM, MAX_ZOOM};
+    
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+    
+    // Where (x, y) in world coords ends up on screen, in pixels from the top left, going by the
+    // matrices the way the vertex shader does.
+    fn project(camera: &Camera, x: f32, y: f32) -> (f32, f32) {
+        let eye = transform(&camera.model_view, [x, y, 0.0, 1.0]);
+        let clip = transform(&camera.projection, eye);
+        (
+            (clip[0] / clip[3] + 1.0) / 2.0 * camera.width as f32,
+            (1.0 - clip[1] / clip[3]) / 2.0 * camera.height as f32
+        )
+    }
+    
+    fn transform(m: &Matrix4<f32>, v: [f32; 4]) -> [f32; 4] {
+        let mut out = [0.0; 4];
+        for (col, &c) in [m.x, m.y, m.z, m.w].iter().zip(v.iter()) {
+            out[0] = out[0] + col.x * c;
+            out[1] = out[1] + col.y * c;
+            out[2] = out[2] + col.z * c;
+            out[3] = out[3] + col.w * c;
+        }
+        out
+    }
+    
+    #[test]
+    fn zoom_stays_within_limits() {
+        let mut camera = Camera::new(400, 200, 10.0);
+        camera.zoom_by(1000.0);
+        camera.snap();
+        assert_eq!(camera.zoom(), MAX_ZOOM);
+        // The view spans 2 * width / zoom world units, which the projection maps onto 2 units.
+        assert!(close(camera.projection.x.x, MAX_ZOOM / 400.0));
+        
+        camera.zoom_by(0.000001);
+        camera.snap();
+        assert_eq!(camera.zoom(), MIN_ZOOM);
+        assert!(close(camera.projection.x.x, MIN_ZOOM / 400.0));
+    }
+    
+    #[test]
+    fn translation_stays_within_bounds() {
+        // The view is 80 x 40 tiles.
+        let mut camera = Camera::new(400, 200, 10.0);
+        camera.bounds = Some(Rect { min_x: 0.0, min_y: 0.0, max_x: 2000.0, max_y: 2000.0 });
+        
+        camera.translate(Vector2::new(500.0, 500.0));
+        camera.snap();
+        assert!(close(camera.model_view.w.x, -40.0) && close(camera.model_view.w.y, -20.0));
+        assert_eq!(camera.visible_rect(), Rect { min_x: 0.0, min_y: 0.0, max_x: 80.0, max_y: 40.0 });
+        
+        camera.translate(Vector2::new(-5000.0, -5000.0));
+        camera.snap();
+        assert!(close(camera.model_view.w.x, -1960.0) && close(camera.model_view.w.y, -1980.0));
+        assert_eq!(camera.visible_rect(), Rect { min_x: 1920.0, min_y: 1960.0, max_x: 2000.0, max_y: 2000.0 });
+    }
+    
+    #[test]
+    fn zoom_at_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera::new(400, 200, 10.0);
+        camera.translate(Vector2::new(-100.0, -50.0));
+        camera.snap();
+        
+        let (sx, sy) = (300.0, 40.0);
+        for &multiplier in [2.0, 0.5, 3.0].iter() {
+            let (x, y) = camera.screen_to_world(sx, sy);
+            camera.zoom_at(multiplier, sx, sy);
+            camera.snap();
+            let (px, py) = project(&camera, x, y);
+            assert!(close(px, sx) && close(py, sy), "({}, {}) moved to ({}, {})", sx, sy, px, py);
+        }
+    }
+    
+    #[test]
+    fn update_eases_toward_the_target() {
+        let mut camera = Camera::new(400, 200, 10.0);
+        camera.translate(Vector2::new(-100.0, 0.0));
+        camera.zoom_by(2.0);
+        
+        // Every step closes in without overshooting, and the matrices keep up.
+        let mut left = 100.0;
+        for _ in 0..10 {
+            camera.update(1.0 / 60.0);
+            let now = camera.translation.x + 100.0;
+            assert!(now > 0.0 && now < left);
+            assert!(camera.zoom() > 10.0 && camera.zoom() < 20.0);
+            assert!(close(camera.model_view.w.x, camera.translation.x));
+            left = now;
+        }
+        
+        for _ in 0..300 {
+            camera.update(1.0 / 60.0);
+        }
+        assert!(close(camera.translation.x, -100.0) && close(camera.translation.y, 0.0));
+        assert!(close(camera.zoom(), 20.0));
+        assert!(close(camera.projection.x.x, 20.0 / 400.0));
     }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use std::f32::consts::PI;
+
+pub const SECONDS_PER_DAY: f32 = 24.0 * 60.0 * 60.0;
+
+// How many game seconds pass per simulated second. At 60, a day lasts 24 minutes.
+pub const TIME_SCALE: f32 = 60.0;
+
+// Darkest the world gets, at midnight.
+pub const NIGHT_LEVEL: f32 = 0.15;
+
+pub struct Clock {
+    pub day:     u32,
+    pub seconds: f32  // Since midnight.
+}
+
+impl Clock {
+    pub fn new(hour: u32, minute: u32) -> Clock {
+        Clock { day: 0, seconds: (hour * 3600 + minute * 60) as f32 }
+    }
+    
+    // dt is in simulated seconds.
+    pub fn advance(&mut self, dt: f32) {
+        self.seconds = self.seconds + dt * TIME_SCALE;
+        while self.seconds >= SECONDS_PER_DAY {
+            self.seconds = self.seconds - SECONDS_PER_DAY;
+            self.day = self.day + 1;
+        }
+    }
+    
+    pub fn hour(&self) -> u32 {
+        (self.seconds / 3600.0) as u32
+    }
+    
+    pub fn minute(&self) -> u32 {
+        ((self.seconds / 60.0) as u32) % 60
+    }
+    
+    // How high the sun is: 1 at noon, 0 at midnight.
+    pub fn sun(&self) -> f32 {
+        (1.0 - (self.seconds / SECONDS_PER_DAY * 2.0 * PI).cos()) / 2.0
+    }
+    
+    // 1 at noon, NIGHT_LEVEL at midnight.
+    pub fn daylight(&self) -> f32 {
+        NIGHT_LEVEL + (1.0 - NIGHT_LEVEL) * self.sun()
+    }
+    
+    // The color the whole world is tinted. Bluish at night, orange around dawn and dusk.
+    pub fn ambient(&self) -> (f32, f32, f32) {
+        let sun = self.sun();
+        let d = self.daylight();
+        // 1 with the sun on the horizon, 0 at noon and midnight.
+        let twilight = 1.0 - ((sun - 0.5) * 2.0).abs();
+        (
+            d + 0.15 * twilight,
+            d,
+            d + 0.10 * (1.0 - sun) - 0.10 * twilight
+        )
+    }
+}
+
+impl ::std::fmt::Display for Clock {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "Day {}, {:02}:{:02}", self.day + 1, self.hour(), self.minute())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,241 @@
+use std::usize;
+
+use camera::Camera;
+use debug::Overlays;
+use gen;
+use input::Action;
+use menu::Command;
+use security::Guard;
+use sim::Sim;
+use tile::{chunk, Tile};
+use ui::Ui;
+
+// The longest command that can be typed.
+const MAX_INPUT_LEN: usize = 80;
+
+// Lines of output showing.
+const OUTPUT_ROWS: usize = 12;
+
+static HELP: [&'static str; 11] = [
+    "help                  List commands.",
+    "clear                 Clear this output.",
+    "tp <x> <y>            Centre the camera on a tile.",
+    "seed <n> [generator]  Generate a new city, procedural or wfc.",
+    "tile <x> <y> <name>   Replace a tile, e.g. tile 10 10 brick_wall.",
+    "spawn police [x y]    Put a patrol car near a tile.",
+    "spawn guard [x y]     Post a guard on a tile.",
+    "chunk [x y]           Describe the chunk holding a tile.",
+    "overlay <name>        Toggle a debug overlay.",
+    "Positions left out default to the tile under the cursor, then the player.",
+    "Tiles and chunks are on the level the player's on."
+];
+
+// A developer console for poking at the game while it runs. While it's open the simulation
+// doesn't tick, like with any other menu.
+pub struct Console {
+    pub open: bool,
+    input:    String,
+    output:   Vec<String>,
+    scroll:   usize,
+    
+    // Commands entered, oldest first, and which one up and down have recalled. A position past
+    // the end means none.
+    history:  Vec<String>,
+    recalled: usize
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            open: false, input: String::new(), output: vec!["Type help for commands.".to_string()],
+            scroll: usize::MAX, history: Vec::new(), recalled: 0
+        }
+    }
+    
+    // Lays out the console along the top of the screen, and runs whatever's entered. position is
+    // the tile under the cursor, if any. Call between ui.begin and ui.end.
+    pub fn update(
+        &mut self, ui: &mut Ui, sim: &mut Sim, camera: &mut Camera, overlays: &mut Overlays,
+        position: Option<(usize, usize)>
+    ) -> Option<Command> {
+        if ui.pressed(Action::ToggleConsole) || ui.pressed(Action::Back) {
+            self.open = false;
+            return None;
+        }
+        
+        if !self.history.is_empty() {
+            if ui.pressed(Action::MenuUp) && self.recalled > 0 {
+                self.recalled = self.recalled - 1;
+                self.input = self.history[self.recalled].clone();
+            }
+            if ui.pressed(Action::MenuDown) && self.recalled < self.history.len() {
+                self.recalled = self.recalled + 1;
+                self.input = self.history.get(self.recalled).cloned().unwrap_or(String::new());
+            }
+        }
+        
+        // Typing always goes to the input line, after the output. Up and down recall commands
+        // instead of moving the focus.
+        ui.focus_on(1);
+        let (width, height) = ui.screen_size();
+        ui.begin_panel_at("Console", 0.0, 0.0, width, (height * 0.4).floor());
+        ui.log(&self.output, OUTPUT_ROWS, &mut self.scroll);
+        let entered = ui.text_input(&mut self.input, MAX_INPUT_LEN);
+        // The key that opens the console types a backquote on the way.
+        self.input = self.input.replace("`", "");
+        if !entered || self.input.trim().is_empty() {
+            return None;
+        }
+        
+        let line = self.input.trim().to_string();
+        self.input.clear();
+        self.history.push(line.clone());
+        self.recalled = self.history.len();
+        self.output.push(format!("> {}", line));
+        self.scroll = usize::MAX;
+        self.run(&line, sim, camera, overlays, position)
+    }
+    
+    fn print(&mut self, line: String) {
+        self.output.push(line);
+    }
+    
+    fn run(
+        &mut self, line: &str, sim: &mut Sim, camera: &mut Camera, overlays: &mut Overlays,
+        position: Option<(usize, usize)>
+    ) -> Option<Command> {
+        let words: Vec<&str> = line.split(' ').filter(|w| !w.is_empty()).collect();
+        let here = position.unwrap_or((sim.player.x, sim.player.y));
+        match (words[0], words.len()) {
+            ("help", 1) => {
+                for line in HELP.iter() {
+                    self.print(line.to_string());
+                }
+                self.print(format!("Overlays: {}", Overlays::names().join(", ")));
+            },
+            ("clear", 1) => {
+                self.output.clear();
+            },
+            ("tp", 3) => {
+                if let Some((x, y)) = self.parse_position(sim, words[1], words[2]) {
+                    camera.jump_to(x as f32, y as f32);
+                }
+            },
+            ("seed", 2) | ("seed", 3) => {
+                let strategy = if words.len() == 3 { gen::strategy_from_name(words[2]) } else { Some(sim.strategy) };
+                match (words[1].parse(), strategy) {
+                    (Ok(seed), Some(strategy)) => {
+                        self.print(format!("Generating city {} with the {} generator", seed, gen::strategy_name(strategy)));
+                        return Some(Command::Start(seed, strategy));
+                    },
+                    (Err(_), _) => { self.print(format!("\"{}\" isn't a seed", words[1])); },
+                    (_, None)   => { self.print(format!("No generator named \"{}\"", words[2])); }
+                }
+            },
+            ("tile", 4) => {
+                if let Some((x, y)) = self.parse_position(sim, words[1], words[2]) {
+                    match Tile::from_name(words[3]) {
+                        Some(tile) => { sim.world.replace_tile(x, y, sim.player.z, tile); },
+                        None       => { self.print(format!("No tile named \"{}\"", words[3])); }
+                    }
+                }
+            },
+            ("spawn", 2) | ("spawn", 4) => {
+                if words[1] != "police" && words[1] != "guard" {
+                    self.print(format!("Can't spawn \"{}\". Only police or a guard.", words[1]));
+                    return None;
+                }
+                let at = if words.len() == 4 { self.parse_position(sim, words[2], words[3]) } else { Some(here) };
+                if let Some((x, y)) = at {
+                    if words[1] == "police" {
+                        sim.police.spawn_unit(&sim.world, x, y);
+                        self.print(format!("{} units on the street", sim.police.units.len()));
+                    } else {
+                        sim.world.guards.push(Guard::new(x, y, sim.player.z));
+                        self.print(format!("{} guards posted", sim.world.guards.len()));
+                    }
+                }
+            },
+            ("chunk", 1) | ("chunk", 3) => {
+                let at = if words.len() == 3 { self.parse_position(sim, words[1], words[2]) } else { Some(here) };
+                if let Some((x, y)) = at {
+                    for line in describe_chunk(sim, x, y).into_iter() {
+                        self.print(line);
+                    }
+                }
+            },
+            ("overlay", 2) => {
+                match overlays.toggle(words[1]) {
+                    Some(on) => { self.print(format!("{} {}", words[1], if on { "on" } else { "off" })); },
+                    None     => { self.print(format!("No overlay named \"{}\"", words[1])); }
+                }
+            },
+            (command, _) => {
+                self.print(format!("Don't know \"{}\" with {} arguments. Try help.", command, words.len() - 1));
+            }
+        }
+        None
+    }
+    
+    // Tile coords from two words, if they're numbers inside the world. Complains if not.
+    fn parse_position(&mut self, sim: &Sim, x: &str, y: &str) -> Option<(usize, usize)> {
+        match (x.parse::<i32>(), y.parse::<i32>()) {
+            (Ok(x), Ok(y)) if sim.world.in_bounds(x, y) => { Some((x as usize, y as usize)) },
+            (Ok(x), Ok(y)) => {
+                self.print(format!("({}, {}) is outside the world", x, y));
+                None
+            },
+            _ => {
+                self.print("Positions are two whole numbers".to_string());
+                None
+            }
+        }
+    }
+}
+
+// The chunk holding the tile on the player's level: where it is, its lights, whether it needs
+// re-meshing, the buildings in it and what its tiles are.
+fn describe_chunk(sim: &Sim, x: usize, y: usize) -> Vec<String> {
+    let world = &sim.world;
+    let (cx, cy, z) = (x / chunk::SIZE, y / chunk::SIZE, sim.player.z);
+    let chunk = match world.chunk_containing(x, y, z) {
+        Some(chunk) => { chunk.borrow() },
+        None        => { return vec![format!("Chunk ({}, {}) has nothing built on level {}", cx, cy, z)]; }
+    };
+    let (min_x, min_y) = (chunk.min_x(), chunk.min_y());
+    let (max_x, max_y) = (min_x + chunk::SIZE - 1, min_y + chunk::SIZE - 1);
+    
+    let mut lines = vec![format!(
+        "Chunk ({}, {}) level {}: tiles ({}, {}) to ({}, {}), {} lights, {}",
+        cx, cy, z, min_x, min_y, max_x, max_y, chunk.lights.len(),
+        if world.dirty_chunks.contains(&(cx, cy, z)) { "dirty" } else { "clean" }
+    )];
+    
+    let buildings: Vec<String> = world.buildings.iter().enumerate().filter(|&(_, b)| {
+        b.min_x <= max_x && b.max_x >= min_x && b.min_y <= max_y && b.max_y >= min_y
+    }).map(|(i, b)| {
+        match b.prefab {
+            Some(ref name) => { format!("{} ({}, {} levels, {} spawn points)", i, name, b.levels, b.spawns.len()) },
+            None           => { format!("{} ({} levels)", i, b.levels) }
+        }
+    }).collect();
+    if !buildings.is_empty() {
+        lines.push(format!("Buildings: {}", buildings.join(", ")));
+    }
+    
+    // Counted by sprite, most common first.
+    let mut counts: Vec<(&'static str, usize)> = Vec::new();
+    for ty in min_y..(max_y + 1) {
+        for tx in min_x..(max_x + 1) {
+            let name = chunk.tile_at(tx, ty).sprite_name();
+            match counts.iter().position(|&(n, _)| n == name) {
+                Some(i) => { counts[i].1 = counts[i].1 + 1; },
+                None    => { counts.push((name, 1)); }
+            }
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+    let counts: Vec<String> = counts.iter().map(|&(name, n)| format!("{} {}", name, n)).collect();
+    lines.push(format!("Tiles: {}", counts.join(", ")));
+    lines
+}
\ No newline at end of file
@@ -0,0 +1,238 @@
+use camera::Camera;
+use gen::street;
+use path;
+use police::SIGHT_RADIUS;
+use security::{CAMERA_RANGE, GUARD_RANGE};
+use sim::Sim;
+use text;
+use tile::chunk;
+
+// How many frames the frame time graph shows, one pixel column each.
+pub const GRAPH_FRAMES: usize = 240;
+
+// In pixels.
+const GRAPH_HEIGHT: f32 = 80.0;
+const GRAPH_MARGIN: f32 = 10.0;
+
+// The frame time at the top of the graph, in seconds.
+const GRAPH_MAX_TIME: f32 = 1.0 / 20.0;
+
+const CHUNK_COLOR:  (f32, f32, f32, f32) = (1.0, 1.0, 0.0, 0.6);
+const STREET_COLOR: (f32, f32, f32, f32) = (0.0, 0.8, 1.0, 0.8);
+const ROUTE_COLOR:  (f32, f32, f32, f32) = (1.0, 0.2, 0.2, 0.8);
+const PATH_COLOR:   (f32, f32, f32, f32) = (0.2, 1.0, 0.2, 0.5);
+const AWAY_COLOR:   (f32, f32, f32, f32) = (0.2, 1.0, 0.2, 0.2);  // Path on other levels.
+const FOV_COLOR:    (f32, f32, f32, f32) = (1.0, 0.3, 0.3, 0.25);
+const CAMERA_COLOR: (f32, f32, f32, f32) = (1.0, 0.6, 0.0, 0.25);
+const LABEL_COLOR:  (f32, f32, f32, f32) = (1.0, 1.0, 0.0, 1.0);
+
+static NAMES: [&'static str; 5] = ["chunks", "streets", "paths", "fov", "fps"];
+
+// Debug drawing over the world, each toggled on its own: chunk borders, the street graph, police
+// routes and the player's path to the hovered tile, what the police, cameras and guards can see,
+// and a graph of recent frame times.
+pub struct Overlays {
+    pub chunks:  bool,
+    pub streets: bool,
+    pub paths:   bool,
+    pub fov:     bool,
+    pub fps:     bool,
+    
+    // The last GRAPH_FRAMES frame times in seconds, oldest first.
+    frame_times: Vec<f32>
+}
+
+impl Overlays {
+    pub fn new() -> Overlays {
+        Overlays {
+            chunks: false, streets: false, paths: false, fov: false, fps: false,
+            frame_times: Vec::with_capacity(GRAPH_FRAMES)
+        }
+    }
+    
+    pub fn names() -> &'static [&'static str] {
+        &NAMES
+    }
+    
+    // Toggles the named overlay. Returns whether it's now on, or None if there's no such overlay.
+    pub fn toggle(&mut self, name: &str) -> Option<bool> {
+        let overlay = match name {
+            "chunks"  => { &mut self.chunks },
+            "streets" => { &mut self.streets },
+            "paths"   => { &mut self.paths },
+            "fov"     => { &mut self.fov },
+            "fps"     => { &mut self.fps },
+            _         => { return None; }
+        };
+        *overlay = !*overlay;
+        Some(*overlay)
+    }
+    
+    // Call once per frame with the real time since the last.
+    pub fn record_frame(&mut self, dt: f32) {
+        if self.frame_times.len() == GRAPH_FRAMES {
+            self.frame_times.remove(0);
+        }
+        self.frame_times.push(dt);
+    }
+    
+    // Queues whichever overlays are on. hovered is the tile under the cursor, for the path.
+    pub fn queue(&self, text: &mut text::Renderer, camera: &Camera, sim: &Sim, hovered: Option<(usize, usize)>) {
+        if self.fov {
+            queue_fov(text, camera, sim);
+        }
+        if self.chunks {
+            queue_chunks(text, camera, sim);
+        }
+        if self.streets {
+            queue_streets(text, camera, sim);
+        }
+        if self.paths {
+            queue_paths(text, camera, sim, hovered);
+        }
+        if self.fps {
+            self.queue_graph(text, camera);
+        }
+    }
+    
+    // Bars in the bottom right, green for frames at the tick rate or better, yellow for half
+    // that, red for worse. The line marks 60 fps.
+    fn queue_graph(&self, text: &mut text::Renderer, camera: &Camera) {
+        if self.frame_times.is_empty() {
+            return;
+        }
+        let left = camera.width as f32 - GRAPH_MARGIN - GRAPH_FRAMES as f32;
+        let bottom = camera.height as f32 - GRAPH_MARGIN;
+        text.queue_rect(left, bottom - GRAPH_HEIGHT, GRAPH_FRAMES as f32, GRAPH_HEIGHT, (0.0, 0.0, 0.0, 0.6));
+        
+        for (i, &dt) in self.frame_times.iter().enumerate() {
+            let height = (dt / GRAPH_MAX_TIME).min(1.0) * GRAPH_HEIGHT;
+            let color = if dt <= 1.0 / 55.0 {
+                (0.2, 0.9, 0.2, 0.9)
+            } else if dt <= 1.0 / 28.0 {
+                (0.9, 0.9, 0.2, 0.9)
+            } else {
+                (0.9, 0.2, 0.2, 0.9)
+            };
+            text.queue_rect(left + i as f32, bottom - height, 1.0, height, color);
+        }
+        let sixty = bottom - (1.0 / 60.0) / GRAPH_MAX_TIME * GRAPH_HEIGHT;
+        text.queue_rect(left, sixty, GRAPH_FRAMES as f32, 1.0, (1.0, 1.0, 1.0, 0.5));
+        
+        let average = self.frame_times.iter().fold(0.0, |sum, &dt| sum + dt) / self.frame_times.len() as f32;
+        let worst = self.frame_times.iter().fold(0.0f32, |worst, &dt| worst.max(dt));
+        let label = format!(
+            "{:.0} fps  {:.1} ms avg  {:.1} ms worst", 1.0 / average, average * 1000.0, worst * 1000.0
+        );
+        let line_height = text.font.cell_height as f32;
+        text.queue(left, bottom - GRAPH_HEIGHT - line_height, &label, LABEL_COLOR);
+    }
+}
+
+// Outlines every chunk on screen and labels it with its indices.
+fn queue_chunks(text: &mut text::Renderer, camera: &Camera, sim: &Sim) {
+    let size = chunk::SIZE as f32;
+    for (cx, cy) in sim.world.chunks_in_rect(&camera.visible_rect()).into_iter() {
+        // Tiles are centred on their coords, so chunk edges fall halfway between tiles.
+        let (x0, y0) = camera.world_to_screen(cx as f32 * size - 0.5, cy as f32 * size - 0.5);
+        let (x1, y1) = camera.world_to_screen((cx + 1) as f32 * size - 0.5, (cy + 1) as f32 * size - 0.5);
+        text.queue_line(x0, y0, x1, y0, 1.0, CHUNK_COLOR);
+        text.queue_line(x0, y0, x0, y1, 1.0, CHUNK_COLOR);
+        text.queue_line(x1, y0, x1, y1, 1.0, CHUNK_COLOR);
+        text.queue_line(x0, y1, x1, y1, 1.0, CHUNK_COLOR);
+        text.queue(x0 + 2.0, y0 + 2.0, &format!("{},{}", cx, cy), LABEL_COLOR);
+    }
+}
+
+// A line along every street, between the centres of the intersections it joins. Intersections the
+// terrain cut off from every street aren't shown.
+fn queue_streets(text: &mut text::Renderer, camera: &Camera, sim: &Sim) {
+    for row in sim.world.grid.intersections.iter() {
+        for inter in row.iter() {
+            let inter = inter.borrow();
+            if !inter.is_open() {
+                continue;
+            }
+            let (x, y) = inter.center();
+            let (sx, sy) = camera.world_to_screen(x as f32, y as f32);
+            text.queue_rect(sx - 3.0, sy - 3.0, 6.0, 6.0, STREET_COLOR);
+            for (nx, ny) in inter.neighbors().into_iter() {
+                let (tx, ty) = street::center(nx, ny);
+                let (tsx, tsy) = camera.world_to_screen(tx as f32, ty as f32);
+                text.queue_line(sx, sy, tsx, tsy, 2.0, STREET_COLOR);
+            }
+        }
+    }
+}
+
+// Each police unit's route, and the path the player would walk to the hovered tile on their level.
+// Any of it on other levels is fainter.
+fn queue_paths(text: &mut text::Renderer, camera: &Camera, sim: &Sim, hovered: Option<(usize, usize)>) {
+    for unit in sim.police.units.iter() {
+        let (mut sx, mut sy) = camera.world_to_screen(unit.x, unit.y);
+        for &(gx, gy) in unit.route.iter() {
+            let (x, y) = street::center(gx, gy);
+            let (tsx, tsy) = camera.world_to_screen(x as f32, y as f32);
+            text.queue_line(sx, sy, tsx, tsy, 3.0, ROUTE_COLOR);
+            sx = tsx;
+            sy = tsy;
+        }
+    }
+    
+    if let Some(goal) = hovered {
+        let z = sim.player.z;
+        let from = (sim.player.x, sim.player.y, z);
+        if let Some(tiles) = path::find_path(&sim.world, &sim.player.keys, from, (goal.0, goal.1, z)) {
+            for &(x, y, tz) in tiles.iter() {
+                queue_tile(text, camera, x, y, if tz == z { PATH_COLOR } else { AWAY_COLOR });
+            }
+        }
+    }
+}
+
+// Shades every tile a police unit could spot the player on right now, and every tile a camera
+// that hasn't gone off yet watches, on the player's level.
+fn queue_fov(text: &mut text::Renderer, camera: &Camera, sim: &Sim) {
+    let daylight = sim.clock.daylight();
+    let reach = SIGHT_RADIUS.ceil() as i32;
+    for unit in sim.police.units.iter() {
+        let (ux, uy) = (unit.x.round() as i32, unit.y.round() as i32);
+        for y in (uy - reach)..(uy + reach + 1) {
+            for x in (ux - reach)..(ux + reach + 1) {
+                if sim.world.in_bounds(x, y) && unit.can_see(&sim.world, daylight, x as usize, y as usize, sim.player.z) {
+                    queue_tile(text, camera, x as usize, y as usize, FOV_COLOR);
+                }
+            }
+        }
+    }
+    
+    let reach = CAMERA_RANGE.ceil() as i32;
+    for security_camera in sim.world.cameras.iter().filter(|c| !c.tripped) {
+        let (cx, cy) = (security_camera.x as i32, security_camera.y as i32);
+        for y in (cy - reach)..(cy + reach + 1) {
+            for x in (cx - reach)..(cx + reach + 1) {
+                if sim.world.in_bounds(x, y) && security_camera.can_see(&sim.world, x as usize, y as usize, sim.player.z) {
+                    queue_tile(text, camera, x as usize, y as usize, CAMERA_COLOR);
+                }
+            }
+        }
+    }
+    
+    let reach = GUARD_RANGE.ceil() as i32;
+    for guard in sim.world.guards.iter().filter(|g| !g.alerted) {
+        let (gx, gy) = (guard.x as i32, guard.y as i32);
+        for y in (gy - reach)..(gy + reach + 1) {
+            for x in (gx - reach)..(gx + reach + 1) {
+                if sim.world.in_bounds(x, y) && guard.can_see(&sim.world, x as usize, y as usize, sim.player.z) {
+                    queue_tile(text, camera, x as usize, y as usize, CAMERA_COLOR);
+                }
+            }
+        }
+    }
+}
+
+fn queue_tile(text: &mut text::Renderer, camera: &Camera, x: usize, y: usize, color: (f32, f32, f32, f32)) {
+    let (x0, y0) = camera.world_to_screen(x as f32 - 0.5, y as f32 - 0.5);
+    let (x1, y1) = camera.world_to_screen(x as f32 + 0.5, y as f32 + 0.5);
+    text.queue_rect(x0, y0, x1 - x0, y1 - y0, color);
+}
\ No newline at end of file
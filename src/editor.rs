@@ -0,0 +1,367 @@
+use std::cell::RefCell;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+use rand::XorShiftRng;
+
+use camera::Camera;
+use gen;
+use input::Action;
+use prefab::{self, Fixture, Prefab};
+use text;
+use tile::Tile;
+use ui::{Ui, ListState};
+use world::World;
+
+// In pixels.
+const PANEL_WIDTH: f32 = 300.0;
+
+const BRUSH_ROWS: usize = 13;
+
+const SELECTION_COLOR: (f32, f32, f32, f32) = (0.3, 0.8, 1.0, 0.9);
+const PREVIEW_COLOR:   (f32, f32, f32, f32) = (1.0, 1.0, 1.0, 0.9);
+
+// Tiles paint the whole tile, fixtures go on whatever floor's there, and erase takes fixtures off.
+static BRUSHES: [&'static str; 20] = [
+    "brick_wall", "concrete", "asphalt", "grass", "sidewalk", "curb", "lane_marking", "crosswalk",
+    "stairs_up", "stairs_down", "elevator", "empty", "water", "bridge", "hill",
+    "door", "locked_door", "safe", "item", "erase"
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Paint,      // Paints tiles under the cursor while the button's down.
+    Rectangle,  // Fills the rectangle dragged out.
+    Select      // Marks the rectangle dragged out, for saving as a prefab or stamping one into.
+}
+
+// One undoable change: the level it was on and the tiles it touched, each with what it was and
+// what it became.
+struct Edit {
+    z:     usize,
+    tiles: Vec<(usize, usize, Tile, Tile)>
+}
+
+// Lets designers paint tiles into the world with the mouse, and save what they make as prefabs
+// to mix in with generated buildings. The simulation stands still while it's open.
+pub struct Editor {
+    pub active: bool,
+    pub level:  usize,      // Which level edits go on. Keep it on the player's.
+    tool:       Tool,
+    brush:      ListState,  // Index into BRUSHES.
+    name:       String,     // Of the prefab or map to save or stamp.
+    status:     String,     // What the last save or stamp did.
+    
+    // Where the mouse went down, while it's down, and what's been painted since.
+    drag_start: Option<(usize, usize)>,
+    last_paint: Option<(usize, usize)>,
+    stroke:     Vec<(usize, usize, Tile, Tile)>,
+    
+    // Inclusive, as (min x, min y, max x, max y).
+    selection:  Option<(usize, usize, usize, usize)>,
+    
+    undo:       Vec<Edit>,
+    redo:       Vec<Edit>,
+    
+    // Rolls locks and loot for fixtures.
+    rng:        XorShiftRng,
+    
+    // The panel's rectangle on screen as (x, y, width, height), for telling clicks on it from
+    // clicks on the world.
+    panel:      (f32, f32, f32, f32)
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor {
+            active: false, level: 0, tool: Tool::Paint, brush: Default::default(),
+            name: String::new(), status: String::new(),
+            drag_start: None, last_paint: None, stroke: Vec::new(), selection: None,
+            undo: Vec::new(), redo: Vec::new(),
+            rng: gen::rng_from_seed(0),
+            panel: (0.0, 0.0, 0.0, 0.0)
+        }
+    }
+    
+    // Whether the screen coords are over the editor's panel.
+    pub fn covers(&self, x: f32, y: f32) -> bool {
+        let (px, py, pw, ph) = self.panel;
+        self.active && x >= px && x < px + pw && y >= py && y < py + ph
+    }
+    
+    // Call when the left button goes down over the world. at is the tile under the cursor.
+    pub fn press(&mut self, world: &mut World, at: Option<(usize, usize)>) {
+        self.drag_start = at;
+        self.last_paint = None;
+        if let (Tool::Paint, Some((x, y))) = (self.tool, at) {
+            self.paint(world, x, y);
+        }
+    }
+    
+    // Call when the left button comes up. Finishes the stroke, rectangle or selection.
+    pub fn release(&mut self, world: &mut World, at: Option<(usize, usize)>) {
+        let start = match self.drag_start.take() {
+            Some(start) => { start },
+            None        => { return; }
+        };
+        match self.tool {
+            Tool::Paint => {
+                let tiles = mem::replace(&mut self.stroke, Vec::new());
+                let z = self.level;
+                self.commit(Edit { z: z, tiles: tiles });
+            },
+            Tool::Rectangle => {
+                if let Some(end) = at {
+                    let (min_x, min_y, max_x, max_y) = rect(start, end);
+                    self.fill(world, min_x, min_y, max_x, max_y);
+                }
+            },
+            Tool::Select => {
+                if let Some(end) = at {
+                    self.selection = Some(rect(start, end));
+                }
+            }
+        }
+    }
+    
+    // Lays out the panel down the left of the screen and carries on any drag. Call between
+    // ui.begin and ui.end. at is the tile under the cursor.
+    pub fn update(&mut self, ui: &mut Ui, world: &mut World, camera: &Camera, at: Option<(usize, usize)>) {
+        if ui.pressed(Action::ToggleEditor) || ui.pressed(Action::Back) {
+            self.active = false;
+            return;
+        }
+        if let (Tool::Paint, Some(_), Some((x, y))) = (self.tool, self.drag_start, at) {
+            self.paint(world, x, y);
+        }
+        self.queue_outlines(&mut ui.text, camera, at);
+        
+        let (_, height) = ui.screen_size();
+        self.panel = (0.0, 0.0, PANEL_WIDTH, height);
+        ui.begin_panel_at("Editor", 0.0, 0.0, PANEL_WIDTH, height);
+        
+        for &(tool, label) in [
+            (Tool::Paint, "Paint"), (Tool::Rectangle, "Rectangle"), (Tool::Select, "Select")
+        ].iter() {
+            let mark = if self.tool == tool { ">" } else { " " };
+            if ui.button(&format!("{} {}", mark, label)) {
+                self.tool = tool;
+            }
+        }
+        
+        let brushes: Vec<String> = BRUSHES.iter().map(|b| b.to_string()).collect();
+        ui.list(&brushes, BRUSH_ROWS, &mut self.brush);
+        
+        if ui.button(&format!("Undo ({})", self.undo.len())) {
+            self.undo(world);
+        }
+        if ui.button(&format!("Redo ({})", self.redo.len())) {
+            self.redo(world);
+        }
+        
+        ui.label("Prefab or map name:");
+        ui.text_input(&mut self.name, 32);
+        if ui.button("Save selection as prefab") {
+            self.save_prefab(world);
+        }
+        if ui.button("Stamp prefab at selection") {
+            self.stamp_prefab(world);
+        }
+        if ui.button("Save whole map") {
+            self.save_map(world);
+        }
+        let status = self.status.clone();
+        ui.label(&status);
+    }
+    
+    // The selection, and while dragging out a rectangle or selection, the rectangle so far.
+    fn queue_outlines(&self, text: &mut text::Renderer, camera: &Camera, at: Option<(usize, usize)>) {
+        if let Some(selection) = self.selection {
+            queue_outline(text, camera, selection, SELECTION_COLOR);
+        }
+        if let (Some(start), Some(end)) = (self.drag_start, at) {
+            if self.tool != Tool::Paint {
+                queue_outline(text, camera, rect(start, end), PREVIEW_COLOR);
+            }
+        }
+    }
+    
+    // Paints the brush along a line from the last tile painted, so fast strokes don't leave gaps.
+    fn paint(&mut self, world: &mut World, x: usize, y: usize) {
+        let (from_x, from_y) = self.last_paint.unwrap_or((x, y));
+        if self.last_paint == Some((x, y)) {
+            return;
+        }
+        let (dx, dy) = (x as i32 - from_x as i32, y as i32 - from_y as i32);
+        let steps = dx.abs().max(dy.abs()).max(1);
+        for i in 1..(steps + 1) {
+            let px = (from_x as f32 + dx as f32 * i as f32 / steps as f32).round() as usize;
+            let py = (from_y as f32 + dy as f32 * i as f32 / steps as f32).round() as usize;
+            let before = world.tile_at(px, py, self.level);
+            let after = self.brush_tile(world, &before, px, py);
+            world.replace_tile(px, py, self.level, after.clone());
+            self.stroke.push((px, py, before, after));
+        }
+        self.last_paint = Some((x, y));
+    }
+    
+    // Plain tiles go in with gen::fill_rect. Fixtures are made one by one, so each gets its own
+    // lock and loot.
+    fn fill(&mut self, world: &mut World, min_x: usize, min_y: usize, max_x: usize, max_y: usize) {
+        let mut befores = Vec::new();
+        for y in min_y..(max_y + 1) {
+            for x in min_x..(max_x + 1) {
+                befores.push((x, y, world.tile_at(x, y, self.level)));
+            }
+        }
+        
+        match Tile::from_name(BRUSHES[self.brush.selected]) {
+            Some(ref tile) if tile.occupant().is_none() => {
+                gen::fill_rect(world, tile.clone(), min_x, min_y, max_x, max_y, self.level);
+            },
+            _ => {
+                for &(x, y, ref before) in befores.iter() {
+                    let after = self.brush_tile(world, before, x, y);
+                    world.replace_tile(x, y, self.level, after);
+                }
+            }
+        }
+        
+        let z = self.level;
+        let tiles = befores.into_iter().map(|(x, y, before)| (x, y, before, world.tile_at(x, y, z))).collect();
+        self.commit(Edit { z: z, tiles: tiles });
+    }
+    
+    // What tile becomes with the brush on it.
+    fn brush_tile(&mut self, world: &mut World, tile: &Tile, x: usize, y: usize) -> Tile {
+        let brush = BRUSHES[self.brush.selected];
+        if brush == "erase" {
+            return tile.without_occupant();
+        }
+        match prefab::fixture_from_name(brush) {
+            Some(fixture) => { self.fixture_tile(world, tile, fixture, x, y) },
+            None          => { Tile::from_name(brush).unwrap() }
+        }
+    }
+    
+    fn fixture_tile(&mut self, world: &mut World, tile: &Tile, fixture: Fixture, x: usize, y: usize) -> Tile {
+        let occupant = prefab::make_fixture(world, &mut self.rng, fixture, x, y);
+        tile.with_occupant(Rc::new(RefCell::new(occupant)))
+    }
+    
+    fn commit(&mut self, edit: Edit) {
+        if !edit.tiles.is_empty() {
+            self.undo.push(edit);
+            self.redo.clear();
+        }
+    }
+    
+    fn undo(&mut self, world: &mut World) {
+        if let Some(edit) = self.undo.pop() {
+            for &(x, y, ref before, _) in edit.tiles.iter().rev() {
+                world.replace_tile(x, y, edit.z, before.clone());
+            }
+            self.redo.push(edit);
+        }
+    }
+    
+    fn redo(&mut self, world: &mut World) {
+        if let Some(edit) = self.redo.pop() {
+            for &(x, y, _, ref after) in edit.tiles.iter() {
+                world.replace_tile(x, y, edit.z, after.clone());
+            }
+            self.undo.push(edit);
+        }
+    }
+    
+    // Checks the name's fit for a file name, and says why not if it isn't.
+    fn checked_name(&mut self) -> Option<String> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            self.status = "Names are letters, digits, _ and -.".to_string();
+            return None;
+        }
+        Some(name)
+    }
+    
+    fn save_prefab(&mut self, world: &World) {
+        let name = match self.checked_name() {
+            Some(name) => { name },
+            None       => { return; }
+        };
+        let (min_x, min_y, max_x, max_y) = match self.selection {
+            Some(selection) => { selection },
+            None            => { self.status = "Select something to save first.".to_string(); return; }
+        };
+        let path = format!("assets/prefabs/{}.txt", name);
+        self.status = match Prefab::from_world(world, min_x, min_y, max_x, max_y, self.level).save(&Path::new(&path)) {
+            Ok(())   => { format!("Saved {}", path) },
+            Err(why) => { why }
+        };
+    }
+    
+    // Stamps with the prefab's top left at the selection's.
+    fn stamp_prefab(&mut self, world: &mut World) {
+        let name = match self.checked_name() {
+            Some(name) => { name },
+            None       => { return; }
+        };
+        let (x, y) = match self.selection {
+            Some((min_x, min_y, _, _)) => { (min_x, min_y) },
+            None                       => { self.status = "Select where to stamp it first.".to_string(); return; }
+        };
+        let path = format!("assets/prefabs/{}.txt", name);
+        if !Path::new(&path).exists() {
+            self.status = format!("There's no {}", path);
+            return;
+        }
+        let prefab = match Prefab::load(&Path::new(&path)) {
+            Ok(prefab) => { prefab },
+            Err(why)   => { self.status = why; return; }
+        };
+        
+        let mut befores = Vec::new();
+        for py in y..(y + prefab.height) {
+            for px in x..(x + prefab.width) {
+                if world.in_bounds(px as i32, py as i32) {
+                    befores.push((px, py, world.tile_at(px, py, self.level)));
+                }
+            }
+        }
+        prefab.stamp(world, &mut self.rng, x, y, self.level);
+        let z = self.level;
+        let tiles = befores.into_iter().map(|(x, y, before)| (x, y, before, world.tile_at(x, y, z))).collect();
+        self.commit(Edit { z: z, tiles: tiles });
+        self.status = format!("Stamped {}", path);
+    }
+    
+    fn save_map(&mut self, world: &World) {
+        let name = match self.checked_name() {
+            Some(name) => { name },
+            None       => { return; }
+        };
+        let path = format!("assets/maps/{}.txt", name);
+        let last = world.size() - 1;
+        self.status = match Prefab::from_world(world, 0, 0, last, last, self.level).save(&Path::new(&path)) {
+            Ok(())   => { format!("Saved {}", path) },
+            Err(why) => { why }
+        };
+    }
+}
+
+// The rectangle with corners at a and b, as (min x, min y, max x, max y).
+fn rect(a: (usize, usize), b: (usize, usize)) -> (usize, usize, usize, usize) {
+    (a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1))
+}
+
+fn queue_outline(
+    text: &mut text::Renderer, camera: &Camera, rect: (usize, usize, usize, usize), color: (f32, f32, f32, f32)
+) {
+    let (min_x, min_y, max_x, max_y) = rect;
+    let (x0, y0) = camera.world_to_screen(min_x as f32 - 0.5, min_y as f32 - 0.5);
+    let (x1, y1) = camera.world_to_screen(max_x as f32 + 0.5, max_y as f32 + 0.5);
+    text.queue_line(x0, y0, x1, y0, 2.0, color);
+    text.queue_line(x0, y1, x1, y1, 2.0, color);
+    text.queue_line(x0, y0, x0, y1, 2.0, color);
+    text.queue_line(x1, y0, x1, y1, 2.0, color);
+}
\ No newline at end of file
@@ -0,0 +1,450 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::path::Path;
+use std::usize;
+use rand::Rng;
+
+use super::{fill_rect, Strategy};
+use super::district::{District, district_of};
+use super::wfc;
+use super::terrain::Terrain;
+use building::Building;
+use inventory::Item;
+use lock::{Lock, LockKind};
+use light::{Light, INTERIOR_RADIUS, INTERIOR_INTENSITY};
+use prefab::{self, Prefab, Fixture, Mark};
+use tile::{Occupant, Door, Container, Tile};
+use tile::Tile::{WallTile, FloorTile};
+use tile::WallTileType::BrickWall;
+use tile::FloorTileType::{ConcreteFloor, StairsUpFloor, StairsDownFloor, ElevatorFloor};
+use world::World;
+use gen::street::{GRID_SIZE, STREET_WIDTH, BLOCK_WIDTH};
+
+pub const LOTS_PER_SIDE: usize = 2;  // Each block is split into LOTS_PER_SIDE x LOTS_PER_SIDE lots.
+pub const LOT_MARGIN:    usize = 4;  // How many tiles of yard between a lot's edge and its building.
+
+// One in this many blocks with a prefab that fits is made one large lot instead of several small.
+pub const LARGE_LOT_CHANCE: u32 = 6;
+
+// One in this many small lots with a prefab that fits gets one instead of a made up building.
+pub const PREFAB_CHANCE: u32 = 4;
+
+// One in this many made up buildings has more than one level, up to MAX_LEVELS.
+pub const TALL_CHANCE: u32 = 3;
+pub const MAX_LEVELS:  usize = 3;
+
+// The most tiles across and down the WFC generator fills on a lot, yard included. It takes about
+// the square of the area, so whole lots would take far too long.
+pub const WFC_SIZE: usize = 32;
+
+// What WFC buildings are made of. Patterns in the example maps with anything else are left out.
+static WFC_KINDS: [&'static str; 3] = ["grass", "concrete", "brick_wall"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LotSize {
+    Small,  // A quarter of a block.
+    Large   // A whole block.
+}
+
+// Name, weight, volume, value, fence price, heat.
+static LOOT: [(&'static str, f32, f32, u32, u32, f32); 8] = [
+    ("Wad of cash",       0.2,  0.3,   2000,  2000, 0.0),
+    ("Gold watch",        0.1,  0.1,   8000,  3000, 1.0),
+    ("Diamond necklace",  0.1,  0.1,  40000, 12000, 3.0),
+    ("Laptop",            2.0,  3.0,   1500,   400, 0.5),
+    ("Gold bar",         12.4,  0.7,  60000, 40000, 2.0),
+    ("Painting",          4.0, 30.0,  90000, 20000, 5.0),
+    ("Bearer bonds",      0.5,  0.5,  25000, 15000, 1.5),
+    ("Silverware",        3.0,  4.0,   1200,   500, 0.5)
+];
+
+pub fn random_item<R: Rng>(world: &mut World, rng: &mut R) -> Item {
+    let &(name, weight, volume, value, fence_price, heat) = rng.choose(&LOOT).unwrap();
+    Item::new(world.next_item_id(), name, weight, volume, value, fence_price, heat)
+}
+
+// Parks are left as they are, and so is any lot that isn't all flat land.
+pub fn generate<R: Rng>(world: &mut World, rng: &mut R, strategy: Strategy, terrain: &Terrain) {
+    let lot_width = BLOCK_WIDTH / LOTS_PER_SIDE;
+    // Without them, every lot gets a made-up building instead.
+    let prefabs = match Prefab::load_dir(Path::new(prefab::BUILDINGS_DIR)) {
+        Ok(prefabs) => { prefabs },
+        Err(why)    => { println!("{}. Building without prefabs.", why); Vec::new() }
+    };
+    let model = match strategy {
+        Strategy::Procedural => { None },
+        Strategy::Wfc        => { Some(wfc::Model::learn(&wfc::EXAMPLE_MAPS, &WFC_KINDS)) }
+    };
+    
+    // Blocks sit between streets, so there's one fewer block than streets in each direction.
+    for block_y in 0..(GRID_SIZE - 1) {
+        for block_x in 0..(GRID_SIZE - 1) {
+            let block_min_x = block_x * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH;
+            let block_min_y = block_y * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH;
+            let district = district_of(block_x, block_y);
+            if terrain.is_park(block_x, block_y) {
+                continue;
+            }
+            
+            // The whole block as one lot, facing south.
+            let (min_x, min_y) = (block_min_x + LOT_MARGIN, block_min_y + LOT_MARGIN);
+            let (max_x, max_y) = (block_min_x + BLOCK_WIDTH - 1 - LOT_MARGIN, block_min_y + BLOCK_WIDTH - 1 - LOT_MARGIN);
+            let large = fitting(&prefabs, LotSize::Large, district, max_x - min_x + 1, max_y - min_y + 1);
+            if !large.is_empty() && terrain.is_clear(min_x, min_y, max_x, max_y) && rng.gen_weighted_bool(LARGE_LOT_CHANCE) {
+                let prefab = *rng.choose(&large).unwrap();
+                stamp_prefab_building(world, rng, prefab, min_x, min_y, max_x, max_y, false);
+                continue;
+            }
+            
+            for lot_y in 0..LOTS_PER_SIDE {
+                for lot_x in 0..LOTS_PER_SIDE {
+                    let min_x = block_min_x + lot_x * lot_width + LOT_MARGIN;
+                    let min_y = block_min_y + lot_y * lot_width + LOT_MARGIN;
+                    let max_x = block_min_x + (lot_x + 1) * lot_width - 1 - LOT_MARGIN;
+                    let max_y = block_min_y + (lot_y + 1) * lot_width - 1 - LOT_MARGIN;
+                    if !terrain.is_clear(min_x, min_y, max_x, max_y) {
+                        continue;
+                    }
+                    
+                    // The top row of lots faces the street to the north, the rest face south.
+                    let faces_north = lot_y == 0;
+                    let small = fitting(&prefabs, LotSize::Small, district, max_x - min_x + 1, max_y - min_y + 1);
+                    if !small.is_empty() && rng.gen_weighted_bool(PREFAB_CHANCE) {
+                        let prefab = *rng.choose(&small).unwrap();
+                        stamp_prefab_building(world, rng, prefab, min_x, min_y, max_x, max_y, faces_north);
+                        continue;
+                    }
+                    let built = match model {
+                        Some(ref model) => { stamp_wfc_building(world, rng, model, min_x, min_y, max_x, max_y, faces_north) },
+                        None            => { false }
+                    };
+                    if !built {
+                        stamp_building(world, rng, min_x, min_y, max_x, max_y, faces_north);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Bounds include the walls. Tall buildings have the same walls on every level, a flight of stairs
+// up from each level to the next along the west wall and an elevator to all of them by the east
+// wall. The safe is on the top level.
+fn stamp_building<R: Rng>(
+    world: &mut World, rng: &mut R,
+    min_x: usize, min_y: usize, max_x: usize, max_y: usize,
+    faces_north: bool
+) {
+    let id = world.buildings.len() as u32;
+    let levels = if rng.gen_weighted_bool(TALL_CHANCE) { rng.gen_range(2, MAX_LEVELS + 1) } else { 1 };
+    
+    for z in 0..levels {
+        fill_rect(world, WallTile(BrickWall),            min_x,     min_y,     max_x,     max_y,     z);
+        fill_rect(world, FloorTile(ConcreteFloor, None), min_x + 1, min_y + 1, max_x - 1, max_y - 1, z);
+        add_ceiling_lights(world, min_x, min_y, max_x, max_y, z);
+    }
+    
+    // Stairs and elevator along the back wall, so they're never in front of the door.
+    let back_y = if faces_north { max_y - 1 } else { min_y + 1 };
+    if levels > 1 {
+        for z in 0..(levels - 1) {
+            let stairs_x = min_x + 2 + 2 * z;
+            world.replace_tile(stairs_x, back_y, z,     FloorTile(StairsUpFloor, None));
+            world.replace_tile(stairs_x, back_y, z + 1, FloorTile(StairsDownFloor, None));
+        }
+        for z in 0..levels {
+            world.replace_tile(max_x - 2, back_y, z, FloorTile(ElevatorFloor, None));
+        }
+    }
+    
+    // Front door, in the middle of the wall facing the street. Most are locked.
+    let door_x = (min_x + max_x) / 2;
+    let door_y = if faces_north { min_y } else { max_y };
+    let door_lock = if rng.gen_weighted_bool(4) {
+        None
+    } else {
+        Some(Lock::new(LockKind::Key(id), rng.gen_range(1, 4)))
+    };
+    place(world, door_x, door_y, 0, Occupant::Door(Door { lock: door_lock, open: false }));
+    
+    // A safe against the back wall.
+    let (safe_y, safe_z) = (back_y, levels - 1);
+    let code = format!("{:04}", rng.gen_range(0, 10000));
+    let mut contents = Vec::new();
+    for _ in 0..rng.gen_range(1, 4) {
+        contents.push(random_item(world, rng));
+    }
+    place(world, door_x, safe_y, safe_z, Occupant::Container(Container {
+        lock: Some(Lock::new(LockKind::Code(code), rng.gen_range(1, 4))),
+        open: false,
+        contents: contents
+    }));
+    
+    // A few things lying around, on any level.
+    for _ in 0..rng.gen_range(0, 4) {
+        let x = rng.gen_range(min_x + 1, max_x);
+        let y = rng.gen_range(min_y + 2, max_y - 1);
+        let z = rng.gen_range(0, levels);
+        let item = random_item(world, rng);
+        place(world, x, y, z, Occupant::Item(item));
+    }
+    
+    world.buildings.push(Building {
+        min_x: min_x, min_y: min_y, max_x: max_x, max_y: max_y,
+        door_x: door_x, door_y: door_y, safe: Some((door_x, safe_y, safe_z)), levels: levels,
+        alarmed: rng.gen_weighted_bool(3), spawns: Vec::new(), prefab: None
+    });
+}
+
+// The building prefabs that can go on a lot of this size in this district, turned any way that
+// fits.
+fn fitting<'a>(
+    prefabs: &'a [Prefab], lot: LotSize, district: District, width: usize, height: usize
+) -> Vec<&'a Prefab> {
+    prefabs.iter().filter(|prefab| {
+        (prefab.lots.is_empty() || prefab.lots.contains(&lot)) &&
+        (prefab.districts.is_empty() || prefab.districts.contains(&district)) &&
+        !turns_fitting(prefab, width, height, false).is_empty()
+    }).collect()
+}
+
+// The quarter turns that fit the prefab on a lot width by height: end over end if that's what it
+// takes to face the street, or on its side either way.
+fn turns_fitting(prefab: &Prefab, width: usize, height: usize, faces_north: bool) -> Vec<usize> {
+    let facing = if faces_north { 2 } else { 0 };
+    [facing, 1, 3].iter().cloned().filter(|&turns| {
+        let (w, h) = if turns % 2 == 0 { (prefab.width, prefab.height) } else { (prefab.height, prefab.width) };
+        w <= width && h <= height
+    }).collect()
+}
+
+// Bounds are the lot's, without its yard. The prefab is drawn facing south, so it's turned
+// around for lots facing north, or maybe on its side if it fits that way, and mirrored half the
+// time so copies don't all look alike. It goes in the middle of the lot, against the street side.
+fn stamp_prefab_building<R: Rng>(
+    world: &mut World, rng: &mut R, prefab: &Prefab,
+    min_x: usize, min_y: usize, max_x: usize, max_y: usize,
+    faces_north: bool
+) {
+    let turns = *rng.choose(&turns_fitting(prefab, max_x - min_x + 1, max_y - min_y + 1, faces_north)).unwrap();
+    let prefab = prefab.transformed(turns, rng.gen());
+    let x = min_x + (max_x - min_x + 1 - prefab.width) / 2;
+    let y = if faces_north { min_y } else { max_y + 1 - prefab.height };
+    let (far_x, far_y) = (x + prefab.width - 1, y + prefab.height - 1);
+    
+    // The front door is whichever door is closest to the street.
+    let street_y = if faces_north { 0 } else { prefab.height - 1 };
+    let mut doors = prefab.find(|cell| cell.fixture == Some(Fixture::Door) || cell.fixture == Some(Fixture::LockedDoor));
+    doors.sort_by(|a, b| distance(a.1, street_y).cmp(&distance(b.1, street_y)));
+    let (door_x, door_y) = match doors.first() {
+        Some(&(px, py)) => { (x + px, y + py) },
+        None            => { panic!("Building prefab {} has no doors", prefab.name) }
+    };
+    let safe = prefab.find(|cell| cell.fixture == Some(Fixture::Safe)).first().map(|&(px, py)| (x + px, y + py, 0));
+    let spawns = prefab.find(|cell| cell.mark == Some(Mark::Spawn)).iter().map(|&(px, py)| (x + px, y + py)).collect();
+    
+    // In the list before it's stamped, so locked doors get keys to it.
+    world.buildings.push(Building {
+        min_x: x, min_y: y, max_x: far_x, max_y: far_y,
+        door_x: door_x, door_y: door_y, safe: safe, levels: 1,
+        alarmed: prefab.alarmed.unwrap_or(rng.gen_weighted_bool(3)), spawns: spawns,
+        prefab: Some(prefab.name.clone())
+    });
+    prefab.stamp(world, rng, x, y, 0);
+    add_ceiling_lights(world, x, y, far_x, far_y, 0);
+}
+
+// Bounds are the lot's, without its yard. The model fills a square of up to WFC_SIZE against the
+// street side, then every room walled off from outside gets a door through to somewhere that
+// isn't, and the front door is whichever way in is closest to the street. Returns false, leaving
+// the lot alone, if the model couldn't come up with anything but grass.
+fn stamp_wfc_building<R: Rng>(
+    world: &mut World, rng: &mut R, model: &wfc::Model,
+    min_x: usize, min_y: usize, max_x: usize, max_y: usize,
+    faces_north: bool
+) -> bool {
+    let (width, height) = (WFC_SIZE.min(max_x - min_x + 1), WFC_SIZE.min(max_y - min_y + 1));
+    let mut names = match model.generate(rng, width, height, "grass") {
+        Some(names) => { names },
+        None        => { return false; }
+    };
+    let x0 = min_x + (max_x - min_x + 1 - width) / 2;
+    let y0 = if faces_north { min_y } else { max_y + 1 - height };
+    let id = world.buildings.len() as u32;
+    
+    let mut border = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                border.push(y * width + x);
+            }
+        }
+    }
+    
+    // Knock a door through a wall with somewhere reachable on one side and somewhere that isn't
+    // on the other, until there aren't any.
+    let mut walls: Vec<bool> = names.iter().map(|&name| name == "brick_wall").collect();
+    let mut doors = Vec::new();
+    loop {
+        let reached = distances(&walls, width, &border);
+        let mut candidates = Vec::new();
+        for y in 1..(height - 1) {
+            for x in 1..(width - 1) {
+                let i = y * width + x;
+                let splits = |a: usize, b: usize| !walls[a] && !walls[b] && (reached[a] == usize::MAX) != (reached[b] == usize::MAX);
+                if walls[i] && (splits(i - 1, i + 1) || splits(i - width, i + width)) {
+                    candidates.push(i);
+                }
+            }
+        }
+        match rng.choose(&candidates) {
+            Some(&i) => {
+                walls[i] = false;
+                names[i] = "concrete";
+                doors.push(i);
+            },
+            None => {
+                // Anything left walled off is filled in.
+                for (i, &d) in reached.iter().enumerate() {
+                    if d == usize::MAX && !walls[i] {
+                        walls[i] = true;
+                        names[i] = "brick_wall";
+                    }
+                }
+                break;
+            }
+        }
+    }
+    
+    let floors: Vec<usize> = (0..names.len()).filter(|&i| names[i] == "concrete" && !doors.contains(&i)).collect();
+    if floors.is_empty() {
+        return false;
+    }
+    
+    // Ways in: doors, and floor open to the grass.
+    let mut entrances = doors.clone();
+    for &i in floors.iter() {
+        let (x, y) = (i % width, i / width);
+        let open = (x > 0 && names[i - 1] == "grass") || (x + 1 < width && names[i + 1] == "grass") ||
+                   (y > 0 && names[i - width] == "grass") || (y + 1 < height && names[i + width] == "grass");
+        if open {
+            entrances.push(i);
+        }
+    }
+    let street_y = if faces_north { 0 } else { height - 1 };
+    entrances.sort_by(|&a, &b| distance(a / width, street_y).cmp(&distance(b / width, street_y)));
+    let front = entrances[0];
+    
+    for (i, &name) in names.iter().enumerate() {
+        let tile = match Tile::from_name(name) {
+            Some(tile) => { tile },
+            None       => { panic!("No tile named \"{}\"", name) }
+        };
+        world.replace_tile(x0 + i % width, y0 + i / width, 0, tile);
+    }
+    for &i in doors.iter() {
+        let lock = if rng.gen_weighted_bool(4) {
+            None
+        } else {
+            Some(Lock::new(LockKind::Key(id), rng.gen_range(1, 4)))
+        };
+        place(world, x0 + i % width, y0 + i / width, 0, Occupant::Door(Door { lock: lock, open: false }));
+    }
+    
+    // The safe goes as far in from the front as it gets, with a few things lying around.
+    let from_front = distances(&walls, width, &[front]);
+    let safe = floors.iter().fold(front, |far, &i| if i != front && (far == front || from_front[i] > from_front[far]) { i } else { far });
+    let (safe_x, safe_y) = (x0 + safe % width, y0 + safe / width);
+    let occupant = prefab::make_fixture(world, rng, Fixture::Safe, safe_x, safe_y);
+    place(world, safe_x, safe_y, 0, occupant);
+    for _ in 0..rng.gen_range(0, 4) {
+        let i = *rng.choose(&floors).unwrap();
+        if i != safe && i != front {
+            let occupant = prefab::make_fixture(world, rng, Fixture::Item, x0 + i % width, y0 + i / width);
+            place(world, x0 + i % width, y0 + i / width, 0, occupant);
+        }
+    }
+    
+    let built: Vec<usize> = (0..names.len()).filter(|&i| names[i] != "grass").collect();
+    let building_min_x = x0 + built.iter().map(|&i| i % width).min().unwrap();
+    let building_max_x = x0 + built.iter().map(|&i| i % width).max().unwrap();
+    let building_min_y = y0 + built.iter().map(|&i| i / width).min().unwrap();
+    let building_max_y = y0 + built.iter().map(|&i| i / width).max().unwrap();
+    add_ceiling_lights(world, building_min_x, building_min_y, building_max_x, building_max_y, 0);
+    
+    world.buildings.push(Building {
+        min_x: building_min_x, min_y: building_min_y, max_x: building_max_x, max_y: building_max_y,
+        door_x: x0 + front % width, door_y: y0 + front / width, safe: Some((safe_x, safe_y, 0)), levels: 1,
+        alarmed: rng.gen_weighted_bool(3), spawns: Vec::new(), prefab: None
+    });
+    true
+}
+
+// Steps from the nearest start to every tile of a width wide grid, without going through walls.
+// usize::MAX for tiles that can't be reached.
+fn distances(walls: &Vec<bool>, width: usize, from: &[usize]) -> Vec<usize> {
+    let mut steps = vec![usize::MAX; walls.len()];
+    let mut frontier = Vec::new();
+    for &i in from.iter() {
+        if !walls[i] {
+            steps[i] = 0;
+            frontier.push(i);
+        }
+    }
+    let mut next = 0;
+    while next < frontier.len() {
+        let i = frontier[next];
+        next = next + 1;
+        let (x, y) = (i % width, i / width);
+        let mut neighbors = Vec::with_capacity(4);
+        if x > 0                      { neighbors.push(i - 1); }
+        if x + 1 < width              { neighbors.push(i + 1); }
+        if y > 0                      { neighbors.push(i - width); }
+        if i + width < walls.len()    { neighbors.push(i + width); }
+        for &n in neighbors.iter() {
+            if !walls[n] && steps[n] == usize::MAX {
+                steps[n] = steps[i] + 1;
+                frontier.push(n);
+            }
+        }
+    }
+    steps
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    if a > b { a - b } else { b - a }
+}
+
+// In a grid on level z, with about one per room's worth of floor. Bounds include the walls, which
+// the lights don't shine past.
+fn add_ceiling_lights(world: &mut World, min_x: usize, min_y: usize, max_x: usize, max_y: usize, z: usize) {
+    let spacing = INTERIOR_RADIUS as usize;
+    let mut y = min_y + spacing / 2;
+    while y < max_y {
+        let mut x = min_x + spacing / 2;
+        while x < max_x {
+            world.add_light(Light::bounded(x, y, INTERIOR_RADIUS, INTERIOR_INTENSITY, (min_x, min_y, max_x, max_y)), z);
+            x = x + spacing;
+        }
+        y = y + spacing;
+    }
+}
+
+pub fn lot_size_from_name(name: &str) -> Option<LotSize> {
+    match name {
+        "small" => { Some(LotSize::Small) },
+        "large" => { Some(LotSize::Large) },
+        _       => { None }
+    }
+}
+
+pub fn lot_size_name(lot: LotSize) -> &'static str {
+    match lot {
+        LotSize::Small => { "small" },
+        LotSize::Large => { "large" }
+    }
+}
+
+fn place(world: &mut World, x: usize, y: usize, z: usize, occupant: Occupant) {
+    world.replace_tile(x, y, z, FloorTile(ConcreteFloor, Some(Rc::new(RefCell::new(occupant)))));
+}
\ No newline at end of file
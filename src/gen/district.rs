@@ -0,0 +1,40 @@
+use gen::street::GRID_SIZE;
+
+// What part of town a block is in. Decides what gets built there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum District {
+    Downtown,     // The middle of the city: banks and museums.
+    Commercial,   // Around downtown: shops.
+    Residential   // The outskirts: houses.
+}
+
+// Districts ring the middle of the city, by how many blocks out a block is.
+pub fn district_of(block_x: usize, block_y: usize) -> District {
+    // Blocks sit between streets, so there's one fewer block than streets in each direction.
+    let middle = (GRID_SIZE - 2) as f32 / 2.0;
+    let out = (block_x as f32 - middle).abs().max((block_y as f32 - middle).abs());
+    if out <= 1.0 {
+        District::Downtown
+    } else if out <= 2.5 {
+        District::Commercial
+    } else {
+        District::Residential
+    }
+}
+
+pub fn district_from_name(name: &str) -> Option<District> {
+    match name {
+        "downtown"    => { Some(District::Downtown) },
+        "commercial"  => { Some(District::Commercial) },
+        "residential" => { Some(District::Residential) },
+        _             => { None }
+    }
+}
+
+pub fn district_name(district: District) -> &'static str {
+    match district {
+        District::Downtown    => { "downtown" },
+        District::Commercial  => { "commercial" },
+        District::Residential => { "residential" }
+    }
+}
\ No newline at end of file
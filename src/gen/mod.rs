@@ -1,29 +1,95 @@
 pub mod street;
+pub mod building;
+pub mod district;
+pub mod wfc;
+pub mod terrain;
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use rand::{Rng, XorShiftRng, SeedableRng};
 
 use tile::{chunk, Chunk, Tile};
 use world::World;
 
-pub fn generate(world: &mut World) {    
-    street::Grid::generate().rasterize(world);
+// How building lots are filled in. Handcrafted prefabs go on some lots either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    Procedural,  // Boxes with a door, a safe and some loot.
+    Wfc          // Shapes made by wave function collapse from the example maps. See gen::wfc.
+}
+
+// Everything random in generation draws from one generator seeded here, so a seed always
+// produces the same city.
+pub fn rng_from_seed(seed: u32) -> XorShiftRng {
+    // XorShift can't be seeded with all zeros.
+    XorShiftRng::from_seed([seed, seed ^ 0x9E3779B9, 0x243F6A88, 0xB7E15162])
+}
+
+pub fn generate(world: &mut World, seed: u32, strategy: Strategy) {    
+    let mut rng = rng_from_seed(seed);
+    
+    // The land first, then streets to suit it, then buildings wherever's left.
+    let terrain = terrain::Terrain::generate(&mut rng, world.size());
+    terrain.rasterize(world);
+    let grid = street::Grid::generate(&terrain);
+    grid.rasterize(world, &terrain);
+    world.grid = grid;
+    building::generate(world, &mut rng, strategy, &terrain);
+    place_police_stations(world, &mut rng);
+    street::place_lamps(world);
     
-    for row in world.chunks.iter() {
-        for chunk in row.iter() {
-            chunk.borrow_mut().buffer();
+    let num_chunks = world.chunks.len();
+    for chunk_y in 0..num_chunks {
+        for chunk_x in 0..num_chunks {
+            world.relight_chunk(chunk_x, chunk_y, 0);
         }
     }
+    let upper: Vec<(usize, usize, usize)> = world.upper_chunks.keys().cloned().collect();
+    for &(chunk_x, chunk_y, z) in upper.iter() {
+        world.relight_chunk(chunk_x, chunk_y, z);
+    }
+}
+
+pub const POLICE_STATIONS: usize = 3;
+
+// Stations sit at different intersections with streets leading off them. Stored in grid coords.
+fn place_police_stations<R: Rng>(world: &mut World, rng: &mut R) {
+    let mut open: Vec<(usize, usize)> = world.grid.intersections.iter().flat_map(|row| row.iter()).filter_map(|inter| {
+        let inter = inter.borrow();
+        if inter.is_open() { Some((inter.x, inter.y)) } else { None }
+    }).collect();
+    for _ in 0..POLICE_STATIONS {
+        if open.is_empty() {
+            return;
+        }
+        let i = rng.gen_range(0, open.len());
+        world.police_stations.push(open.swap_remove(i));
+    }
 }
 
 // Fills from min to max inclusive.
 pub fn fill_rect(
     world: &mut World, tile: Tile,
-    min_x: usize, min_y: usize, max_x: usize, max_y: usize
+    min_x: usize, min_y: usize, max_x: usize, max_y: usize, z: usize
 ) {
     for y in min_y..(max_y + 1) {
         for x in min_x..(max_x + 1) {
-            world.replace_tile(x, y, tile.clone());
+            world.replace_tile(x, y, z, tile.clone());
         }
     }
+}
+
+pub fn strategy_from_name(name: &str) -> Option<Strategy> {
+    match name {
+        "procedural" => { Some(Strategy::Procedural) },
+        "wfc"        => { Some(Strategy::Wfc) },
+        _            => { None }
+    }
+}
+
+pub fn strategy_name(strategy: Strategy) -> &'static str {
+    match strategy {
+        Strategy::Procedural => { "procedural" },
+        Strategy::Wfc        => { "wfc" }
+    }
 }
\ No newline at end of file
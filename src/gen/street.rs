@@ -1,14 +1,40 @@
+use std::cmp;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use super::fill_rect;
+use tile::Tile;
 use tile::Tile::FloorTile;
-use tile::FloorTileType::{GrassFloor, AsphaltFloor};
+use tile::FloorTileType::{
+    AsphaltFloor, SidewalkFloor, CurbFloor, LaneMarkingFloor, CrosswalkFloor, BridgeFloor
+};
 use world::World;
+use light::{Light, STREET_LAMP_RADIUS, STREET_LAMP_INTENSITY};
+use super::terrain::{Terrain, Ground};
 
 pub const GRID_SIZE:    usize = 10;  // How many streets wide and tall the city is.
 pub const STREET_WIDTH: usize = 15;  // How many tiles wide a street is.
 pub const BLOCK_WIDTH:  usize = 185; // How many tiles wide a city block is, not counting any adjacent streets.
+pub const LAMP_SPACING: usize = 12;  // How many tiles apart street lamps are.
+
+// Across a street, from either edge: sidewalk, then a curb, then road with a dashed line down
+// the middle.
+pub const SIDEWALK_WIDTH:   usize = 2;
+pub const CROSSWALK_LENGTH: usize = 3;  // How far crosswalks reach along the street from each end.
+pub const DASH_LENGTH:      usize = 3;  // Dashes and the gaps between them.
+
+// Streets crossing more water than this, or climbing over more hillside, are left out where the
+// city can do without them. Shorter crossings get bridges, and roads cut straight through hills.
+pub const MAX_BRIDGE_LENGTH: usize = 40;
+pub const MAX_HILL_CLIMB:    usize = 60;
+
+// The middle of the intersection at grid coords (x, y), in world coords.
+pub fn center(x: usize, y: usize) -> (usize, usize) {
+    (
+        x * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH / 2,
+        y * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH / 2
+    )
+}
 
 pub struct Grid {
     pub intersections: Vec<Vec<Rc<RefCell<Intersection>>>>
@@ -30,6 +56,7 @@ pub struct Intersection {
     pub nw: Connection
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConnectDir { N, NE, E, SE, S, SW, W, NW }
 
 pub struct Street {
@@ -40,7 +67,13 @@ pub struct Street {
 }
 
 impl Grid {
-    pub fn generate() -> Grid {
+    pub fn empty() -> Grid {
+        Grid { intersections: Vec::new() }
+    }
+    
+    // A street between every pair of neighbouring intersections, less any too costly to build
+    // over the terrain. Every intersection still on a street can reach every other.
+    pub fn generate(terrain: &Terrain) -> Grid {
         // Initialize all the intersections. They're not connected by streets yet.
         let mut grid = Grid { intersections: Vec::with_capacity(GRID_SIZE) };
         for y in 0..GRID_SIZE {
@@ -79,15 +112,174 @@ impl Grid {
             }
         }
         
+        // Keep going until nothing more can come out, since taking one street out can leave
+        // others free to go that weren't before.
+        let mut costly: Vec<Rc<RefCell<Street>>> = grid.streets().into_iter().filter(|street| {
+            let (water, hill) = street.borrow().crossing(terrain);
+            water > MAX_BRIDGE_LENGTH || hill > MAX_HILL_CLIMB
+        }).collect();
+        loop {
+            let before = costly.len();
+            costly.retain(|street| {
+                street.borrow().disconnect();
+                if grid.is_connected() {
+                    false
+                } else {
+                    street.borrow().connect(street.clone());
+                    true
+                }
+            });
+            if costly.len() == before {
+                break;
+            }
+        }
+        
         grid
     }
     
-    pub fn rasterize(&self, world: &mut World) {
+    // Each street once.
+    fn streets(&self) -> Vec<Rc<RefCell<Street>>> {
+        let mut streets = Vec::new();
+        for row in self.intersections.iter() {
+            for inter in row.iter() {
+                let inter = inter.borrow();
+                for connection in [&inter.e, &inter.s].iter() {
+                    if let Some(ref street) = **connection {
+                        streets.push(street.clone());
+                    }
+                }
+            }
+        }
+        streets
+    }
+    
+    // Whether every intersection on a street can drive to every other.
+    fn is_connected(&self) -> bool {
+        let open: Vec<(usize, usize)> = self.intersections.iter().flat_map(|row| row.iter()).filter_map(|inter| {
+            let inter = inter.borrow();
+            if inter.is_open() { Some((inter.x, inter.y)) } else { None }
+        }).collect();
+        let start = match open.first() {
+            Some(&start) => { start },
+            None         => { return true; }
+        };
+        
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::new();
+        reached.insert(start);
+        queue.push_back(start);
+        while let Some(pos) = queue.pop_front() {
+            for next in self.intersections[pos.1][pos.0].borrow().neighbors().into_iter() {
+                if reached.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached.len() == open.len()
+    }
+    
+    // The first intersection a street leads off from, going along each row from the NW corner.
+    // In grid coords.
+    pub fn first_open(&self) -> (usize, usize) {
+        for row in self.intersections.iter() {
+            for inter in row.iter() {
+                let inter = inter.borrow();
+                if inter.is_open() {
+                    return (inter.x, inter.y);
+                }
+            }
+        }
+        (0, 0)
+    }
+    
+    // The intersection on a street closest to a point in world coords. Returns grid coords.
+    pub fn nearest(&self, x: usize, y: usize) -> (usize, usize) {
+        let dist = |inter: &Intersection| {
+            let (cx, cy) = inter.center();
+            let (dx, dy) = (cx as i64 - x as i64, cy as i64 - y as i64);
+            dx * dx + dy * dy
+        };
+        let mut best = self.first_open();
+        for row in self.intersections.iter() {
+            for inter in row.iter() {
+                let inter = inter.borrow();
+                if inter.is_open() && dist(&inter) < dist(&self.intersections[best.1][best.0].borrow()) {
+                    best = (inter.x, inter.y);
+                }
+            }
+        }
+        best
+    }
+    
+    // Breadth-first search along streets. Returns the intersections to drive through in grid
+    // coords, excluding from and including to.
+    pub fn route(&self, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+        came_from.insert(from, from);
+        
+        while let Some(pos) = queue.pop_front() {
+            if pos == to {
+                let mut route = Vec::new();
+                let mut pos = to;
+                while pos != from {
+                    route.push(pos);
+                    pos = came_from[&pos];
+                }
+                route.reverse();
+                return Some(route);
+            }
+            
+            for next in self.intersections[pos.1][pos.0].borrow().neighbors().into_iter() {
+                if !came_from.contains_key(&next) {
+                    came_from.insert(next, pos);
+                    queue.push_back(next);
+                }
+            }
+        }
+        
+        None
+    }
+    
+    pub fn rasterize(&self, world: &mut World, terrain: &Terrain) {
         for row in self.intersections.iter() {
             for inter in row.iter() {
-                inter.borrow().rasterize(world);
+                inter.borrow().rasterize(world, terrain);
+            }
+        }
+    }
+}
+
+impl Street {
+    // How many tiles of water and of hillside lie along the middle of the street, from one
+    // intersection's centre to the other's.
+    fn crossing(&self, terrain: &Terrain) -> (usize, usize) {
+        let (x1, y1) = self.inter1.borrow().center();
+        let (x2, y2) = self.inter2.borrow().center();
+        let (mut water, mut hill) = (0, 0);
+        for y in y1..(y2 + 1) {
+            for x in x1..(x2 + 1) {
+                match terrain.ground_at(x, y) {
+                    Ground::Land  => {},
+                    Ground::Water => { water = water + 1; },
+                    Ground::Hill  => { hill = hill + 1; }
+                }
             }
         }
+        (water, hill)
+    }
+    
+    // Takes the street out of both its intersections.
+    fn disconnect(&self) {
+        *self.inter1.borrow_mut().connection_mut(self.inter1_dir) = None;
+        *self.inter2.borrow_mut().connection_mut(self.inter2_dir) = None;
+    }
+    
+    // Puts it back. street must be the Rc holding self.
+    fn connect(&self, street: Rc<RefCell<Street>>) {
+        *self.inter1.borrow_mut().connection_mut(self.inter1_dir) = Some(street.clone());
+        *self.inter2.borrow_mut().connection_mut(self.inter2_dir) = Some(street);
     }
 }
 
@@ -100,6 +292,42 @@ impl Intersection {
         }
     }
     
+    fn connection_mut(&mut self, dir: ConnectDir) -> &mut Connection {
+        match dir {
+            ConnectDir::N  => { &mut self.n },
+            ConnectDir::NE => { &mut self.ne },
+            ConnectDir::E  => { &mut self.e },
+            ConnectDir::SE => { &mut self.se },
+            ConnectDir::S  => { &mut self.s },
+            ConnectDir::SW => { &mut self.sw },
+            ConnectDir::W  => { &mut self.w },
+            ConnectDir::NW => { &mut self.nw }
+        }
+    }
+    
+    // Whether any street leads off from here.
+    pub fn is_open(&self) -> bool {
+        !self.neighbors().is_empty()
+    }
+    
+    // Grid coords of every intersection a street leads to from here.
+    pub fn neighbors(&self) -> Vec<(usize, usize)> {
+        let mut neighbors = Vec::new();
+        let connections = [&self.n, &self.ne, &self.e, &self.se, &self.s, &self.sw, &self.w, &self.nw];
+        for connection in connections.iter() {
+            if let Some(ref street) = **connection {
+                let street = street.borrow();
+                let (i1, i2) = (street.inter1.borrow(), street.inter2.borrow());
+                if i1.x == self.x && i1.y == self.y {
+                    neighbors.push((i2.x, i2.y));
+                } else {
+                    neighbors.push((i1.x, i1.y));
+                }
+            }
+        }
+        neighbors
+    }
+    
     // In world coords.
     fn max_x(&self) -> usize {
         self.x * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH - 1
@@ -120,33 +348,121 @@ impl Intersection {
         self.y * (STREET_WIDTH + BLOCK_WIDTH)
     }
     
-    pub fn rasterize(&self, world: &mut World) {
-        // Fill the intersection with asphalt.
-        fill_rect(
-            world,
-            FloorTile(AsphaltFloor, None),
-            self.min_x(), self.min_y(),
-            self.max_x(), self.max_y()
-        );
+    // The middle of the intersection, in world coords.
+    pub fn center(&self) -> (usize, usize) {
+        center(self.x, self.y)
+    }
+    
+    // Intersections no street leads off from aren't built at all.
+    pub fn rasterize(&self, world: &mut World, terrain: &Terrain) {
+        if self.is_open() {
+            for y in 0..STREET_WIDTH {
+                for x in 0..STREET_WIDTH {
+                    let (wx, wy) = (self.min_x() + x, self.min_y() + y);
+                    world.replace_tile(wx, wy, 0, over_terrain(terrain, wx, wy, self.tile_at(x, y)));
+                }
+            }
+        }
+        
+        if self.e.is_some() {
+            // The east street runs along x.
+            for along in 0..BLOCK_WIDTH {
+                for across in 0..STREET_WIDTH {
+                    let (wx, wy) = (self.max_x() + 1 + along, self.min_y() + across);
+                    world.replace_tile(wx, wy, 0, over_terrain(terrain, wx, wy, street_tile(across, along)));
+                }
+            }
+        }
+        
+        if self.s.is_some() {
+            // The south street runs along y.
+            for along in 0..BLOCK_WIDTH {
+                for across in 0..STREET_WIDTH {
+                    let (wx, wy) = (self.min_x() + across, self.max_y() + 1 + along);
+                    world.replace_tile(wx, wy, 0, over_terrain(terrain, wx, wy, street_tile(across, along)));
+                }
+            }
+        }
+    }
+    
+    // The tile at (x, y) relative to the intersection's NW corner. Sidewalks wrap around the
+    // corners, and run straight along any side no street leads off from, e.g. at the city's edge.
+    fn tile_at(&self, x: usize, y: usize) -> Tile {
+        let from_edge_x = cmp::min(x, STREET_WIDTH - 1 - x);
+        let from_edge_y = cmp::min(y, STREET_WIDTH - 1 - y);
         
-        if self.x < GRID_SIZE - 1 {
-          // Fill the east street with asphalt.
-          fill_rect(
-              world,
-              FloorTile(AsphaltFloor, None),
-              self.max_x() + 1, self.min_y(),
-              self.max_x() + BLOCK_WIDTH, self.max_y()
-          );
+        // How far this is from the outer edge of the nearest sidewalk. Anywhere else is road.
+        let mut depth = STREET_WIDTH;
+        if from_edge_x <= SIDEWALK_WIDTH && from_edge_y <= SIDEWALK_WIDTH {
+            depth = cmp::max(from_edge_x, from_edge_y);
+        }
+        for &(open, dist) in [
+            (self.n.is_some(), y), (self.s.is_some(), STREET_WIDTH - 1 - y),
+            (self.w.is_some(), x), (self.e.is_some(), STREET_WIDTH - 1 - x)
+        ].iter() {
+            if !open && dist < depth {
+                depth = dist;
+            }
         }
         
-        if self.y < GRID_SIZE - 1 {
-            // Fill the south street with asphalt.
-            fill_rect(
-                world,
-                FloorTile(AsphaltFloor, None),
-                self.min_x(), self.max_y() + 1,
-                self.max_x(), self.max_y() + BLOCK_WIDTH
-            );
+        if depth < SIDEWALK_WIDTH {
+            FloorTile(SidewalkFloor, None)
+        } else if depth == SIDEWALK_WIDTH {
+            FloorTile(CurbFloor, None)
+        } else {
+            FloorTile(AsphaltFloor, None)
+        }
+    }
+}
+
+// The tile across tiles from a street's edge and along tiles from the intersection it starts at.
+fn street_tile(across: usize, along: usize) -> Tile {
+    let from_edge = cmp::min(across, STREET_WIDTH - 1 - across);
+    let near_end = along < CROSSWALK_LENGTH || along >= BLOCK_WIDTH - CROSSWALK_LENGTH;
+    
+    if from_edge < SIDEWALK_WIDTH {
+        FloorTile(SidewalkFloor, None)
+    } else if from_edge == SIDEWALK_WIDTH {
+        FloorTile(CurbFloor, None)
+    } else if near_end {
+        if across % 2 == 0 { FloorTile(CrosswalkFloor, None) } else { FloorTile(AsphaltFloor, None) }
+    } else if across == STREET_WIDTH / 2 && (along / DASH_LENGTH) % 2 == 0 {
+        FloorTile(LaneMarkingFloor, None)
+    } else {
+        FloorTile(AsphaltFloor, None)
+    }
+}
+
+// Over water, anything but the paint down the middle of the road is bridge deck.
+fn over_terrain(terrain: &Terrain, x: usize, y: usize, tile: Tile) -> Tile {
+    match (terrain.ground_at(x, y), tile) {
+        (Ground::Water, FloorTile(LaneMarkingFloor, _)) => { FloorTile(LaneMarkingFloor, None) },
+        (Ground::Water, _)                              => { FloorTile(BridgeFloor, None) },
+        (_, tile)                                       => { tile }
+    }
+}
+
+// Street lamps down both sides of every street, wherever there's sidewalk or bridge to put them on.
+pub fn place_lamps(world: &mut World) {
+    let spacing = STREET_WIDTH + BLOCK_WIDTH;
+    let extent = spacing * (GRID_SIZE - 1) + STREET_WIDTH;
+    for i in 0..GRID_SIZE {
+        let near = i * spacing;
+        let far = near + STREET_WIDTH - 1;
+        let mut along = 0;
+        while along < extent {
+            for &side in [near, far].iter() {
+                // East-west streets, then north-south streets.
+                for &(x, y) in [(along, side), (side, along)].iter() {
+                    match world.tile_at(x, y, 0) {
+                        FloorTile(SidewalkFloor, _) | FloorTile(BridgeFloor, _) => {
+                            world.add_light(Light::new(x, y, STREET_LAMP_RADIUS, STREET_LAMP_INTENSITY), 0);
+                        },
+                        _ => {}
+                    }
+                }
+            }
+            along = along + LAMP_SPACING;
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,199 @@
+use rand::Rng;
+
+use tile::Tile::FloorTile;
+use tile::FloorTileType::{SidewalkFloor, WaterFloor, HillFloor};
+use world::World;
+use light::{Light, STREET_LAMP_RADIUS, STREET_LAMP_INTENSITY};
+use gen::street::{GRID_SIZE, STREET_WIDTH, BLOCK_WIDTH, LAMP_SPACING};
+
+// Rivers wander this many tiles either side of where they'd run straight, over bends about
+// RIVER_BEND tiles long.
+pub const RIVER_WIDTH:   usize = 20;
+pub const RIVER_MEANDER: f32   = 150.0;
+pub const RIVER_BEND:    usize = 250;
+
+// How far the sea reaches in from the city's edge, give or take COAST_VARIATION.
+pub const COAST_DEPTH:     usize = 120;
+pub const COAST_VARIATION: f32   = 80.0;
+
+// Noise runs from 0 to 1. Hills are wherever it's above this.
+pub const HILL_LEVEL: f32 = 0.65;
+
+// One in this many blocks is left as a park instead of being built on.
+pub const PARK_CHANCE: u32 = 8;
+pub const PATH_WIDTH:  usize = 2;  // Parks have footpaths across them, crossing in the middle.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ground {
+    Land,
+    Water,
+    Hill
+}
+
+// The lay of the land, worked out before anything's built. Streets go around or over it, and
+// buildings only go on clear ground.
+pub struct Terrain {
+    size:      usize,
+    ground:    Vec<Ground>,
+    pub parks: Vec<(usize, usize)>  // Block coords.
+}
+
+// Value noise: random values on a lattice cell tiles apart, smoothly blended in between.
+struct Noise {
+    cell:    usize,
+    width:   usize,
+    lattice: Vec<f32>
+}
+
+impl Noise {
+    // Covers size x size tiles. A height of 1 gives noise along a line.
+    fn new<R: Rng>(rng: &mut R, cell: usize, size: usize, height: usize) -> Noise {
+        let width = size / cell + 2;
+        let rows = if height == 1 { 1 } else { height / cell + 2 };
+        let lattice = (0..(width * rows)).map(|_| rng.gen::<f32>()).collect();
+        Noise { cell: cell, width: width, lattice: lattice }
+    }
+    
+    fn at(&self, x: usize, y: usize) -> f32 {
+        let (gx, gy) = (x / self.cell, y / self.cell);
+        let tx = smoothstep((x % self.cell) as f32 / self.cell as f32);
+        let ty = smoothstep((y % self.cell) as f32 / self.cell as f32);
+        let rows = self.lattice.len() / self.width;
+        let gy1 = if gy + 1 < rows { gy + 1 } else { gy };
+        let value = |gx: usize, gy: usize| self.lattice[gy * self.width + gx];
+        let top = lerp(value(gx, gy), value(gx + 1, gy), tx);
+        let bottom = lerp(value(gx, gy1), value(gx + 1, gy1), tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+impl Terrain {
+    // Either a river across the city or a stretch of coast along one edge, then hills and parks.
+    pub fn generate<R: Rng>(rng: &mut R, size: usize) -> Terrain {
+        let mut terrain = Terrain { size: size, ground: vec![Ground::Land; size * size], parks: Vec::new() };
+        
+        let bends = Noise::new(rng, RIVER_BEND, size, 1);
+        if rng.gen_weighted_bool(2) {
+            // Runs north-south or east-west, somewhere away from the edges.
+            let vertical = rng.gen_weighted_bool(2);
+            let base = rng.gen_range(size * 3 / 10, size * 7 / 10) as f32;
+            let half_width = (RIVER_WIDTH / 2) as f32;
+            for along in 0..size {
+                let middle = base + (bends.at(along, 0) * 2.0 - 1.0) * RIVER_MEANDER;
+                for across in 0..size {
+                    if (across as f32 - middle).abs() <= half_width {
+                        let (x, y) = if vertical { (across, along) } else { (along, across) };
+                        terrain.set(x, y, Ground::Water);
+                    }
+                }
+            }
+        } else {
+            // 0 to 3 is north, east, south and west.
+            let edge = rng.gen_range(0, 4);
+            for along in 0..size {
+                let depth = COAST_DEPTH as f32 + (bends.at(along, 0) * 2.0 - 1.0) * COAST_VARIATION;
+                for from_edge in 0..(depth.max(0.0) as usize) {
+                    let (x, y) = match edge {
+                        0 => { (along, from_edge) },
+                        1 => { (size - 1 - from_edge, along) },
+                        2 => { (along, size - 1 - from_edge) },
+                        _ => { (from_edge, along) }
+                    };
+                    terrain.set(x, y, Ground::Water);
+                }
+            }
+        }
+        
+        // A few octaves, each twice as fine and half as strong as the last.
+        let octaves = [
+            (Noise::new(rng, 300, size, size), 1.0),
+            (Noise::new(rng, 150, size, size), 0.5),
+            (Noise::new(rng, 75,  size, size), 0.25)
+        ];
+        let total = octaves.iter().fold(0.0, |sum, &(_, weight)| sum + weight);
+        for y in 0..size {
+            for x in 0..size {
+                let height = octaves.iter().fold(0.0, |sum, &(ref noise, weight)| sum + noise.at(x, y) * weight) / total;
+                if height > HILL_LEVEL && terrain.ground_at(x, y) == Ground::Land {
+                    terrain.set(x, y, Ground::Hill);
+                }
+            }
+        }
+        
+        for block_y in 0..(GRID_SIZE - 1) {
+            for block_x in 0..(GRID_SIZE - 1) {
+                if rng.gen_weighted_bool(PARK_CHANCE) {
+                    terrain.parks.push((block_x, block_y));
+                }
+            }
+        }
+        
+        terrain
+    }
+    
+    fn set(&mut self, x: usize, y: usize, ground: Ground) {
+        self.ground[y * self.size + x] = ground;
+    }
+    
+    pub fn ground_at(&self, x: usize, y: usize) -> Ground {
+        self.ground[y * self.size + x]
+    }
+    
+    // Whether there's nothing but flat land from min to max inclusive.
+    pub fn is_clear(&self, min_x: usize, min_y: usize, max_x: usize, max_y: usize) -> bool {
+        (min_y..(max_y + 1)).all(|y| (min_x..(max_x + 1)).all(|x| self.ground_at(x, y) == Ground::Land))
+    }
+    
+    pub fn is_park(&self, block_x: usize, block_y: usize) -> bool {
+        self.parks.contains(&(block_x, block_y))
+    }
+    
+    // Water and hills, and the footpaths and lamps in parks. Everywhere else stays grass until
+    // something's built on it.
+    pub fn rasterize(&self, world: &mut World) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                match self.ground_at(x, y) {
+                    Ground::Land  => {},
+                    Ground::Water => { world.replace_tile(x, y, 0, FloorTile(WaterFloor, None)); },
+                    Ground::Hill  => { world.replace_tile(x, y, 0, FloorTile(HillFloor, None)); }
+                }
+            }
+        }
+        
+        for &(block_x, block_y) in self.parks.iter() {
+            let min_x = block_x * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH;
+            let min_y = block_y * (STREET_WIDTH + BLOCK_WIDTH) + STREET_WIDTH;
+            let middle = BLOCK_WIDTH / 2 - PATH_WIDTH / 2;
+            for along in 0..BLOCK_WIDTH {
+                for across in middle..(middle + PATH_WIDTH) {
+                    // East-west, then north-south.
+                    self.path(world, min_x + along, min_y + across);
+                    self.path(world, min_x + across, min_y + along);
+                }
+                if along % LAMP_SPACING == 0 {
+                    for &(x, y) in [(min_x + along, min_y + middle), (min_x + middle, min_y + along)].iter() {
+                        if self.ground_at(x, y) != Ground::Water {
+                            world.add_light(Light::new(x, y, STREET_LAMP_RADIUS, STREET_LAMP_INTENSITY), 0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    // Paths stop at the water's edge.
+    fn path(&self, world: &mut World, x: usize, y: usize) {
+        if self.ground_at(x, y) != Ground::Water {
+            world.replace_tile(x, y, 0, FloorTile(SidewalkFloor, None));
+        }
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
\ No newline at end of file
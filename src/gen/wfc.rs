@@ -0,0 +1,320 @@
+use std::error::Error;
+use std::path::Path;
+use image::{self, Pixel, RgbImage};
+use rand::Rng;
+
+use tile::Tile;
+
+// The example maps the WFC generator learns from. One pixel is one tile, coloured the way
+// Tile::from_rgb reads them.
+pub static EXAMPLE_MAPS: [&'static str; 1] = ["assets/maps/test-map.png"];
+
+// Patterns are squares of this many tiles a side. Bigger ones copy the examples more faithfully,
+// but there are many more of them.
+pub const PATTERN_SIZE: usize = 3;
+
+// How many times to start over after running into a contradiction before giving up.
+const MAX_ATTEMPTS: usize = 5;
+
+// The four directions, as offsets: right, down, left, up.
+static DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+// An overlapping wave function collapse model. Every PATTERN_SIZE square of tiles in the examples,
+// turned and mirrored every way, is a pattern, weighted by how often it turns up. Two patterns can
+// sit side by side if they agree on the tiles they'd share. Generating picks one pattern per tile
+// of the output, so that every neighbouring pair can, and each tile comes out as its pattern's top
+// left.
+pub struct Model {
+    patterns:   Vec<Vec<&'static str>>,  // Sprite names, row by row.
+    weights:    Vec<f32>,
+    
+    // Indexed by pattern then direction: the patterns that can sit one step that way from it.
+    compatible: Vec<Vec<Vec<u64>>>,
+    
+    // Everything that can sit one step in each direction from at least one pattern, to save
+    // working it out for tiles where nothing's been decided yet.
+    any:        Vec<Vec<u64>>,
+    
+    // The length of a set of patterns, in u64s.
+    words:      usize
+}
+
+impl Model {
+    // Learns from the example maps, leaving out any pattern with a tile that isn't in kinds.
+    pub fn learn(paths: &[&str], kinds: &[&str]) -> Model {
+        let mut patterns: Vec<Vec<&'static str>> = Vec::new();
+        let mut weights: Vec<f32> = Vec::new();
+        for path in paths.iter() {
+            let image: RgbImage = match image::open(&Path::new(path)) {
+                Err(why) => panic!("Couldn't load example map {}: {}", path, why.description()),
+                Ok(image) => image.to_rgb()
+            };
+            let (width, height) = (image.width() as usize, image.height() as usize);
+            if width < PATTERN_SIZE || height < PATTERN_SIZE {
+                continue;
+            }
+            for y in 0..(height - PATTERN_SIZE + 1) {
+                for x in 0..(width - PATTERN_SIZE + 1) {
+                    let mut pattern = Vec::with_capacity(PATTERN_SIZE * PATTERN_SIZE);
+                    for py in 0..PATTERN_SIZE {
+                        for px in 0..PATTERN_SIZE {
+                            let (r, g, b, _) = image.get_pixel((x + px) as u32, (y + py) as u32).channels4();
+                            pattern.push(Tile::from_rgb(r, g, b).sprite_name());
+                        }
+                    }
+                    if !pattern.iter().all(|name| kinds.contains(name)) {
+                        continue;
+                    }
+                    for variant in symmetries(&pattern).into_iter() {
+                        match patterns.iter().position(|p| *p == variant) {
+                            Some(i) => { weights[i] = weights[i] + 1.0; },
+                            None    => { patterns.push(variant); weights.push(1.0); }
+                        }
+                    }
+                }
+            }
+        }
+        if patterns.is_empty() {
+            panic!("The example maps have no patterns made only of {}", kinds.join(", "));
+        }
+        
+        let words = (patterns.len() + 63) / 64;
+        let mut compatible = Vec::with_capacity(patterns.len());
+        let mut any = vec![vec![0; words]; DIRECTIONS.len()];
+        for a in patterns.iter() {
+            let mut by_direction = Vec::with_capacity(DIRECTIONS.len());
+            for (d, &(dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let mut set = vec![0; words];
+                for (i, b) in patterns.iter().enumerate() {
+                    if agrees(a, b, dx, dy) {
+                        set[i / 64] = set[i / 64] | (1 << (i % 64));
+                    }
+                }
+                for w in 0..words {
+                    any[d][w] = any[d][w] | set[w];
+                }
+                by_direction.push(set);
+            }
+            compatible.push(by_direction);
+        }
+        
+        Model { patterns: patterns, weights: weights, compatible: compatible, any: any, words: words }
+    }
+    
+    // Sprite names for width x height tiles, row by row. The outermost tiles all come out as
+    // outside, so whatever's generated sits apart from its surroundings. None if every attempt ran
+    // into a contradiction, or the examples have nothing made only of outside.
+    pub fn generate<R: Rng>(&self, rng: &mut R, width: usize, height: usize, outside: &str) -> Option<Vec<&'static str>> {
+        for _ in 0..MAX_ATTEMPTS {
+            if let Some(tiles) = self.attempt(rng, width, height, outside) {
+                return Some(tiles);
+            }
+        }
+        None
+    }
+    
+    fn attempt<R: Rng>(&self, rng: &mut R, width: usize, height: usize, outside: &str) -> Option<Vec<&'static str>> {
+        let mut all = vec![0; self.words];
+        let mut plain = vec![0; self.words];
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            all[i / 64] = all[i / 64] | (1 << (i % 64));
+            if pattern.iter().all(|&name| name == outside) {
+                plain[i / 64] = plain[i / 64] | (1 << (i % 64));
+            }
+        }
+        
+        // The patterns each tile could still have. A pattern reaches PATTERN_SIZE - 1 tiles past
+        // its own, so the margin along the right and bottom has to be outside too.
+        let margin = PATTERN_SIZE;
+        let mut wave = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let edge = x == 0 || y == 0 || x + margin >= width || y + margin >= height;
+                wave.push(if edge { plain.clone() } else { all.clone() });
+            }
+        }
+        let mut counts: Vec<u32> = wave.iter().map(|options| count_ones(options)).collect();
+        let mut changed: Vec<usize> = (0..(width * height)).collect();
+        if !self.propagate(&mut wave, &mut counts, &mut changed, width, &all) {
+            return None;
+        }
+        
+        // Ties between tiles with as few options left are broken at random.
+        let noise: Vec<f32> = (0..(width * height)).map(|_| rng.gen::<f32>() * 0.5).collect();
+        loop {
+            // Collapse the undecided tile with the fewest options left.
+            let mut least: Option<(f32, usize)> = None;
+            for (i, &count) in counts.iter().enumerate() {
+                if count > 1 {
+                    let entropy = count as f32 + noise[i];
+                    if least.map(|(e, _)| entropy < e).unwrap_or(true) {
+                        least = Some((entropy, i));
+                    }
+                }
+            }
+            let i = match least {
+                Some((_, i)) => { i },
+                None         => { break; }
+            };
+            
+            let options = members(&wave[i]);
+            let total = options.iter().fold(0.0, |sum, &p| sum + self.weights[p]);
+            let mut roll = rng.gen::<f32>() * total;
+            let mut picked = options[options.len() - 1];
+            for &p in options.iter() {
+                if roll < self.weights[p] {
+                    picked = p;
+                    break;
+                }
+                roll = roll - self.weights[p];
+            }
+            for w in 0..self.words {
+                wave[i][w] = 0;
+            }
+            wave[i][picked / 64] = 1 << (picked % 64);
+            counts[i] = 1;
+            
+            let mut changed = vec![i];
+            if !self.propagate(&mut wave, &mut counts, &mut changed, width, &all) {
+                return None;
+            }
+        }
+        
+        Some(wave.iter().map(|options| self.patterns[members(options)[0]][0]).collect())
+    }
+    
+    // Rules out whatever can't sit next to what's left around the changed tiles, and so on
+    // outwards. Returns false if some tile's left with nothing.
+    fn propagate(
+        &self, wave: &mut Vec<Vec<u64>>, counts: &mut Vec<u32>, changed: &mut Vec<usize>, width: usize, all: &Vec<u64>
+    ) -> bool {
+        let height = wave.len() / width;
+        let mut allowed = vec![0; self.words];
+        while let Some(i) = changed.pop() {
+            let (x, y) = ((i % width) as i32, (i / width) as i32);
+            for (d, &(dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+                let n = ny as usize * width + nx as usize;
+                
+                if wave[i] == *all {
+                    allowed.clone_from(&self.any[d]);
+                } else {
+                    for w in 0..self.words {
+                        allowed[w] = 0;
+                    }
+                    for p in members(&wave[i]).into_iter() {
+                        for w in 0..self.words {
+                            allowed[w] = allowed[w] | self.compatible[p][d][w];
+                        }
+                    }
+                }
+                
+                let mut narrowed = false;
+                let mut empty = true;
+                for w in 0..self.words {
+                    let left = wave[n][w] & allowed[w];
+                    if left != wave[n][w] {
+                        wave[n][w] = left;
+                        narrowed = true;
+                    }
+                    empty = empty && left == 0;
+                }
+                if empty {
+                    return false;
+                }
+                if narrowed {
+                    counts[n] = count_ones(&wave[n]);
+                    changed.push(n);
+                }
+            }
+        }
+        true
+    }
+}
+
+// The patterns in the set, lowest first.
+fn members(set: &Vec<u64>) -> Vec<usize> {
+    let mut patterns = Vec::new();
+    for (w, &word) in set.iter().enumerate() {
+        let mut bits = word;
+        while bits != 0 {
+            patterns.push(w * 64 + bits.trailing_zeros() as usize);
+            bits = bits & (bits - 1);
+        }
+    }
+    patterns
+}
+
+fn count_ones(set: &Vec<u64>) -> u32 {
+    set.iter().fold(0, |sum, w| sum + w.count_ones())
+}
+
+// Whether b can sit at (dx, dy) from a: they have the same tiles where they overlap.
+fn agrees(a: &Vec<&'static str>, b: &Vec<&'static str>, dx: i32, dy: i32) -> bool {
+    let n = PATTERN_SIZE as i32;
+    for y in 0..n {
+        for x in 0..n {
+            let (bx, by) = (x - dx, y - dy);
+            if bx >= 0 && by >= 0 && bx < n && by < n && a[(y * n + x) as usize] != b[(by * n + bx) as usize] {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// The pattern turned each quarter turn, each mirrored too.
+fn symmetries(pattern: &Vec<&'static str>) -> Vec<Vec<&'static str>> {
+    let n = PATTERN_SIZE;
+    let mut variants = Vec::with_capacity(8);
+    let mut turned = pattern.clone();
+    for _ in 0..4 {
+        let mut mirrored = Vec::with_capacity(n * n);
+        for y in 0..n {
+            for x in 0..n {
+                mirrored.push(turned[y * n + (n - 1 - x)]);
+            }
+        }
+        // Clockwise: the left column becomes the top row.
+        let mut next = Vec::with_capacity(n * n);
+        for y in 0..n {
+            for x in 0..n {
+                next.push(turned[(n - 1 - x) * n + y]);
+            }
+        }
+        variants.push(turned);
+        variants.push(mirrored);
+        turned = next;
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use gen::rng_from_seed;
+    use gen::building::WFC_SIZE;
+    use super::{Model, EXAMPLE_MAPS};
+    
+    static KINDS: [&'static str; 3] = ["grass", "concrete", "brick_wall"];
+    
+    // Learns from scratch each time, so nothing carries over between runs but the seed.
+    fn building(seed: u32) -> Option<Vec<&'static str>> {
+        let model = Model::learn(&EXAMPLE_MAPS, &KINDS);
+        model.generate(&mut rng_from_seed(seed), WFC_SIZE, WFC_SIZE, "grass")
+    }
+    
+    #[test]
+    fn same_seed_same_building() {
+        let first = building(1);
+        assert!(first.is_some());
+        assert_eq!(building(1), first);
+    }
+    
+    #[test]
+    fn different_seed_different_building() {
+        assert!(building(1) != building(2));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,181 @@
+use std::i32;
+
+use camera::Camera;
+use mission::MissionStatus;
+use sim::Sim;
+use text;
+use tile::{Tile, Occupant};
+use tile::Tile::*;
+use tile::FloorTileType::*;
+use traffic::VehicleState;
+
+// Gap between the HUD and the window's edges, in pixels.
+pub const MARGIN: f32 = 10.0;
+
+const TEXT_COLOR:   (f32, f32, f32, f32) = (1.0, 1.0, 1.0, 1.0);
+const SHADOW_COLOR: (f32, f32, f32, f32) = (0.0, 0.0, 0.0, 0.8);
+
+// Security cameras, before and after they've spotted the player.
+const CAMERA_COLOR:  (f32, f32, f32, f32) = (0.3, 0.3, 0.35, 0.9);
+const TRIPPED_COLOR: (f32, f32, f32, f32) = (0.9, 0.1, 0.1, 0.9);
+
+// Guards, before and after they've spotted the player.
+const GUARD_COLOR:   (f32, f32, f32, f32) = (0.2, 0.3, 0.8, 1.0);
+const ALERTED_COLOR: (f32, f32, f32, f32) = (0.9, 0.1, 0.1, 1.0);
+
+// Cars, and the one the player's driving.
+const CAR_COLOR:        (f32, f32, f32, f32) = (0.75, 0.6, 0.2, 1.0);
+const PLAYER_CAR_COLOR: (f32, f32, f32, f32) = (0.1, 0.8, 0.3, 1.0);
+
+// Queues the HUD's text: the time, heat, what the player's carrying, which level they're on or how
+// fast they're driving and how long the mission has left in the top left, and what's under the
+// cursor on their level in the bottom left.
+pub fn queue(text: &mut text::Renderer, sim: &Sim, hovered: Option<(usize, usize)>, height: u16) {
+    let inventory = &sim.player.inventory;
+    let mut status = format!(
+        "{}\nHeat {:.0} {}\nCarrying {} items: {:.1}/{:.0} kg, {:.1}/{:.0} L, worth ${}",
+        sim.clock,
+        sim.police.heat, stars(sim.police.wanted_level()),
+        inventory.items.len(),
+        inventory.weight(), inventory.max_weight,
+        inventory.volume(), inventory.max_volume,
+        inventory.value()
+    );
+    if sim.player.z > 0 {
+        status.push_str(&format!("\nLevel {}", sim.player.z));
+    }
+    if let Some(car) = sim.player.driving {
+        status.push_str(&format!("\nDriving at {:.0} tiles/s", sim.traffic.vehicles[car].speed));
+    }
+    if let Some(ref mission) = sim.mission {
+        if mission.status == MissionStatus::InProgress {
+            let left = mission.time_left() as u32;
+            status.push_str(&format!("\nMission: {}:{:02} left", left / 60, left % 60));
+        }
+    }
+    queue_with_shadow(text, MARGIN, MARGIN, &status);
+    
+    if let Some((x, y)) = hovered {
+        let z = sim.player.z;
+        let mut line = format!("({}, {}) {}", x, y, describe(&sim.world.tile_at(x, y, z)));
+        if sim.world.cameras.iter().any(|c| c.x == x && c.y == y && c.z == z) {
+            line.push_str(", watched by a security camera");
+        }
+        if sim.world.guards.iter().any(|g| g.x == x && g.y == y && g.z == z) {
+            line.push_str(", with a guard on it");
+        }
+        if z == 0 && sim.traffic.vehicle_at(x as i32, y as i32).is_some() {
+            line.push_str(", with a car on it");
+        }
+        let line_height = text.font.cell_height as f32;
+        queue_with_shadow(text, MARGIN, height as f32 - MARGIN - line_height, &line);
+    }
+}
+
+// The shadow keeps text readable over light tiles.
+fn queue_with_shadow(text: &mut text::Renderer, x: f32, y: f32, s: &str) {
+    text.queue(x + 1.0, y + 1.0, s, SHADOW_COLOR);
+    text.queue(x, y, s, TEXT_COLOR);
+}
+
+fn stars(wanted_level: usize) -> String {
+    (0..5).map(|i| if i < wanted_level { '*' } else { '.' }).collect()
+}
+
+fn describe(tile: &Tile) -> String {
+    let floor = match *tile {
+        WallTile(_)                    => { return "Brick wall".to_string(); },
+        EmptyTile                      => { return "Open air".to_string(); },
+        FloorTile(ConcreteFloor, _)    => { "Concrete floor" },
+        FloorTile(AsphaltFloor, _)     => { "Road" },
+        FloorTile(LaneMarkingFloor, _) => { "Road" },
+        FloorTile(CrosswalkFloor, _)   => { "Crosswalk" },
+        FloorTile(SidewalkFloor, _)    => { "Sidewalk" },
+        FloorTile(CurbFloor, _)        => { "Curb" },
+        FloorTile(GrassFloor, _)       => { "Grass" },
+        FloorTile(StairsUpFloor, _)    => { "Stairs up" },
+        FloorTile(StairsDownFloor, _)  => { "Stairs down" },
+        FloorTile(ElevatorFloor, _)    => { "Elevator" },
+        FloorTile(WaterFloor, _)       => { "Water" },
+        FloorTile(BridgeFloor, _)      => { "Bridge" },
+        FloorTile(HillFloor, _)        => { "Hillside" }
+    };
+    let occ = match tile.occupant() {
+        Some(occ) => { occ },
+        None      => { return floor.to_string(); }
+    };
+    let occ = occ.borrow();
+    let locked = match occ.lock() {
+        Some(lock) => { lock.locked },
+        None       => { false }
+    };
+    match *occ {
+        Occupant::Door(ref door) => {
+            let state = if door.open { "open" } else if locked { "locked" } else { "closed" };
+            format!("{}, {} door", floor, state)
+        },
+        Occupant::Container(ref container) => {
+            if container.open {
+                format!("{}, open safe holding {} items", floor, container.contents.len())
+            } else {
+                format!("{}, {} safe", floor, if locked { "locked" } else { "closed" })
+            }
+        },
+        Occupant::Item(ref item) => {
+            format!("{}, {} worth ${}", floor, item.name, item.value)
+        }
+    }
+}
+
+// A box in the top left corner of every security camera's tile on screen, on the player's level.
+pub fn queue_cameras(text: &mut text::Renderer, camera: &Camera, sim: &Sim) {
+    let rect = camera.visible_rect();
+    for security_camera in sim.world.cameras.iter().filter(|c| c.z == sim.player.z) {
+        let (x, y) = (security_camera.x as f32, security_camera.y as f32);
+        if x < rect.min_x - 1.0 || x > rect.max_x + 1.0 || y < rect.min_y - 1.0 || y > rect.max_y + 1.0 {
+            continue;
+        }
+        let (x0, y0) = camera.world_to_screen(x - 0.5, y - 0.5);
+        let (x1, _) = camera.world_to_screen(x + 0.5, y + 0.5);
+        let size = ((x1 - x0) * 0.4).max(3.0);
+        let color = if security_camera.tripped { TRIPPED_COLOR } else { CAMERA_COLOR };
+        text.queue_rect(x0, y0, size, size, color);
+    }
+}
+
+// A dot in the middle of every guard's tile on screen, on the player's level.
+pub fn queue_guards(text: &mut text::Renderer, camera: &Camera, sim: &Sim) {
+    let rect = camera.visible_rect();
+    for guard in sim.world.guards.iter().filter(|g| g.z == sim.player.z) {
+        let (x, y) = (guard.x as f32, guard.y as f32);
+        if x < rect.min_x - 1.0 || x > rect.max_x + 1.0 || y < rect.min_y - 1.0 || y > rect.max_y + 1.0 {
+            continue;
+        }
+        let (x0, y0) = camera.world_to_screen(x - 0.3, y - 0.3);
+        let (x1, y1) = camera.world_to_screen(x + 0.3, y + 0.3);
+        let color = if guard.alerted { ALERTED_COLOR } else { GUARD_COLOR };
+        text.queue_rect(x0, y0, x1 - x0, y1 - y0, color);
+    }
+}
+
+// Every car on screen, when the player's on the ground. Cars sit a little inside the tiles they
+// cover.
+pub fn queue_vehicles(text: &mut text::Renderer, camera: &Camera, sim: &Sim) {
+    if sim.player.z > 0 {
+        return;
+    }
+    let rect = camera.visible_rect();
+    for vehicle in sim.traffic.vehicles.iter() {
+        let tiles = vehicle.tiles();
+        let (min_x, max_x) = tiles.iter().fold((i32::MAX, i32::MIN), |(lo, hi), &(x, _)| (lo.min(x), hi.max(x)));
+        let (min_y, max_y) = tiles.iter().fold((i32::MAX, i32::MIN), |(lo, hi), &(_, y)| (lo.min(y), hi.max(y)));
+        let (min_x, min_y, max_x, max_y) = (min_x as f32, min_y as f32, max_x as f32, max_y as f32);
+        if max_x < rect.min_x - 1.0 || min_x > rect.max_x + 1.0 || max_y < rect.min_y - 1.0 || min_y > rect.max_y + 1.0 {
+            continue;
+        }
+        let (x0, y0) = camera.world_to_screen(min_x - 0.4, min_y - 0.4);
+        let (x1, y1) = camera.world_to_screen(max_x + 0.4, max_y + 0.4);
+        let color = if vehicle.state == VehicleState::Player { PLAYER_CAR_COLOR } else { CAR_COLOR };
+        text.queue_rect(x0, y0, x1 - x0, y1 - y0, color);
+    }
+}
\ No newline at end of file
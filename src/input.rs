@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::mem;
+use std::path::Path;
+use glfw;
+use glfw::{Glfw, Key, JoystickId};
+
+// Gamepad axes count as pushed past this far from the middle.
+pub const AXIS_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    PanUp, PanDown, PanLeft, PanRight,
+    ZoomIn, ZoomOut,
+    MoveUp, MoveDown, MoveLeft, MoveRight,
+    Interact, PickUp, Drop,
+    ToggleFollow,
+    Pause, Inventory, ToggleConsole, ToggleEditor,
+    MenuUp, MenuDown, Confirm, Back, Erase
+}
+
+static ACTIONS: [(&'static str, Action); 23] = [
+    ("pan_up",     Action::PanUp),
+    ("pan_down",   Action::PanDown),
+    ("pan_left",   Action::PanLeft),
+    ("pan_right",  Action::PanRight),
+    ("zoom_in",    Action::ZoomIn),
+    ("zoom_out",   Action::ZoomOut),
+    ("move_up",    Action::MoveUp),
+    ("move_down",  Action::MoveDown),
+    ("move_left",  Action::MoveLeft),
+    ("move_right", Action::MoveRight),
+    ("interact",   Action::Interact),
+    ("pick_up",    Action::PickUp),
+    ("drop",       Action::Drop),
+    ("follow",     Action::ToggleFollow),
+    ("pause",      Action::Pause),
+    ("inventory",  Action::Inventory),
+    ("console",    Action::ToggleConsole),
+    ("editor",     Action::ToggleEditor),
+    ("menu_up",    Action::MenuUp),
+    ("menu_down",  Action::MenuDown),
+    ("confirm",    Action::Confirm),
+    ("back",       Action::Back),
+    ("erase",      Action::Erase)
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    Key(Key),
+    GamepadButton(usize),
+    GamepadAxis(usize, bool)  // Axis index, and whether it's bound in the positive direction.
+}
+
+// What one frame's input came to: the actions held down, and the ones pressed this frame in the
+// order they were pressed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub held:    Vec<Action>,
+    pub pressed: Vec<Action>
+}
+
+// Turns key and gamepad input into actions. An action can have any number of bindings.
+//
+// Each frame: call begin_frame, feed it the window's key events with on_key, call poll_gamepad
+// and end_frame, then ask is_held and pressed. A frame here is one tick of the simulation.
+// Recording captures each frame's actions, and playing a recording back replaces live input with
+// it, which lets tests drive the game without a window.
+//
+// Menus run once per drawn frame rather than per tick, and while they're open the simulation
+// doesn't tick at all, so they take their presses separately with take_menu_presses.
+pub struct Input {
+    bindings:  Vec<(Action, Binding)>,
+    
+    keys_down: HashSet<Key>,
+    gamepad:   HashSet<Action>,  // Actions held on the gamepad.
+    frame:     Frame,
+    menu:      Vec<Action>,  // Presses since the last take_menu_presses.
+    
+    recording: Option<Vec<Frame>>,
+    playback:  Option<(Vec<Frame>, usize)>  // Frames, and the next to play.
+}
+
+impl Input {
+    pub fn new(bindings: Vec<(Action, Binding)>) -> Input {
+        Input {
+            bindings: bindings, keys_down: HashSet::new(), gamepad: HashSet::new(),
+            frame: Frame { held: Vec::new(), pressed: Vec::new() }, menu: Vec::new(),
+            recording: None, playback: None
+        }
+    }
+    
+    // Bindings file lines are "action input". Lines starting with # are comments. See
+    // assets/config/bindings.txt.
+    pub fn load(path: &Path) -> Input {
+        let file = match File::open(path) {
+            Err(why) => panic!("Couldn't open {}: {}", path.display(), why.description()),
+            Ok(file) => file
+        };
+        
+        let mut bindings = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = match line {
+                Err(why) => panic!("Couldn't read {}: {}", path.display(), why.description()),
+                Ok(line) => line
+            };
+            let words: Vec<&str> = line.split(' ').filter(|w| !w.is_empty()).collect();
+            if words.is_empty() || words[0].starts_with("#") {
+                continue;
+            }
+            if words.len() != 2 {
+                panic!("{} line {}: expected \"action input\"", path.display(), i + 1);
+            }
+            let action = match action_from_name(words[0]) {
+                Some(action) => { action },
+                None         => { panic!("{} line {}: no action named \"{}\"", path.display(), i + 1, words[0]) }
+            };
+            let binding = match binding_from_name(words[1]) {
+                Some(binding) => { binding },
+                None          => { panic!("{} line {}: can't bind \"{}\"", path.display(), i + 1, words[1]) }
+            };
+            bindings.push((action, binding));
+        }
+        Input::new(bindings)
+    }
+    
+    pub fn begin_frame(&mut self) {
+        self.frame.pressed.clear();
+    }
+    
+    pub fn on_key(&mut self, key: Key, action: glfw::Action) {
+        match action {
+            glfw::Action::Press => {
+                self.keys_down.insert(key);
+                for &(bound, ref binding) in self.bindings.iter() {
+                    if *binding == Binding::Key(key) {
+                        self.frame.pressed.push(bound);
+                        self.menu.push(bound);
+                    }
+                }
+            },
+            glfw::Action::Release => { self.keys_down.remove(&key); },
+            // Holding a key down scrolls through menus, but doesn't repeat in the game.
+            glfw::Action::Repeat  => {
+                for &(bound, ref binding) in self.bindings.iter() {
+                    if *binding == Binding::Key(key) {
+                        self.menu.push(bound);
+                    }
+                }
+            }
+        }
+    }
+    
+    // Reads the first gamepad, if one's plugged in. Buttons and axes count as pressed on the frame
+    // they go down or past the threshold.
+    pub fn poll_gamepad(&mut self, glfw: &Glfw) {
+        let joystick = glfw.get_joystick(JoystickId::Joystick1);
+        let mut now = HashSet::new();
+        if joystick.is_present() {
+            let axes = joystick.get_axes();
+            let buttons = joystick.get_buttons();
+            for &(action, ref binding) in self.bindings.iter() {
+                let down = match *binding {
+                    Binding::Key(_)                   => { false },
+                    Binding::GamepadButton(i)         => { i < buttons.len() && buttons[i] != 0 },
+                    Binding::GamepadAxis(i, positive) => {
+                        i < axes.len() && if positive { axes[i] > AXIS_THRESHOLD } else { axes[i] < -AXIS_THRESHOLD }
+                    }
+                };
+                if down {
+                    now.insert(action);
+                }
+            }
+        }
+        for &action in now.iter() {
+            if !self.gamepad.contains(&action) {
+                self.frame.pressed.push(action);
+                self.menu.push(action);
+            }
+        }
+        self.gamepad = now;
+    }
+    
+    // Call once the frame's input is all in, before asking about it.
+    pub fn end_frame(&mut self) {
+        if let Some((ref frames, ref mut next)) = self.playback {
+            self.frame = if *next < frames.len() {
+                frames[*next].clone()
+            } else {
+                Frame { held: Vec::new(), pressed: Vec::new() }
+            };
+            *next = *next + 1;
+        } else {
+            let mut held = Vec::new();
+            for &(action, ref binding) in self.bindings.iter() {
+                let down = match *binding {
+                    Binding::Key(key) => { self.keys_down.contains(&key) },
+                    _                 => { self.gamepad.contains(&action) }
+                };
+                if down && !held.contains(&action) {
+                    held.push(action);
+                }
+            }
+            self.frame.held = held;
+        }
+        
+        if let Some(ref mut frames) = self.recording {
+            frames.push(self.frame.clone());
+        }
+    }
+    
+    pub fn is_held(&self, action: Action) -> bool {
+        self.frame.held.contains(&action)
+    }
+    
+    pub fn pressed(&self) -> &[Action] {
+        &self.frame.pressed
+    }
+    
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+    
+    // Every action pressed since the last call, in order. Unlike pressed, these aren't recorded
+    // or played back.
+    pub fn take_menu_presses(&mut self) -> Vec<Action> {
+        mem::replace(&mut self.menu, Vec::new())
+    }
+    
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+    
+    // Returns every frame since start_recording.
+    pub fn stop_recording(&mut self) -> Vec<Frame> {
+        self.recording.take().unwrap_or(Vec::new())
+    }
+    
+    // From now on each frame comes from frames instead of the keyboard and gamepad. Once they run
+    // out, nothing is held or pressed.
+    pub fn play(&mut self, frames: Vec<Frame>) {
+        self.playback = Some((frames, 0));
+    }
+    
+    pub fn is_playing(&self) -> bool {
+        match self.playback {
+            Some((ref frames, next)) => { next < frames.len() },
+            None                     => { false }
+        }
+    }
+}
+
+pub fn action_from_name(name: &str) -> Option<Action> {
+    ACTIONS.iter().find(|&&(n, _)| n == name).map(|&(_, action)| action)
+}
+
+pub fn action_name(action: Action) -> &'static str {
+    ACTIONS.iter().find(|&&(_, a)| a == action).map(|&(n, _)| n).unwrap()
+}
+
+// Key names are GLFW's, e.g. W, Up or LeftShift. Gamepad buttons are button:<index>, and axes
+// are axis:<index>+ or axis:<index>- for the direction.
+pub fn binding_from_name(name: &str) -> Option<Binding> {
+    if name.starts_with("button:") {
+        return name["button:".len()..].parse().ok().map(|i| Binding::GamepadButton(i));
+    }
+    if name.starts_with("axis:") && name.len() > "axis:".len() + 1 {
+        let (index, sign) = name["axis:".len()..].split_at(name.len() - "axis:".len() - 1);
+        let positive = match sign {
+            "+" => { true },
+            "-" => { false },
+            _   => { return None; }
+        };
+        return index.parse().ok().map(|i| Binding::GamepadAxis(i, positive));
+    }
+    key_from_name(name).map(|key| Binding::Key(key))
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    let letters = [
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+        Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+        Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z
+    ];
+    let digits = [
+        Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4,
+        Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9
+    ];
+    let function_keys = [
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+        Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12
+    ];
+    let bytes = name.as_bytes();
+    if bytes.len() == 1 && bytes[0] >= b'A' && bytes[0] <= b'Z' {
+        return Some(letters[(bytes[0] - b'A') as usize]);
+    }
+    if bytes.len() == 1 && bytes[0] >= b'0' && bytes[0] <= b'9' {
+        return Some(digits[(bytes[0] - b'0') as usize]);
+    }
+    if name.starts_with("F") {
+        if let Ok(n) = name[1..].parse::<usize>() {
+            return if n >= 1 && n <= 12 { Some(function_keys[n - 1]) } else { None };
+        }
+    }
+    match name {
+        "Up"           => { Some(Key::Up) },
+        "Down"         => { Some(Key::Down) },
+        "Left"         => { Some(Key::Left) },
+        "Right"        => { Some(Key::Right) },
+        "Space"        => { Some(Key::Space) },
+        "Enter"        => { Some(Key::Enter) },
+        "Tab"          => { Some(Key::Tab) },
+        "Backspace"    => { Some(Key::Backspace) },
+        "Escape"       => { Some(Key::Escape) },
+        "LeftShift"    => { Some(Key::LeftShift) },
+        "RightShift"   => { Some(Key::RightShift) },
+        "LeftControl"  => { Some(Key::LeftControl) },
+        "RightControl" => { Some(Key::RightControl) },
+        "LeftAlt"      => { Some(Key::LeftAlt) },
+        "RightAlt"     => { Some(Key::RightAlt) },
+        "Minus"        => { Some(Key::Minus) },
+        "GraveAccent"  => { Some(Key::GraveAccent) },
+        "Equal"        => { Some(Key::Equal) },
+        "PageUp"       => { Some(Key::PageUp) },
+        "PageDown"     => { Some(Key::PageDown) },
+        _              => { None }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub id:          u32,
+    pub name:        String,
+    pub weight:      f32, // Kilograms.
+    pub volume:      f32, // Litres.
+    pub value:       u32, // What it's worth on the open market, in dollars.
+    pub fence_price: u32, // What a fence will actually pay for it.
+    pub heat:        f32  // How much attention carrying or fencing it draws. 0 is none.
+}
+
+pub struct Inventory {
+    pub max_weight: f32,
+    pub max_volume: f32,
+    pub items:      Vec<Item>
+}
+
+// What the player walked out of a building with.
+pub struct HaulSummary {
+    pub building: usize, // Index into world.buildings.
+    pub items:    Vec<Item>
+}
+
+impl Item {
+    pub fn new(
+        id: u32, name: &str, weight: f32, volume: f32, value: u32, fence_price: u32, heat: f32
+    ) -> Item {
+        Item {
+            id: id, name: name.to_string(), weight: weight, volume: volume,
+            value: value, fence_price: fence_price, heat: heat
+        }
+    }
+}
+
+impl Inventory {
+    pub fn new(max_weight: f32, max_volume: f32) -> Inventory {
+        Inventory { max_weight: max_weight, max_volume: max_volume, items: Vec::new() }
+    }
+    
+    pub fn weight(&self) -> f32 {
+        self.items.iter().fold(0.0, |sum, item| sum + item.weight)
+    }
+    
+    pub fn volume(&self) -> f32 {
+        self.items.iter().fold(0.0, |sum, item| sum + item.volume)
+    }
+    
+    pub fn value(&self) -> u32 {
+        self.items.iter().fold(0, |sum, item| sum + item.value)
+    }
+    
+    pub fn heat(&self) -> f32 {
+        self.items.iter().fold(0.0, |sum, item| sum + item.heat)
+    }
+    
+    pub fn can_carry(&self, item: &Item) -> bool {
+        self.weight() + item.weight <= self.max_weight &&
+        self.volume() + item.volume <= self.max_volume
+    }
+    
+    // Gives the item back if it doesn't fit.
+    pub fn add(&mut self, item: Item) -> Result<(), Item> {
+        if self.can_carry(&item) {
+            self.items.push(item);
+            Ok(())
+        } else {
+            Err(item)
+        }
+    }
+    
+    pub fn remove(&mut self, index: usize) -> Option<Item> {
+        if index < self.items.len() {
+            Some(self.items.remove(index))
+        } else {
+            None
+        }
+    }
+    
+    pub fn ids(&self) -> Vec<u32> {
+        self.items.iter().map(|item| item.id).collect()
+    }
+    
+    // Everything carried now that wasn't among before_ids.
+    pub fn haul_since(&self, before_ids: &[u32], building: usize) -> HaulSummary {
+        HaulSummary {
+            building: building,
+            items: self.items.iter().filter(|item| !before_ids.contains(&item.id)).cloned().collect()
+        }
+    }
+}
+
+impl HaulSummary {
+    pub fn value(&self) -> u32 {
+        self.items.iter().fold(0, |sum, item| sum + item.value)
+    }
+    
+    pub fn fence_price(&self) -> u32 {
+        self.items.iter().fold(0, |sum, item| sum + item.fence_price)
+    }
+    
+    pub fn heat(&self) -> f32 {
+        self.items.iter().fold(0.0, |sum, item| sum + item.heat)
+    }
+}
+
+impl fmt::Display for HaulSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Haul: {} items", self.items.len()));
+        for item in self.items.iter() {
+            try!(writeln!(f, "  {} (worth ${}, fences for ${})", item.name, item.value, item.fence_price));
+        }
+        write!(
+            f, "Total: worth ${}, fences for ${}, heat {:.1}",
+            self.value(), self.fence_price(), self.heat()
+        )
+    }
+}
\ No newline at end of file
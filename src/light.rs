@@ -0,0 +1,47 @@
+// No light reaches farther than this many tiles, so lights only need checking against nearby
+// chunks.
+pub const MAX_RADIUS: f32 = 12.0;
+
+pub const STREET_LAMP_RADIUS:    f32 = 8.0;
+pub const STREET_LAMP_INTENSITY: f32 = 0.9;
+pub const INTERIOR_RADIUS:       f32 = 12.0;
+pub const INTERIOR_INTENSITY:    f32 = 0.7;
+
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub x:         usize,
+    pub y:         usize,
+    pub radius:    f32,
+    pub intensity: f32, // Brightness at the source, from 0 to 1.
+    
+    // The min and max x and y the light reaches, inclusive, e.g. the walls of the building it's
+    // in, so it doesn't shine through them. None if it reaches all the way round.
+    pub bounds:    Option<(usize, usize, usize, usize)>
+}
+
+impl Light {
+    pub fn new(x: usize, y: usize, radius: f32, intensity: f32) -> Light {
+        Light { x: x, y: y, radius: radius, intensity: intensity, bounds: None }
+    }
+    
+    pub fn bounded(x: usize, y: usize, radius: f32, intensity: f32, bounds: (usize, usize, usize, usize)) -> Light {
+        Light { x: x, y: y, radius: radius, intensity: intensity, bounds: Some(bounds) }
+    }
+    
+    // Brightness at (x, y), falling off linearly to nothing at the radius or the bounds.
+    pub fn brightness_at(&self, x: usize, y: usize) -> f32 {
+        if let Some((min_x, min_y, max_x, max_y)) = self.bounds {
+            if x < min_x || x > max_x || y < min_y || y > max_y {
+                return 0.0;
+            }
+        }
+        let dx = self.x as f32 - x as f32;
+        let dy = self.y as f32 - y as f32;
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist >= self.radius {
+            0.0
+        } else {
+            self.intensity * (1.0 - dist / self.radius)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,125 @@
+use sound::Sound;
+
+// Seconds it takes to pick or crack a lock of difficulty 1. Harder locks scale linearly.
+pub const PICK_SECONDS:  f32 = 4.0;
+pub const CRACK_SECONDS: f32 = 20.0;
+
+// How far, in tiles, the sounds of picking and cracking carry.
+pub const PICK_LOUDNESS:  f32 = 3.0;
+pub const CRACK_LOUDNESS: f32 = 6.0;
+
+// How often, in seconds, a lock being worked on makes a sound.
+const SOUND_INTERVAL: f32 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockKind {
+    Key(u32),      // Opens with the key of this id.
+    Keycard(u8),   // Opens with any keycard of at least this clearance level.
+    Code(String)   // Opens by entering this code.
+}
+
+#[derive(Debug, Clone)]
+pub struct Lock {
+    pub kind:       LockKind,
+    pub locked:     bool,
+    pub difficulty: u8
+}
+
+// The keys, keycards and codes someone carries.
+#[derive(Debug, Clone, Default)]
+pub struct KeyRing {
+    pub keys:      Vec<u32>,
+    pub keycards:  Vec<u8>,
+    pub codes:     Vec<String>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockActionKind {
+    Pick,   // Key locks, with a lockpick.
+    Crack   // Code locks, by listening to the dial.
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LockActionStatus {
+    InProgress,
+    Done
+}
+
+// An attempt to defeat a lock without the right credentials. Takes simulated time and makes
+// noise the whole time it's being worked on.
+#[derive(Debug, Clone)]
+pub struct LockAction {
+    pub kind:      LockActionKind,
+    pub x:         usize,
+    pub y:         usize,
+    pub remaining: f32,
+    since_sound:   f32
+}
+
+impl Lock {
+    pub fn new(kind: LockKind, difficulty: u8) -> Lock {
+        Lock { kind: kind, locked: true, difficulty: difficulty }
+    }
+}
+
+impl KeyRing {
+    pub fn new() -> KeyRing {
+        KeyRing { keys: Vec::new(), keycards: Vec::new(), codes: Vec::new() }
+    }
+    
+    pub fn can_open(&self, lock: &Lock) -> bool {
+        match lock.kind {
+            LockKind::Key(id)       => { self.keys.contains(&id) },
+            LockKind::Keycard(lvl)  => { self.keycards.iter().any(|card| *card >= lvl) },
+            LockKind::Code(ref c)   => { self.codes.iter().any(|known| known == c) }
+        }
+    }
+    
+    // True if the lock is open already or these credentials open it.
+    pub fn has_access(&self, lock: Option<&Lock>) -> bool {
+        match lock {
+            None       => { true },
+            Some(lock) => { !lock.locked || self.can_open(lock) }
+        }
+    }
+}
+
+impl LockAction {
+    // Returns None if the lock can't be defeated this way, e.g. keycard readers.
+    pub fn begin(lock: &Lock, x: usize, y: usize) -> Option<LockAction> {
+        let (kind, seconds) = match lock.kind {
+            LockKind::Key(_)     => { (LockActionKind::Pick,  PICK_SECONDS) },
+            LockKind::Code(_)    => { (LockActionKind::Crack, CRACK_SECONDS) },
+            LockKind::Keycard(_) => { return None; }
+        };
+        Some(LockAction {
+            kind: kind, x: x, y: y,
+            remaining: seconds * lock.difficulty as f32,
+            since_sound: SOUND_INTERVAL
+        })
+    }
+    
+    pub fn loudness(&self) -> f32 {
+        match self.kind {
+            LockActionKind::Pick  => { PICK_LOUDNESS },
+            LockActionKind::Crack => { CRACK_LOUDNESS }
+        }
+    }
+    
+    // Advances the attempt by dt seconds, pushing any sounds it makes onto sounds. When this
+    // returns Done, the caller should unlock the lock.
+    pub fn tick(&mut self, dt: f32, sounds: &mut Vec<Sound>) -> LockActionStatus {
+        self.since_sound = self.since_sound + dt;
+        if self.since_sound >= SOUND_INTERVAL {
+            self.since_sound = 0.0;
+            sounds.push(Sound::new(self.x, self.y, self.loudness()));
+        }
+        
+        self.remaining = self.remaining - dt;
+        if self.remaining <= 0.0 {
+            LockActionStatus::Done
+        } else {
+            LockActionStatus::InProgress
+        }
+    }
+}
\ No newline at end of file
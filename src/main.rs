@@ -6,6 +6,7 @@ extern crate gl;
 extern crate image;
 extern crate cgmath;
 extern crate libc;
+extern crate rand;
 
 mod macros;
 mod glutil;
@@ -13,19 +14,70 @@ mod tile;
 mod camera;
 mod gen;
 mod world;
+mod sound;
+mod lock;
+mod path;
+mod player;
+mod inventory;
+mod building;
+mod security;
+mod mission;
+mod police;
+mod traffic;
+mod clock;
+mod light;
+mod input;
+mod sim;
+mod replay;
+mod minimap;
+mod text;
+mod hud;
+mod ui;
+mod menu;
+mod console;
+mod debug;
+mod prefab;
+mod editor;
 
+use std::env;
 use std::path::Path;
 use std::cell::RefCell;
 use std::rc::Rc;
-use glfw::{Context, Key, Action};
+use glfw::{Context, Action, MouseButton, WindowEvent};
 use image::RgbImage;
 use cgmath::Vector2;
 
 use world::World;
 use tile::Chunk;
-use camera::Camera;
+use camera::{Camera, Rect};
+use input::{Input, Action as InputAction};
+use sim::{Sim, TICK};
+use replay::Replay;
+use minimap::Minimap;
+use ui::Ui;
+use menu::{Menus, Command};
+use console::Console;
+use debug::Overlays;
+use editor::Editor;
+
+// How much one notch of the scroll wheel zooms in.
+const ZOOM_PER_SCROLL: f32 = 1.1;
+
+// The most real time the simulation catches up on in one frame, e.g. after the window was dragged.
+const MAX_FRAME_TIME: f32 = 0.25;
 
 fn main() {
+    // Run with --record <file> to save this session's input for replaying, and with
+    // --replay <file> to re-run a saved session headlessly and check it ends the same way.
+    let args: Vec<String> = env::args().collect();
+    let mut record_path = None;
+    if args.len() == 3 && args[1] == "--replay" {
+        replay(&Path::new(&args[2]));
+        return;
+    } else if args.len() == 3 && args[1] == "--record" {
+        record_path = Some(args[2].clone());
+    }
+    
     println!("Initing GLFW");
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).ok().expect("Failed to init glfw.");
     
@@ -39,6 +91,10 @@ fn main() {
     ).expect("Failed to create GLFW window.");
     
     window.set_key_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_scroll_polling(true);
+    window.set_char_polling(true);
     window.make_current();
     
     // Load the external functions. From the gl-rs crate.
@@ -59,62 +115,255 @@ fn main() {
     println!("Loading tile program");
     let tile_program = tile::Program::new();
     
-    let mut world = World::new(&tile_program);
+    println!("Generating world");
+    let seed: u32 = 1;
+    let mut sim = Sim::new(seed, gen::Strategy::Procedural);
+    
+    // Tiles are centred on their coords, so the world reaches half a tile past the first and last.
+    let size = sim.world.size() as f32;
+    camera.bounds = Some(Rect { min_x: -0.5, min_y: -0.5, max_x: size - 0.5, max_y: size - 0.5 });
+    camera.follow(sim.player.x as f32, sim.player.y as f32);
+    camera.snap();
+    
+    println!("Uploading chunks");
+    let mut tile_renderer = tile::Renderer::new(
+        &tile_program, &sim.world,
+        tile::Atlas::load(&Path::new("assets/tiles/atlas.png"), &Path::new("assets/tiles/sprites.txt"))
+    );
     
     /*println!("Loading test image");
     let image_buf: RgbImage = image::open(&Path::new("assets/maps/test-map.png")).unwrap().to_rgb();
     let (img_w, img_h) = image_buf.dimensions();
     println!("Image w: {}, image h: {}", img_w, img_h);
-    let chunk = Chunk::from_image_buffer(&tile_program, 0, 0, &image_buf, 0, 0);*/
-    //let chunk = Chunk::blank(&tile_program, 0, 0);
+    let chunk = Chunk::from_image_buffer(0, 0, &image_buf, 0, 0);*/
+    //let chunk = Chunk::blank(0, 0);
     //world.chunks[0].push(Rc::new(RefCell::new(chunk)));
     
+    println!("Loading text program");
+    let text_program = text::Program::new();
+    let mut ui = Ui::new(text::Renderer::new(
+        &text_program,
+        text::Font::load(&Path::new("assets/fonts/mono.png"), &Path::new("assets/fonts/mono.txt"))
+    ));
+    let mut menus = Menus::new(seed);
+    let mut console = Console::new();
+    let mut overlays = Overlays::new();
+    let mut editor = Editor::new();
+    
+    println!("Drawing minimap");
+    let mut minimap = Minimap::new(&sim.world);
+    
+    // Where the cursor is in screen coords, and whether the right button is down to drag the view.
+    let mut cursor: (f64, f64) = window.get_cursor_pos();
+    let mut dragging = false;
+    
+    let mut input = Input::load(&Path::new("assets/config/bindings.txt"));
+    
     println!("Starting main loop");
+    let mut last_time = glfw.get_time();
+    let mut unsimulated: f32 = 0.0;  // Real time the simulation hasn't caught up on yet.
     while !window.should_close() {
+        let now = glfw.get_time();
+        let dt = (now - last_time) as f32;
+        last_time = now;
+        overlays.record_frame(dt);
+        unsimulated = unsimulated + if dt > MAX_FRAME_TIME { MAX_FRAME_TIME } else { dt };
+        let ambient = sim.clock.ambient();
+        let paused = menus.is_paused() || console.open || editor.active;
+        // Whether clicks and scrolling go to the UI rather than the world.
+        let ui_has_mouse = menus.is_paused() || console.open || editor.covers(cursor.0 as f32, cursor.1 as f32);
+        
         let (width, height) = window.get_size();
         camera.resize(width as u16, height as u16);
+        if camera.is_following() {
+            camera.follow(sim.player.x as f32, sim.player.y as f32);
+        }
+        camera.update(dt);
         
         unsafe {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
         
-        for chunks in world.chunks.iter() {
-            for chunk in chunks.iter() {
-                chunk.borrow().draw(&tile_program, &camera);
-            }
+        let remeshed = tile_renderer.remesh(&mut sim.world);
+        minimap.update_chunks(&sim.world, &remeshed);
+        let under_cursor = tile_at_cursor(&sim.world, &camera, cursor);
+        tile_renderer.hovered = if ui_has_mouse { None } else { under_cursor };
+        editor.level = sim.player.z;
+        
+        // Only draw chunks on screen, up to the player's level.
+        let visible = sim.world.chunks_in_rect(&camera.visible_rect());
+        tile_renderer.draw(&tile_program, &camera, ambient, &visible, sim.player.z);
+        minimap.draw(&camera, &sim);
+        overlays.queue(&mut ui.text, &camera, &sim, tile_renderer.hovered);
+        hud::queue_cameras(&mut ui.text, &camera, &sim);
+        hud::queue_guards(&mut ui.text, &camera, &sim);
+        hud::queue_vehicles(&mut ui.text, &camera, &sim);
+        hud::queue(&mut ui.text, &sim, tile_renderer.hovered, camera.height);
+        
+        // Menus, the console and the editor go on top of the HUD. The console and editor only
+        // open during play.
+        ui.begin(camera.width, camera.height, input.take_menu_presses());
+        let command = if console.open {
+            console.update(&mut ui, &mut sim, &mut camera, &mut overlays, under_cursor)
+        } else if !menus.is_paused() && ui.pressed(InputAction::ToggleConsole) {
+            console.open = true;
+            None
+        } else if editor.active {
+            editor.update(&mut ui, &mut sim.world, &camera, under_cursor);
+            None
+        } else if !menus.is_paused() && ui.pressed(InputAction::ToggleEditor) {
+            editor.active = true;
+            None
+        } else {
+            menus.update(&mut ui, &sim)
+        };
+        ui.end();
+        ui.draw(&text_program);
+        
+        match command {
+            Some(Command::Start(new_seed, strategy)) => {
+                // The world on show was only ever a backdrop for the menu, but it's already
+                // generated, so keep it if the seed and generator are the same.
+                if new_seed != sim.seed || strategy != sim.strategy || sim.ticks > 0 {
+                    println!("Generating world");
+                    sim = Sim::new(new_seed, strategy);
+                    tile_renderer = tile::Renderer::new(
+                        &tile_program, &sim.world,
+                        tile::Atlas::load(&Path::new("assets/tiles/atlas.png"), &Path::new("assets/tiles/sprites.txt"))
+                    );
+                    minimap = Minimap::new(&sim.world);
+                    camera.follow(sim.player.x as f32, sim.player.y as f32);
+                    camera.snap();
+                    menus.new_game();
+                }
+                if record_path.is_some() {
+                    input.start_recording();
+                }
+            },
+            Some(Command::Quit) => { window.set_should_close(true); },
+            None                => {}
         }
         
         window.swap_buffers();
         
         glfw.poll_events();
-        glfw::flush_messages(&events);
-        
-        // Pan camera with W and S.
-        if window.get_key(Key::W) == Action::Press {
-            let zoom = camera.zoom();
-            camera.translate(Vector2::new(0.0, 20.0 / zoom));
-        }
-        if window.get_key(Key::S) == Action::Press {
-            let zoom = camera.zoom();
-            camera.translate(Vector2::new(0.0, -20.0 / zoom));
+        for (_, event) in glfw::flush_messages(&events) {
+            match event {
+                WindowEvent::Key(key, _, action, _) => { input.on_key(key, action); },
+                WindowEvent::Char(c)                => { ui.on_char(c); },
+                
+                // With a menu open, the mouse is the menu's.
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) if ui_has_mouse => {
+                    ui.on_click();
+                },
+                WindowEvent::Scroll(_, amount) if ui_has_mouse => {
+                    ui.on_scroll(amount as f32);
+                },
+                
+                // In the editor, the left button paints.
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) if editor.active => {
+                    let at = tile_at_cursor(&sim.world, &camera, cursor);
+                    editor.press(&mut sim.world, at);
+                },
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Release, _) if editor.active => {
+                    let at = tile_at_cursor(&sim.world, &camera, cursor);
+                    editor.release(&mut sim.world, at);
+                },
+                
+                // Click to select a tile, drag with the right button to pan and scroll to zoom
+                // in on the cursor.
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => {
+                    tile_renderer.selected = tile_at_cursor(&sim.world, &camera, cursor);
+                },
+                WindowEvent::MouseButton(MouseButton::Button2, action, _) => {
+                    dragging = action != Action::Release;
+                },
+                WindowEvent::CursorPos(x, y) => {
+                    if dragging {
+                        camera.pan_by_pixels((x - cursor.0) as f32, (y - cursor.1) as f32);
+                    }
+                    cursor = (x, y);
+                    ui.on_cursor(x as f32, y as f32);
+                },
+                WindowEvent::Scroll(_, amount) => {
+                    camera.zoom_at(ZOOM_PER_SCROLL.powf(amount as f32), cursor.0 as f32, cursor.1 as f32);
+                },
+                _ => {}
+            }
         }
         
-        // Pan camera with A and D.
-        if window.get_key(Key::A) == Action::Press {
-            let zoom = camera.zoom();
-            camera.translate(Vector2::new(20.0 / zoom, 0.0));
-        }
-        if window.get_key(Key::D) == Action::Press {
-            let zoom = camera.zoom();
-            camera.translate(Vector2::new(-20.0 / zoom, 0.0));
-        }
+        input.poll_gamepad(&glfw);
         
-        // Zoom camera with Z and X.
-        if window.get_key(Key::Z) == Action::Press {
-            camera.zoom_by(1.05);
+        // The simulation stands still while a menu's open, and forgets what was pressed in it.
+        if paused {
+            input.begin_frame();
+            unsimulated = 0.0;
         }
-        if window.get_key(Key::X) == Action::Press {
-            camera.zoom_by(0.9523809524);
+        
+        // Key presses since the last tick all land on the next one.
+        while unsimulated >= TICK {
+            input.end_frame();
+            sim.tick(input.frame());
+            
+            if input.pressed().contains(&InputAction::ToggleFollow) {
+                if camera.is_following() {
+                    camera.unfollow();
+                } else {
+                    camera.follow(sim.player.x as f32, sim.player.y as f32);
+                }
+            }
+            
+            // Pan camera.
+            if input.is_held(InputAction::PanUp) {
+                let zoom = camera.zoom();
+                camera.translate(Vector2::new(0.0, 20.0 / zoom));
+            }
+            if input.is_held(InputAction::PanDown) {
+                let zoom = camera.zoom();
+                camera.translate(Vector2::new(0.0, -20.0 / zoom));
+            }
+            if input.is_held(InputAction::PanLeft) {
+                let zoom = camera.zoom();
+                camera.translate(Vector2::new(20.0 / zoom, 0.0));
+            }
+            if input.is_held(InputAction::PanRight) {
+                let zoom = camera.zoom();
+                camera.translate(Vector2::new(-20.0 / zoom, 0.0));
+            }
+            
+            // Zoom camera.
+            if input.is_held(InputAction::ZoomIn) {
+                camera.zoom_by(1.05);
+            }
+            if input.is_held(InputAction::ZoomOut) {
+                camera.zoom_by(0.9523809524);
+            }
+            
+            input.begin_frame();
+            unsimulated = unsimulated - TICK;
         }
     }
+    
+    if let Some(path) = record_path {
+        let replay = Replay { seed: sim.seed, strategy: sim.strategy, frames: input.stop_recording(), hash: sim.state_hash() };
+        replay.save(&Path::new(&path));
+        println!("Recorded {} ticks to {}", replay.frames.len(), path);
+    }
+}
+
+// Re-runs a recorded session and panics if it doesn't end in the same state.
+fn replay(path: &Path) {
+    let replay = Replay::load(path);
+    println!("Replaying {} ticks from {}", replay.frames.len(), path.display());
+    let hash = replay.run();
+    if hash != replay.hash {
+        panic!("Replay diverged: ended on state hash {:016x}, recorded {:016x}", hash, replay.hash);
+    }
+    println!("Replay matches: {:016x}", hash);
+}
+
+// The tile under the cursor, if it's in the world.
+fn tile_at_cursor(world: &World, camera: &Camera, cursor: (f64, f64)) -> Option<(usize, usize)> {
+    let (x, y) = camera.screen_to_tile(cursor.0 as f32, cursor.1 as f32);
+    if world.in_bounds(x, y) { Some((x as usize, y as usize)) } else { None }
 }
@@ -0,0 +1,222 @@
+use std::usize;
+
+use gen::{self, Strategy};
+use input::Action;
+use mission::MissionStatus;
+use sim::Sim;
+use ui::{Ui, ListState};
+
+// Panel sizes, in pixels.
+const MENU_WIDTH:  f32 = 360.0;
+const MENU_HEIGHT: f32 = 300.0;
+const WIDE_WIDTH:  f32 = 640.0;
+const WIDE_HEIGHT: f32 = 480.0;
+
+// Lines of text showing in logs and lists.
+const LOG_ROWS:    usize = 18;
+const LIST_ROWS:   usize = 14;
+
+// Seeds are u32s, so never more than 10 digits.
+const MAX_SEED_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Screen {
+    MainMenu,
+    Briefing,
+    Playing,
+    Paused,
+    Inventory,
+    ConfirmQuit,
+    MissionOver
+}
+
+// What the game has to do about what the player picked in a menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Start(u32, Strategy),  // Start a heist in the city generated from this seed, this way.
+    Quit
+}
+
+// Which menu is open, and what the menus remember between frames. The simulation only runs
+// while the screen is Playing.
+pub struct Menus {
+    pub screen:        Screen,
+    seed:              String,
+    seed_error:        bool,
+    strategy:          Strategy,
+    briefing_scroll:   usize,
+    log_scroll:        usize,
+    inventory:         ListState,
+    seen_mission_over: bool
+}
+
+impl Menus {
+    pub fn new(seed: u32) -> Menus {
+        Menus {
+            screen: Screen::MainMenu, seed: format!("{}", seed), seed_error: false,
+            strategy: Strategy::Procedural,
+            briefing_scroll: 0, log_scroll: usize::MAX, inventory: Default::default(),
+            seen_mission_over: false
+        }
+    }
+    
+    pub fn is_paused(&self) -> bool {
+        self.screen != Screen::Playing
+    }
+    
+    // Forgets everything about the last game, for starting another.
+    pub fn new_game(&mut self) {
+        self.seen_mission_over = false;
+        self.briefing_scroll = 0;
+        self.log_scroll = usize::MAX;
+        self.inventory = Default::default();
+    }
+    
+    fn open(&mut self, ui: &mut Ui, screen: Screen) {
+        self.screen = screen;
+        ui.reset_focus();
+    }
+    
+    // Lays out the open menu. Call between ui.begin and ui.end.
+    pub fn update(&mut self, ui: &mut Ui, sim: &Sim) -> Option<Command> {
+        match self.screen {
+            Screen::MainMenu    => { self.main_menu(ui) },
+            Screen::Briefing    => { self.briefing(ui, sim); None },
+            Screen::Playing     => { self.playing(ui, sim); None },
+            Screen::Paused      => { self.paused(ui, sim); None },
+            Screen::Inventory   => { self.inventory(ui, sim); None },
+            Screen::ConfirmQuit => {
+                match dialogue(ui, "Quit", "Walk away from the heist?", &["Keep playing", "Quit"]) {
+                    Some(1) => { Some(Command::Quit) },
+                    Some(_) => { self.open(ui, Screen::Paused); None },
+                    None    => { None }
+                }
+            },
+            Screen::MissionOver => {
+                let report = sim.log.last().map(|s| &s[..]).unwrap_or("");
+                match dialogue(ui, "Heist over", report, &["Keep exploring", "Quit"]) {
+                    Some(1) => { Some(Command::Quit) },
+                    Some(_) => { self.open(ui, Screen::Playing); None },
+                    None    => { None }
+                }
+            }
+        }
+    }
+    
+    fn main_menu(&mut self, ui: &mut Ui) -> Option<Command> {
+        ui.begin_panel("Capital Larceny", MENU_WIDTH, MENU_HEIGHT);
+        ui.label("City seed:");
+        let mut start = ui.text_input(&mut self.seed, MAX_SEED_LEN);
+        if self.seed_error {
+            ui.label("The seed has to be a whole number.");
+        }
+        if ui.button(&format!("Generator: {}", gen::strategy_name(self.strategy))) {
+            self.strategy = match self.strategy {
+                Strategy::Procedural => { Strategy::Wfc },
+                Strategy::Wfc        => { Strategy::Procedural }
+            };
+        }
+        start = ui.button("Start heist") || start;
+        let quit = ui.button("Quit");
+        
+        if quit {
+            return Some(Command::Quit);
+        }
+        if start {
+            match self.seed.trim().parse() {
+                Ok(seed) => {
+                    self.seed_error = false;
+                    self.new_game();
+                    self.open(ui, Screen::Briefing);
+                    return Some(Command::Start(seed, self.strategy));
+                },
+                Err(_) => { self.seed_error = true; }
+            }
+        }
+        None
+    }
+    
+    fn briefing(&mut self, ui: &mut Ui, sim: &Sim) {
+        ui.begin_panel("Mission briefing", WIDE_WIDTH, WIDE_HEIGHT);
+        let briefing = match sim.mission {
+            Some(ref mission) => { format!("{}", mission) },
+            None              => { "No job tonight. Have a look around the city.".to_string() }
+        };
+        ui.log(&[briefing], LOG_ROWS, &mut self.briefing_scroll);
+        if ui.button("Begin") || ui.pressed(Action::Back) {
+            self.open(ui, Screen::Playing);
+        }
+    }
+    
+    // No panel, but watches for the keys that open menus, and for the heist ending.
+    fn playing(&mut self, ui: &mut Ui, sim: &Sim) {
+        if let Some(ref mission) = sim.mission {
+            if mission.status != MissionStatus::InProgress && !self.seen_mission_over {
+                self.seen_mission_over = true;
+                self.open(ui, Screen::MissionOver);
+                return;
+            }
+        }
+        if ui.pressed(Action::Pause) {
+            self.log_scroll = usize::MAX;
+            self.open(ui, Screen::Paused);
+        } else if ui.pressed(Action::Inventory) {
+            self.open(ui, Screen::Inventory);
+        }
+    }
+    
+    fn paused(&mut self, ui: &mut Ui, sim: &Sim) {
+        ui.begin_panel("Paused", WIDE_WIDTH, WIDE_HEIGHT);
+        ui.log(&sim.log, LOG_ROWS - 4, &mut self.log_scroll);
+        if ui.button("Resume") || ui.pressed(Action::Back) || ui.pressed(Action::Pause) {
+            self.open(ui, Screen::Playing);
+        } else if ui.button("Inventory") {
+            self.open(ui, Screen::Inventory);
+        } else if ui.button("Briefing") {
+            self.open(ui, Screen::Briefing);
+        } else if ui.button("Quit") {
+            self.open(ui, Screen::ConfirmQuit);
+        }
+    }
+    
+    fn inventory(&mut self, ui: &mut Ui, sim: &Sim) {
+        let inventory = &sim.player.inventory;
+        ui.begin_panel("Inventory", WIDE_WIDTH, WIDE_HEIGHT);
+        ui.label(&format!(
+            "{} items: {:.1}/{:.0} kg, {:.1}/{:.0} L, worth ${}",
+            inventory.items.len(),
+            inventory.weight(), inventory.max_weight,
+            inventory.volume(), inventory.max_volume,
+            inventory.value()
+        ));
+        
+        let rows: Vec<String> = inventory.items.iter().map(|item| {
+            format!(
+                "{:<28} {:>5.1} kg {:>5.1} L {:>9}",
+                item.name, item.weight, item.volume, format!("${}", item.value)
+            )
+        }).collect();
+        ui.list(&rows, LIST_ROWS, &mut self.inventory);
+        match inventory.items.get(self.inventory.selected) {
+            Some(item) => { ui.label(&format!("A fence would pay ${}. Heat {:.1}.", item.fence_price, item.heat)); },
+            None       => { ui.label("Empty-handed."); }
+        }
+        
+        if ui.button("Close") || ui.pressed(Action::Back) || ui.pressed(Action::Inventory) {
+            self.open(ui, Screen::Playing);
+        }
+    }
+}
+
+// A message with a button per choice. Returns the index of the choice picked, if any.
+fn dialogue(ui: &mut Ui, title: &str, text: &str, choices: &[&str]) -> Option<usize> {
+    ui.begin_panel(title, WIDE_WIDTH, WIDE_HEIGHT);
+    ui.label(text);
+    let mut picked = None;
+    for (i, choice) in choices.iter().enumerate() {
+        if ui.button(choice) && picked.is_none() {
+            picked = Some(i);
+        }
+    }
+    picked
+}
\ No newline at end of file
@@ -0,0 +1,255 @@
+use std::path::Path;
+use std::ptr;
+use gl;
+use gl::types::*;
+use libc::c_void;
+
+use glutil;
+use camera::Camera;
+use mission::MissionStatus;
+use sim::Sim;
+use tile::{chunk, Tile, Occupant};
+use tile::Tile::*;
+use tile::FloorTileType::*;
+use world::World;
+
+// Size of the minimap and its gap from the top right corner, in pixels.
+pub const SIZE:   f32 = 240.0;
+pub const MARGIN: f32 = 10.0;
+
+// Half the width of the dots marking things on the map, in pixels.
+pub const DOT_RADIUS: f32 = 3.0;
+
+// A map of the whole city in a corner of the screen, one texel per tile. Shows what the camera's
+// looking at, the player, the mission target and the police.
+pub struct Minimap {
+    program:         GLuint,
+    map_min_idx:     GLint,
+    map_max_idx:     GLint,
+    map_idx:         GLint,
+    color_idx:       GLint,
+    textured_idx:    GLint,
+    position_idx:    GLuint,
+    
+    vao:             GLuint,
+    position_buffer: GLuint,
+    texture:         GLuint,
+    
+    world_size:      usize
+}
+
+impl Minimap {
+    pub fn new(world: &World) -> Minimap {
+        let id = glutil::make_program(
+            &Path::new("glsl/minimap.vert.glsl"), &Path::new("glsl/minimap.frag.glsl")
+        );
+        let mut minimap = Minimap {
+            program:      id,
+            map_min_idx:  glutil::get_uniform_location(id, "mapMin"),
+            map_max_idx:  glutil::get_uniform_location(id, "mapMax"),
+            map_idx:      glutil::get_uniform_location(id, "map"),
+            color_idx:    glutil::get_uniform_location(id, "color"),
+            textured_idx: glutil::get_uniform_location(id, "textured"),
+            position_idx: glutil::get_attrib_location( id, "position"),
+            vao: 0, position_buffer: 0, texture: 0,
+            world_size: world.size()
+        };
+        
+        let mut texels: Vec<u8> = Vec::with_capacity(4 * minimap.world_size * minimap.world_size);
+        for y in 0..minimap.world_size {
+            for x in 0..minimap.world_size {
+                let (r, g, b) = color(&world.tile_at(x, y, 0));
+                texels.push_all(&[r, g, b, 255]);
+            }
+        }
+        
+        unsafe {
+            gl::GenVertexArrays(1, &mut minimap.vao);
+            gl::GenBuffers(1, &mut minimap.position_buffer);
+            gl::BindVertexArray(minimap.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, minimap.position_buffer);
+            gl::EnableVertexAttribArray(minimap.position_idx);
+            gl::VertexAttribPointer(minimap.position_idx, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            
+            // Linear filtering, since the map's shrunk a lot more than it's ever blown up.
+            gl::GenTextures(1, &mut minimap.texture);
+            gl::BindTexture(gl::TEXTURE_2D, minimap.texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::RGBA as GLint,
+                minimap.world_size as GLsizei, minimap.world_size as GLsizei, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE,
+                texels.as_ptr() as *const c_void
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        minimap
+    }
+    
+    // Redraws the given chunks on the map, e.g. the ones the tile renderer just re-meshed. The map
+    // only shows the ground, so chunks on other levels are skipped.
+    pub fn update_chunks(&self, world: &World, chunks: &[(usize, usize, usize)]) {
+        for &(chunk_x, chunk_y, z) in chunks.iter() {
+            if z > 0 {
+                continue;
+            }
+            let chunk = world.chunks[chunk_y][chunk_x].borrow();
+            let mut texels: Vec<u8> = Vec::with_capacity(4 * chunk::SIZE * chunk::SIZE);
+            for y in chunk.min_y()..(chunk.min_y() + chunk::SIZE) {
+                for x in chunk.min_x()..(chunk.min_x() + chunk::SIZE) {
+                    let (r, g, b) = color(chunk.tile_at(x, y));
+                    texels.push_all(&[r, g, b, 255]);
+                }
+            }
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, self.texture);
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D, 0,
+                    chunk.min_x() as GLint, chunk.min_y() as GLint,
+                    chunk::SIZE as GLsizei, chunk::SIZE as GLsizei,
+                    gl::RGBA, gl::UNSIGNED_BYTE,
+                    texels.as_ptr() as *const c_void
+                );
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+        }
+    }
+    
+    pub fn draw(&self, camera: &Camera, sim: &Sim) {
+        // The map's corners in normalized device coords, where y points up.
+        let (w, h) = (camera.width as f32, camera.height as f32);
+        let (min_x, max_x) = (1.0 - 2.0 * (MARGIN + SIZE) / w, 1.0 - 2.0 * MARGIN / w);
+        let (min_y, max_y) = (1.0 - 2.0 * (MARGIN + SIZE) / h, 1.0 - 2.0 * MARGIN / h);
+        
+        // World coords to normalized device coords on the map.
+        let size = self.world_size as f32;
+        let to_map = |x: f32, y: f32| -> (f32, f32) {
+            (
+                min_x + (x + 0.5) / size * (max_x - min_x),
+                max_y - (y + 0.5) / size * (max_y - min_y)
+            )
+        };
+        let (dot_w, dot_h) = (2.0 * DOT_RADIUS / w, 2.0 * DOT_RADIUS / h);
+        let dot = |x: f32, y: f32| -> Vec<f32> {
+            let (cx, cy) = to_map(x, y);
+            quad(cx - dot_w, cy - dot_h, cx + dot_w, cy + dot_h)
+        };
+        
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::UseProgram(self.program);
+            gl::Uniform2f(self.map_min_idx, min_x, min_y);
+            gl::Uniform2f(self.map_max_idx, max_x, max_y);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::Uniform1i(self.map_idx, 0);
+            gl::BindVertexArray(self.vao);
+        }
+        
+        self.draw_vertices(gl::TRIANGLES, &quad(min_x, min_y, max_x, max_y), None);
+        
+        // What the camera's looking at.
+        let view = camera.visible_rect();
+        let (left, top) = to_map(view.min_x, view.min_y);
+        let (right, bottom) = to_map(view.max_x, view.max_y);
+        let outline = [left, top, right, top, right, bottom, left, bottom];
+        self.draw_vertices(gl::LINE_LOOP, &outline, Some((1.0, 1.0, 1.0, 1.0)));
+        
+        // The target building, while there's still a mission on.
+        if let Some(ref mission) = sim.mission {
+            if mission.status == MissionStatus::InProgress {
+                let b = &sim.world.buildings[mission.target_building];
+                let (x, y) = match b.safe {
+                    Some((x, y, _)) => { (x as f32, y as f32) },
+                    None            => { ((b.min_x + b.max_x) as f32 / 2.0, (b.min_y + b.max_y) as f32 / 2.0) }
+                };
+                self.draw_vertices(gl::TRIANGLES, &dot(x, y), Some((1.0, 0.85, 0.1, 1.0)));
+            }
+        }
+        
+        let mut police = Vec::new();
+        for unit in sim.police.units.iter() {
+            police.push_all(&dot(unit.x, unit.y));
+        }
+        self.draw_vertices(gl::TRIANGLES, &police, Some((0.2, 0.4, 1.0, 1.0)));
+        
+        let player = dot(sim.player.x as f32, sim.player.y as f32);
+        self.draw_vertices(gl::TRIANGLES, &player, Some((0.1, 1.0, 0.3, 1.0)));
+        
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+    
+    // Draws the given positions, textured with the map if color is None.
+    fn draw_vertices(&self, mode: GLenum, positions: &[f32], color: Option<(f32, f32, f32, f32)>) {
+        if positions.is_empty() {
+            return;
+        }
+        unsafe {
+            match color {
+                Some((r, g, b, a)) => {
+                    gl::Uniform4f(self.color_idx, r, g, b, a);
+                    gl::Uniform1f(self.textured_idx, 0.0);
+                },
+                None => {
+                    gl::Uniform1f(self.textured_idx, 1.0);
+                }
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.position_buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                4 * positions.len() as i64,
+                positions.as_ptr() as *const c_void,
+                gl::STREAM_DRAW
+            );
+            gl::DrawArrays(mode, 0, (positions.len() / 2) as GLsizei);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+}
+
+// Two triangles covering the rectangle.
+fn quad(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Vec<f32> {
+    vec![
+        min_x, min_y, max_x, min_y, max_x, max_y,
+        min_x, min_y, max_x, max_y, min_x, max_y
+    ]
+}
+
+// What a tile looks like on the map. Buildings stand out from the streets, and doors, safes and
+// loot show up in them.
+fn color(tile: &Tile) -> (u8, u8, u8) {
+    match *tile {
+        FloorTile(_, Some(ref occ)) => {
+            match *occ.borrow() {
+                Occupant::Door(_)      => { (170, 110,  50) },
+                Occupant::Container(_) => { ( 90,  90, 120) },
+                Occupant::Item(_)      => { (220, 180,  60) }
+            }
+        },
+        WallTile(_)                    => { (200, 190, 180) },
+        FloorTile(ConcreteFloor, _)    => { (110, 110, 110) },
+        FloorTile(AsphaltFloor, _)     => { ( 40,  40,  42) },
+        FloorTile(LaneMarkingFloor, _) => { ( 40,  40,  42) },
+        FloorTile(CrosswalkFloor, _)   => { ( 60,  60,  62) },
+        FloorTile(SidewalkFloor, _)    => { ( 90,  90,  88) },
+        FloorTile(CurbFloor, _)        => { ( 90,  90,  88) },
+        FloorTile(GrassFloor, _)       => { ( 30,  90,  45) },
+        FloorTile(StairsUpFloor, _)    => { (130, 120, 100) },
+        FloorTile(StairsDownFloor, _)  => { (130, 120, 100) },
+        FloorTile(ElevatorFloor, _)    => { (150, 150, 160) },
+        FloorTile(WaterFloor, _)       => { ( 40,  80, 160) },
+        FloorTile(BridgeFloor, _)      => { (120, 100,  80) },
+        FloorTile(HillFloor, _)        => { ( 80, 110,  60) },
+        EmptyTile                      => { (  0,   0,   0) }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,312 @@
+use std::fmt;
+use rand::Rng;
+
+use inventory::{Item, HaulSummary};
+use player::Player;
+use tile::Occupant;
+use world::World;
+
+// Score adjustments, in dollars.
+pub const OBJECTIVE_BONUS:    i64 = 5000;
+pub const CONSTRAINT_PENALTY: i64 = 10000;
+pub const HEAT_PENALTY:       i64 = 1000;  // Per point of heat.
+
+// Name, weight, volume, value, fence price, heat. Mission targets are worth more and run hotter
+// than anything lying around.
+static TARGETS: [(&'static str, f32, f32, u32, u32, f32); 4] = [
+    ("Blue diamond",         0.1, 0.1, 2000000, 500000, 10.0),
+    ("Faberge egg",          0.5, 0.5, 1500000, 300000,  8.0),
+    ("Prototype microchip",  0.1, 0.1,  800000, 400000,  6.0),
+    ("Ledger of bribes",     1.0, 1.5,   50000, 250000,  4.0)
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Objective {
+    StealTarget,       // Leave the target building with the target item. Always the first objective.
+    TakeAtLeast(u32),  // Leave with at least this much loot besides the target, by value.
+    CrackSafe          // Open the target building's safe.
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    NoAlarms,          // Don't set off any alarms.
+    Quiet(f32)         // Don't make any sound louder than this.
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissionStatus {
+    InProgress,
+    Succeeded,
+    Failed
+}
+
+pub struct Mission {
+    pub target_building: usize,  // Index into world.buildings.
+    pub target_item:     u32,    // Item id.
+    pub target_name:     String,
+    pub objectives:      Vec<Objective>,
+    pub constraints:     Vec<Constraint>,
+    pub time_limit:      f32,    // Seconds.
+    
+    // Progress, parallel to objectives and constraints.
+    pub completed:       Vec<bool>,
+    pub broken:          Vec<bool>,
+    pub elapsed:         f32,
+    pub status:          MissionStatus
+}
+
+pub struct MissionReport {
+    pub status:      MissionStatus,
+    pub take:        HaulSummary,
+    pub heat:        f32,
+    pub objectives:  Vec<(Objective, bool)>,
+    pub constraints: Vec<(Constraint, bool)>,
+    pub elapsed:     f32,
+    pub score:       i64
+}
+
+impl Mission {
+    // Picks a building with a safe, puts the target in it and rolls optional objectives and
+    // constraints. Returns None if the city has nowhere to hide a target.
+    pub fn generate<R: Rng>(world: &mut World, rng: &mut R) -> Option<Mission> {
+        let candidates: Vec<(usize, (usize, usize, usize))> = (0..world.buildings.len())
+            .filter_map(|i| world.buildings[i].safe.map(|safe| (i, safe)))
+            .collect();
+        let (building, (safe_x, safe_y, safe_z)) = match rng.choose(&candidates) {
+            Some(&candidate) => { candidate },
+            None             => { return None; }
+        };
+        
+        let &(name, weight, volume, value, fence_price, heat) = rng.choose(&TARGETS).unwrap();
+        let target = Item::new(world.next_item_id(), name, weight, volume, value, fence_price, heat);
+        let target_id = target.id;
+        if let Some(occ) = world.tile_at(safe_x, safe_y, safe_z).occupant() {
+            if let Occupant::Container(ref mut safe) = *occ.borrow_mut() {
+                safe.contents.push(target);
+            }
+        }
+        
+        let mut objectives = vec![Objective::StealTarget];
+        if rng.gen_weighted_bool(2) {
+            objectives.push(Objective::TakeAtLeast(rng.gen_range(1, 6) * 10000));
+        }
+        if rng.gen_weighted_bool(2) {
+            objectives.push(Objective::CrackSafe);
+        }
+        
+        let mut constraints = Vec::new();
+        if rng.gen_weighted_bool(2) {
+            constraints.push(Constraint::NoAlarms);
+        }
+        if rng.gen_weighted_bool(3) {
+            constraints.push(Constraint::Quiet(4.0));
+        }
+        
+        Some(Mission {
+            target_building: building,
+            target_item: target_id,
+            target_name: name.to_string(),
+            completed: objectives.iter().map(|_| false).collect(),
+            broken: constraints.iter().map(|_| false).collect(),
+            objectives: objectives,
+            constraints: constraints,
+            time_limit: rng.gen_range(10, 31) as f32 * 60.0,
+            elapsed: 0.0,
+            status: MissionStatus::InProgress
+        })
+    }
+    
+    // Call once per tick, before the world's sounds are cleared. Once time_left hits zero, the
+    // caller should end the mission with abandon.
+    pub fn update(&mut self, world: &World, dt: f32) {
+        if self.status != MissionStatus::InProgress {
+            return;
+        }
+        
+        self.elapsed = self.elapsed + dt;
+        
+        for (i, objective) in self.objectives.iter().enumerate() {
+            if *objective == Objective::CrackSafe {
+                if let Some((x, y, z)) = world.buildings[self.target_building].safe {
+                    if let Some(occ) = world.tile_at(x, y, z).occupant() {
+                        if let Some(lock) = occ.borrow().lock() {
+                            if !lock.locked { self.completed[i] = true; }
+                        }
+                    }
+                }
+            }
+        }
+        
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            if let Constraint::Quiet(max_loudness) = *constraint {
+                if world.sounds.iter().any(|sound| sound.loudness > max_loudness) {
+                    self.broken[i] = true;
+                }
+            }
+        }
+    }
+    
+    pub fn alarm_raised(&mut self) {
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            if *constraint == Constraint::NoAlarms {
+                self.broken[i] = true;
+            }
+        }
+    }
+    
+    // Call with the haul whenever the player walks out of a building. Returns a report if that
+    // ended the mission, i.e. the player left the target building with the target.
+    pub fn on_exit(&mut self, haul: &HaulSummary, player: &Player) -> Option<MissionReport> {
+        if self.status != MissionStatus::InProgress || haul.building != self.target_building {
+            return None;
+        }
+        if !haul.items.iter().any(|item| item.id == self.target_item) {
+            return None;
+        }
+        
+        for (i, objective) in self.objectives.iter().enumerate() {
+            match *objective {
+                Objective::StealTarget         => { self.completed[i] = true; },
+                Objective::TakeAtLeast(amount) => {
+                    let rest = haul.items.iter().filter(|item| item.id != self.target_item).fold(0, |sum, item| sum + item.value);
+                    self.completed[i] = rest >= amount;
+                },
+                Objective::CrackSafe           => {}
+            }
+        }
+        self.status = MissionStatus::Succeeded;
+        Some(self.report(haul, player))
+    }
+    
+    // For ending a mission that didn't end by the player walking out with the target, e.g. when
+    // time runs out. Everything the player is carrying counts as the take.
+    pub fn abandon(&mut self, player: &Player) -> MissionReport {
+        self.status = MissionStatus::Failed;
+        let take = HaulSummary {
+            building: self.target_building, items: player.inventory.items.clone()
+        };
+        self.report(&take, player)
+    }
+    
+    fn report(&self, take: &HaulSummary, player: &Player) -> MissionReport {
+        let heat = player.inventory.heat();
+        let mut score: i64 = 0;
+        if self.status == MissionStatus::Succeeded {
+            score = score + take.fence_price() as i64;
+            for (i, _) in self.objectives.iter().enumerate().skip(1) {
+                if self.completed[i] { score = score + OBJECTIVE_BONUS; }
+            }
+        }
+        for broken in self.broken.iter() {
+            if *broken { score = score - CONSTRAINT_PENALTY; }
+        }
+        score = score - (heat * HEAT_PENALTY as f32) as i64;
+        
+        MissionReport {
+            status: self.status.clone(),
+            take: HaulSummary { building: take.building, items: take.items.clone() },
+            heat: heat,
+            objectives: self.objectives.iter().cloned().zip(self.completed.iter().cloned()).collect(),
+            constraints: self.constraints.iter().cloned().zip(self.broken.iter().map(|b| !*b)).collect(),
+            elapsed: self.elapsed,
+            score: score
+        }
+    }
+    
+    pub fn time_left(&self) -> f32 {
+        if self.elapsed > self.time_limit { 0.0 } else { self.time_limit - self.elapsed }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Objective::StealTarget         => { write!(f, "Steal the target") },
+            Objective::TakeAtLeast(amount) => { write!(f, "Walk out with at least ${} besides the target", amount) },
+            Objective::CrackSafe           => { write!(f, "Crack the safe") }
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Constraint::NoAlarms => { write!(f, "Don't trip any alarms") },
+            Constraint::Quiet(_) => { write!(f, "Keep it quiet") }
+        }
+    }
+}
+
+// The briefing.
+impl fmt::Display for Mission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Target: {} in building {}", self.target_name, self.target_building));
+        try!(writeln!(f, "Time limit: {} minutes", (self.time_limit / 60.0) as u32));
+        for objective in self.objectives.iter() {
+            try!(writeln!(f, "  Objective: {}", objective));
+        }
+        for constraint in self.constraints.iter() {
+            try!(writeln!(f, "  Constraint: {}", constraint));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MissionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let outcome = match self.status {
+            MissionStatus::Succeeded  => { "succeeded" },
+            MissionStatus::Failed     => { "failed" },
+            MissionStatus::InProgress => { "in progress" }
+        };
+        try!(writeln!(f, "Mission {} after {} seconds", outcome, self.elapsed as u32));
+        try!(writeln!(f, "{}", self.take));
+        try!(writeln!(f, "Heat carried: {:.1}", self.heat));
+        for &(ref objective, done) in self.objectives.iter() {
+            try!(writeln!(f, "  [{}] {}", if done { "x" } else { " " }, objective));
+        }
+        for &(ref constraint, kept) in self.constraints.iter() {
+            try!(writeln!(f, "  [{}] {}", if kept { "x" } else { " " }, constraint));
+        }
+        write!(f, "Score: {}", self.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use inventory::{Item, HaulSummary};
+    use player::Player;
+    use super::{Mission, MissionStatus, Objective};
+    
+    // Stealing target item 1 from building 0, with at least $20,000 of other loot.
+    fn mission() -> Mission {
+        Mission {
+            target_building: 0, target_item: 1, target_name: "Ledger of bribes".to_string(),
+            objectives: vec![Objective::StealTarget, Objective::TakeAtLeast(20000)],
+            constraints: Vec::new(), time_limit: 600.0,
+            completed: vec![false, false], broken: Vec::new(), elapsed: 0.0,
+            status: MissionStatus::InProgress
+        }
+    }
+    
+    fn target() -> Item {
+        Item::new(1, "Ledger of bribes", 1.0, 1.5, 50000, 250000, 4.0)
+    }
+    
+    #[test]
+    fn the_target_alone_isnt_enough_loot() {
+        let mut mission = mission();
+        let haul = HaulSummary { building: 0, items: vec![target()] };
+        let report = mission.on_exit(&haul, &Player::new(0, 0)).unwrap();
+        assert_eq!(report.objectives, vec![(Objective::StealTarget, true), (Objective::TakeAtLeast(20000), false)]);
+    }
+    
+    #[test]
+    fn other_loot_counts() {
+        let mut mission = mission();
+        let painting = Item::new(2, "Painting", 5.0, 20.0, 20000, 4000, 1.0);
+        let haul = HaulSummary { building: 0, items: vec![target(), painting] };
+        let report = mission.on_exit(&haul, &Player::new(0, 0)).unwrap();
+        assert_eq!(report.objectives, vec![(Objective::StealTarget, true), (Objective::TakeAtLeast(20000), true)]);
+    }
+}
\ No newline at end of file
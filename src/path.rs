@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use lock::KeyRing;
+use player;
+use world::World;
+
+// Give up on paths longer than this many tiles. Keeps a search for an unreachable goal from
+// flooding the whole city.
+pub const MAX_PATH_LENGTH: usize = 1000;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Node {
+    pos:  (usize, usize, usize),
+    cost: usize, // Steps from the start so far.
+    est:  usize  // cost plus the heuristic.
+}
+
+// BinaryHeap is a max-heap, so the ordering is reversed to pop the cheapest node first.
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        (other.est, other.cost).cmp(&(self.est, self.cost))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Levels don't count: a single step onto stairs or ride in an elevator can cover them.
+fn manhattan(a: (usize, usize, usize), b: (usize, usize, usize)) -> usize {
+    let dx = if a.0 > b.0 { a.0 - b.0 } else { b.0 - a.0 };
+    let dy = if a.1 > b.1 { a.1 - b.1 } else { b.1 - a.1 };
+    dx + dy
+}
+
+// A* over every level's tiles as (x, y, z), with 4-way movement. Stepping onto stairs goes to
+// the level they lead to, the way it does for the player, and riding an elevator goes to any other
+// level with one in the same place. Walls, containers, open air and any door keys can't open are
+// impassable. Returns the tiles to step through, excluding from and including to.
+pub fn find_path(
+    world: &World, keys: &KeyRing, from: (usize, usize, usize), to: (usize, usize, usize)
+) -> Option<Vec<(usize, usize, usize)>> {
+    if from == to {
+        return Some(Vec::new());
+    }
+    if !world.tile_at(to.0, to.1, to.2).is_passable_for(keys) {
+        return None;
+    }
+    
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<(usize, usize, usize), (usize, usize, usize)> = HashMap::new();
+    let mut best_cost: HashMap<(usize, usize, usize), usize> = HashMap::new();
+    
+    open.push(Node { pos: from, cost: 0, est: manhattan(from, to) });
+    best_cost.insert(from, 0);
+    
+    while let Some(node) = open.pop() {
+        if node.pos == to {
+            let mut path = vec![to];
+            let mut pos = to;
+            while let Some(&prev) = came_from.get(&pos) {
+                if prev == from { break; }
+                path.push(prev);
+                pos = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        
+        if node.cost >= MAX_PATH_LENGTH {
+            continue;
+        }
+        if let Some(&cost) = best_cost.get(&node.pos) {
+            if cost < node.cost { continue; }
+        }
+        
+        let (x, y, z) = (node.pos.0 as i32, node.pos.1 as i32, node.pos.2);
+        let mut neighbors = Vec::with_capacity(4);
+        for &(nx, ny) in [(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)].iter() {
+            if !world.in_bounds(nx, ny) { continue; }
+            let tile = world.tile_at(nx as usize, ny as usize, z);
+            if !tile.is_passable_for(keys) { continue; }
+            neighbors.push((nx as usize, ny as usize, tile.stairs_to(z).unwrap_or(z)));
+        }
+        
+        // A ride counts as a step, like the interaction it takes.
+        if world.tile_at(node.pos.0, node.pos.1, z).is_elevator() {
+            if let Some(level) = player::elevator_stop(world, node.pos.0, node.pos.1, z) {
+                neighbors.push((node.pos.0, node.pos.1, level));
+            }
+        }
+        
+        for &next in neighbors.iter() {
+            let cost = node.cost + 1;
+            let better = match best_cost.get(&next) {
+                Some(&old) => { cost < old },
+                None       => { true }
+            };
+            if better {
+                best_cost.insert(next, cost);
+                came_from.insert(next, node.pos);
+                open.push(Node { pos: next, cost: cost, est: cost + manhattan(next, to) });
+            }
+        }
+    }
+    
+    None
+}
\ No newline at end of file
@@ -0,0 +1,234 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use lock::{KeyRing, LockAction, LockActionStatus};
+use inventory::{Inventory, HaulSummary};
+use tile::Occupant;
+use world::World;
+
+// How much the player can carry, in kilograms and litres.
+pub const MAX_WEIGHT: f32 = 30.0;
+pub const MAX_VOLUME: f32 = 40.0;
+
+pub struct Player {
+    pub x:      usize,
+    pub y:      usize,
+    pub z:      usize,  // The level they're on, from 0 at the ground up.
+    pub keys:      KeyRing,
+    pub inventory: Inventory,
+    
+    // Direction of the last attempted step. The player interacts with the tile in front of them.
+    pub facing: (i32, i32),
+    
+    // The lock the player is currently picking or cracking, if any.
+    pub action: Option<LockAction>,
+    
+    // Index into world.buildings of the building the player is in, and the ids of the items they
+    // carried when they walked in.
+    pub inside:        Option<usize>,
+    pub ids_on_entry:  Vec<u32>,
+    
+    // Index into traffic.vehicles of the car the player's driving, if any. They're on its front
+    // tile while they are.
+    pub driving: Option<usize>
+}
+
+impl Player {
+    pub fn new(x: usize, y: usize) -> Player {
+        Player {
+            x: x, y: y, z: 0, keys: KeyRing::new(), inventory: Inventory::new(MAX_WEIGHT, MAX_VOLUME),
+            facing: (0, 1), action: None, inside: None, ids_on_entry: Vec::new(), driving: None
+        }
+    }
+    
+    // Steps one tile, opening any door the player has access to. Stepping onto stairs takes the
+    // player to the level they lead to. Moving abandons whatever lock the player was working on.
+    pub fn try_move(&mut self, world: &mut World, dx: i32, dy: i32) -> bool {
+        self.facing = (dx, dy);
+        let nx = self.x as i32 + dx;
+        let ny = self.y as i32 + dy;
+        if !world.in_bounds(nx, ny) {
+            return false;
+        }
+        let (nx, ny) = (nx as usize, ny as usize);
+        let tile = world.tile_at(nx, ny, self.z);
+        if !tile.is_passable_for(&self.keys) {
+            return false;
+        }
+        if let Some(occ) = tile.occupant() {
+            if let Occupant::Door(ref mut door) = *occ.borrow_mut() {
+                if let Some(ref mut lock) = door.lock {
+                    lock.locked = false;
+                }
+                door.open = true;
+            }
+            world.mark_dirty(nx, ny, self.z);
+        }
+        self.action = None;
+        self.x = nx;
+        self.y = ny;
+        if let Some(z) = tile.stairs_to(self.z) {
+            self.z = z;
+        }
+        true
+    }
+    
+    // Rides the elevator the player's standing in up to the next level with one in the same
+    // place, or from the top back down to the lowest. Returns false if there's nowhere to go.
+    pub fn ride_elevator(&mut self, world: &World) -> bool {
+        match elevator_stop(world, self.x, self.y, self.z) {
+            Some(z) => { self.action = None; self.z = z; true },
+            None    => { false }
+        }
+    }
+    
+    // Interacts with the lockable occupant at (x, y): opens it if it's unlocked, unlocks it if
+    // the player holds the right credentials, and otherwise starts picking or cracking it.
+    pub fn interact(&mut self, world: &mut World, x: usize, y: usize) {
+        let occ = match world.tile_at(x, y, self.z).occupant() {
+            Some(occ) => { occ },
+            None      => { return; }
+        };
+        let mut occ = occ.borrow_mut();
+        
+        let access = self.keys.has_access(occ.lock());
+        if access {
+            if let Some(lock) = occ.lock_mut() {
+                lock.locked = false;
+            }
+            match *occ {
+                Occupant::Door(ref mut door)           => { door.open = !door.open; },
+                Occupant::Container(ref mut container) => { container.open = true; },
+                Occupant::Item(_)                      => {}
+            }
+            world.mark_dirty(x, y, self.z);
+        } else if let Some(lock) = occ.lock() {
+            self.action = LockAction::begin(lock, x, y);
+        }
+    }
+    
+    // Picks up the item the player is standing on, or else whatever is in front of them: a loose
+    // item or the contents of an open container. Anything too heavy or bulky is left behind.
+    pub fn pick_up(&mut self, world: &mut World) {
+        let (x, y) = (self.x, self.y);
+        if self.pick_up_at(world, x, y) {
+            return;
+        }
+        let fx = self.x as i32 + self.facing.0;
+        let fy = self.y as i32 + self.facing.1;
+        if world.in_bounds(fx, fy) {
+            self.pick_up_at(world, fx as usize, fy as usize);
+        }
+    }
+    
+    // Returns true if there was anything there to try picking up.
+    fn pick_up_at(&mut self, world: &mut World, x: usize, y: usize) -> bool {
+        let tile = world.tile_at(x, y, self.z);
+        let occ = match tile.occupant() {
+            Some(occ) => { occ },
+            None      => { return false; }
+        };
+        
+        let loose = match *occ.borrow() {
+            Occupant::Item(ref item) => { Some(item.clone()) },
+            _                        => { None }
+        };
+        if let Some(item) = loose {
+            if self.inventory.add(item).is_ok() {
+                world.replace_tile(x, y, self.z, tile.without_occupant());
+            }
+            return true;
+        }
+        
+        if let Occupant::Container(ref mut container) = *occ.borrow_mut() {
+            if !container.open {
+                return false;
+            }
+            let mut left = Vec::new();
+            while let Some(item) = container.contents.pop() {
+                if let Err(item) = self.inventory.add(item) {
+                    left.push(item);
+                }
+            }
+            container.contents = left;
+            return true;
+        }
+        
+        false
+    }
+    
+    // Drops the item at index in the inventory onto the player's tile, if nothing's there yet.
+    pub fn drop_item(&mut self, world: &mut World, index: usize) {
+        let tile = world.tile_at(self.x, self.y, self.z);
+        if tile.occupant().is_some() {
+            return;
+        }
+        if let Some(item) = self.inventory.remove(index) {
+            let occ = Rc::new(RefCell::new(Occupant::Item(item)));
+            world.replace_tile(self.x, self.y, self.z, tile.with_occupant(occ));
+        }
+    }
+    
+    // Call after moving. Returns what the player is carrying out if they just left a building.
+    pub fn update_location(&mut self, world: &World) -> Option<HaulSummary> {
+        let now_inside = world.building_containing(self.x, self.y);
+        if now_inside == self.inside {
+            return None;
+        }
+        
+        let haul = match self.inside {
+            Some(b) => { Some(self.inventory.haul_since(&self.ids_on_entry, b)) },
+            None    => { None }
+        };
+        if now_inside.is_some() {
+            self.ids_on_entry = self.inventory.ids();
+        }
+        self.inside = now_inside;
+        haul
+    }
+    
+    // Standing in an elevator, interacting rides it instead.
+    pub fn interact_facing(&mut self, world: &mut World) {
+        if world.tile_at(self.x, self.y, self.z).is_elevator() && self.ride_elevator(world) {
+            return;
+        }
+        let x = self.x as i32 + self.facing.0;
+        let y = self.y as i32 + self.facing.1;
+        if world.in_bounds(x, y) {
+            self.interact(world, x as usize, y as usize);
+        }
+    }
+    
+    // Advances any lock the player is working on by dt seconds.
+    pub fn update(&mut self, world: &mut World, dt: f32) {
+        let done = match self.action {
+            Some(ref mut action) => { action.tick(dt, &mut world.sounds) == LockActionStatus::Done },
+            None                 => { false }
+        };
+        if done {
+            let action = self.action.take().unwrap();
+            if let Some(occ) = world.tile_at(action.x, action.y, self.z).occupant() {
+                if let Some(lock) = occ.borrow_mut().lock_mut() {
+                    lock.locked = false;
+                }
+                
+                // Defeating a lock in an alarmed building trips the alarm.
+                if let Some(b) = world.building_containing(action.x, action.y) {
+                    if world.buildings[b].alarmed {
+                        world.alarms.push((action.x, action.y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The level an elevator at (x, y) on level z goes to next: the nearest one above with an elevator
+// there too, or else the lowest one below. None if it's the only stop.
+pub fn elevator_stop(world: &World, x: usize, y: usize, z: usize) -> Option<usize> {
+    let stops: Vec<usize> = (0..world.levels()).filter(|&l| l != z && world.tile_at(x, y, l).is_elevator()).collect();
+    match stops.iter().find(|&&l| l > z) {
+        Some(&l) => { Some(l) },
+        None     => { stops.first().cloned() }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,280 @@
+use rand::Rng;
+
+use clock::Clock;
+use gen::street;
+use player::Player;
+use world::World;
+
+pub const MAX_HEAT:             f32 = 100.0;
+pub const HEAT_PER_STAR:        f32 = 20.0;  // Each star of wanted level puts another unit on the street.
+pub const ALARM_HEAT:           f32 = 25.0;  // Per alarm tripped.
+pub const HEARD_CRIME_HEAT:     f32 = 5.0;   // Per suspicious sound a unit hears.
+pub const WITNESSED_CRIME_HEAT: f32 = 15.0;  // Per second a unit watches the player commit a crime.
+pub const HEAT_DECAY:           f32 = 0.5;   // Per second no unit can see the player.
+pub const LAY_LOW_MULTIPLIER:   f32 = 3.0;   // Heat decays this much faster while the player is indoors.
+
+pub const UNIT_SPEED:     f32 = 12.0;  // Tiles per second.
+pub const SIGHT_RADIUS:   f32 = 15.0;  // Tiles, in full light. Units see less far in the dark.
+pub const SEARCH_SECONDS: f32 = 60.0;  // How long units search around the last known position.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnitState {
+    Responding,      // Driving to the last known position.
+    Searching(f32),  // Circling the last known position. Holds the seconds left to search.
+    Returning        // Driving back to the station, after which the unit goes off duty.
+}
+
+// A patrol car. Units only drive along streets.
+pub struct Unit {
+    pub x:       f32,                 // World coords.
+    pub y:       f32,
+    pub station: (usize, usize),      // Grid coords.
+    pub route:   Vec<(usize, usize)>, // Intersections still to drive through, in grid coords.
+    pub state:   UnitState
+}
+
+pub struct Police {
+    pub heat:       f32,
+    pub units:      Vec<Unit>,
+    
+    // Where the police last saw or heard of the player, in world coords.
+    pub last_known: Option<(usize, usize)>,
+    
+    // Whether any unit could see the player last tick.
+    pub spotted:    bool
+}
+
+impl Unit {
+    fn tile(&self) -> (usize, usize) {
+        (self.x.round() as usize, self.y.round() as usize)
+    }
+    
+    // Replaces the route with one from wherever the unit is to the intersection goal. The unit is
+    // always on a street, so the nearest intersection is straight ahead or behind.
+    fn route_to(&mut self, world: &World, goal: (usize, usize)) {
+        let (x, y) = self.tile();
+        let start = world.grid.nearest(x, y);
+        let mut route = vec![start];
+        if let Some(rest) = world.grid.route(start, goal) {
+            route.push_all(&rest);
+        }
+        self.route = route;
+    }
+    
+    // Drives toward the next intersection on the route. Returns true once the route is used up.
+    fn drive(&mut self, dt: f32) -> bool {
+        let mut step = UNIT_SPEED * dt;
+        while step > 0.0 && !self.route.is_empty() {
+            let (tx, ty) = street::center(self.route[0].0, self.route[0].1);
+            let (dx, dy) = (tx as f32 - self.x, ty as f32 - self.y);
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist <= step {
+                self.x = tx as f32;
+                self.y = ty as f32;
+                self.route.remove(0);
+                step = step - dist;
+            } else {
+                self.x = self.x + dx / dist * step;
+                self.y = self.y + dy / dist * step;
+                step = 0.0;
+            }
+        }
+        self.route.is_empty()
+    }
+    
+    // Units are on the street, so they can't see anyone off the ground.
+    pub fn can_see(&self, world: &World, daylight: f32, x: usize, y: usize, z: usize) -> bool {
+        let (ux, uy) = self.tile();
+        let (dx, dy) = (ux as f32 - x as f32, uy as f32 - y as f32);
+        let radius = SIGHT_RADIUS * world.visibility_at(x, y, 0, daylight);
+        z == 0 && (dx * dx + dy * dy).sqrt() <= radius && world.line_of_sight((ux, uy), (x, y), 0)
+    }
+}
+
+impl Police {
+    pub fn new() -> Police {
+        Police { heat: 0.0, units: Vec::new(), last_known: None, spotted: false }
+    }
+    
+    // 0 to 5 stars.
+    pub fn wanted_level(&self) -> usize {
+        (self.heat / HEAT_PER_STAR) as usize
+    }
+    
+    fn add_heat(&mut self, amount: f32) {
+        self.heat = self.heat + amount;
+        if self.heat > MAX_HEAT { self.heat = MAX_HEAT; }
+        if self.heat < 0.0      { self.heat = 0.0; }
+    }
+    
+    // Sends every unit on duty to the intersection nearest pos. If pos is already the last known
+    // position, units searching there just keep at it for longer, and units already on their way
+    // keep their routes, so reports every tick the player's in sight don't search the streets over
+    // and over.
+    fn report(&mut self, world: &World, pos: (usize, usize)) {
+        if self.last_known == Some(pos) {
+            for unit in self.units.iter_mut() {
+                if let UnitState::Searching(_) = unit.state {
+                    unit.state = UnitState::Searching(SEARCH_SECONDS);
+                }
+            }
+            return;
+        }
+        self.last_known = Some(pos);
+        let goal = world.grid.nearest(pos.0, pos.1);
+        for unit in self.units.iter_mut() {
+            let heading_there = unit.state == UnitState::Responding && unit.route.last() == Some(&goal);
+            if unit.state != UnitState::Returning && !heading_there {
+                unit.route_to(world, goal);
+                unit.state = UnitState::Responding;
+            }
+        }
+    }
+    
+    // Call once per tick, before the world's alarms and sounds are cleared.
+    pub fn update<R: Rng>(&mut self, world: &World, player: &Player, clock: &Clock, dt: f32, rng: &mut R) {
+        // Alarms call it in directly.
+        for &alarm in world.alarms.iter() {
+            self.add_heat(ALARM_HEAT);
+            self.report(world, alarm);
+        }
+        
+        // Units hear lockpicking and the like.
+        let mut heard = Vec::new();
+        for sound in world.sounds.iter() {
+            if self.units.iter().any(|unit| { let (x, y) = unit.tile(); sound.audible_from(x, y) }) {
+                heard.push((sound.x, sound.y));
+            }
+        }
+        for pos in heard.into_iter() {
+            self.add_heat(HEARD_CRIME_HEAT);
+            self.report(world, pos);
+        }
+        
+        // Units that can see the player keep tabs on them, and catch them in the act. Lit areas
+        // and daylight make the player easier to spot.
+        let daylight = clock.daylight();
+        self.spotted = self.units.iter().any(|unit| unit.can_see(world, daylight, player.x, player.y, player.z));
+        if self.spotted {
+            if player.action.is_some() {
+                self.add_heat(WITNESSED_CRIME_HEAT * dt);
+            }
+            if self.wanted_level() > 0 {
+                self.report(world, (player.x, player.y));
+            }
+        } else {
+            let rate = if player.inside.is_some() { HEAT_DECAY * LAY_LOW_MULTIPLIER } else { HEAT_DECAY };
+            self.add_heat(-rate * dt);
+        }
+        
+        self.dispatch(world);
+        self.drive_units(world, dt, rng);
+    }
+    
+    // Puts a unit at the intersection nearest (x, y), in world coords, to search around it. For
+    // debugging: with no heat on, it heads straight back to the nearest station.
+    pub fn spawn_unit(&mut self, world: &World, x: usize, y: usize) {
+        let spot = world.grid.nearest(x, y);
+        let station = nearest_station(world, spot).unwrap_or(spot);
+        let (cx, cy) = street::center(spot.0, spot.1);
+        if self.last_known.is_none() {
+            self.last_known = Some((cx, cy));
+        }
+        self.units.push(Unit {
+            x: cx as f32, y: cy as f32, station: station, route: Vec::new(),
+            state: UnitState::Searching(SEARCH_SECONDS)
+        });
+    }
+    
+    // Puts units on the street until there's one per star, from the station nearest the last
+    // known position. Sends everyone home once the heat's off.
+    fn dispatch(&mut self, world: &World) {
+        let pos = match self.last_known {
+            Some(pos) => { pos },
+            None      => { return; }
+        };
+        let goal = world.grid.nearest(pos.0, pos.1);
+        
+        if self.wanted_level() == 0 {
+            for unit in self.units.iter_mut() {
+                if unit.state != UnitState::Returning {
+                    let station = unit.station;
+                    unit.route_to(world, station);
+                    unit.state = UnitState::Returning;
+                }
+            }
+            return;
+        }
+        
+        while self.units.iter().filter(|u| u.state != UnitState::Returning).count() < self.wanted_level() {
+            let station = match nearest_station(world, goal) {
+                Some(station) => { station },
+                None          => { return; }
+            };
+            let (x, y) = street::center(station.0, station.1);
+            let mut unit = Unit {
+                x: x as f32, y: y as f32, station: station, route: Vec::new(),
+                state: UnitState::Responding
+            };
+            unit.route_to(world, goal);
+            self.units.push(unit);
+        }
+    }
+    
+    fn drive_units<R: Rng>(&mut self, world: &World, dt: f32, rng: &mut R) {
+        let search_center = match self.last_known {
+            Some(pos) => { world.grid.nearest(pos.0, pos.1) },
+            None      => { return; }
+        };
+        
+        for unit in self.units.iter_mut() {
+            let arrived = unit.drive(dt);
+            let state = unit.state.clone();
+            match state {
+                UnitState::Responding => {
+                    if arrived {
+                        unit.state = UnitState::Searching(SEARCH_SECONDS);
+                    }
+                },
+                UnitState::Searching(left) => {
+                    let left = left - dt;
+                    if left <= 0.0 {
+                        let station = unit.station;
+                        unit.route_to(world, station);
+                        unit.state = UnitState::Returning;
+                    } else {
+                        // Drive around the blocks next to the last known position.
+                        if arrived {
+                            let inter = world.grid.intersections[search_center.1][search_center.0].clone();
+                            let mut choices = inter.borrow().neighbors();
+                            choices.push(search_center);
+                            let next = *rng.choose(&choices).unwrap();
+                            unit.route_to(world, next);
+                        }
+                        unit.state = UnitState::Searching(left);
+                    }
+                },
+                UnitState::Returning => {}
+            }
+        }
+        
+        // Units that made it back to the station go off duty.
+        self.units.retain(|unit| !(unit.state == UnitState::Returning && unit.route.is_empty()));
+    }
+}
+
+fn nearest_station(world: &World, goal: (usize, usize)) -> Option<(usize, usize)> {
+    let mut best = None;
+    let mut best_dist = 0;
+    for &station in world.police_stations.iter() {
+        let dist = match world.grid.route(station, goal) {
+            Some(route) => { route.len() },
+            None        => { continue; }
+        };
+        if best.is_none() || dist < best_dist {
+            best = Some(station);
+            best_dist = dist;
+        }
+    }
+    best
+}
\ No newline at end of file
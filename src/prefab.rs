@@ -0,0 +1,461 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use rand::Rng;
+
+use gen::building::{random_item, LotSize, lot_size_from_name, lot_size_name};
+use gen::district::{District, district_from_name, district_name};
+use lock::{Lock, LockKind};
+use security::{SecurityCamera, Guard};
+use tile::{Occupant, Door, Container, Tile};
+use world::World;
+
+// Characters for legend entries that don't have one of their own in DEFAULT_CHARS.
+static SPARE_CHARS: &'static str = "ABCEFGHIJKMNOPQRTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+// What each kind of cell is written as, where there's an obvious choice.
+static DEFAULT_CHARS: [(char, &'static str, Option<Fixture>, Option<Mark>); 21] = [
+    ('#', "brick_wall",   None,                     None),
+    ('.', "concrete",     None,                     None),
+    ('=', "asphalt",      None,                     None),
+    (',', "grass",        None,                     None),
+    (':', "sidewalk",     None,                     None),
+    (';', "curb",         None,                     None),
+    ('-', "lane_marking", None,                     None),
+    ('%', "crosswalk",    None,                     None),
+    ('<', "stairs_up",    None,                     None),
+    ('>', "stairs_down",  None,                     None),
+    ('^', "elevator",     None,                     None),
+    ('_', "empty",        None,                     None),
+    ('~', "water",        None,                     None),
+    ('+', "bridge",       None,                     None),
+    ('&', "hill",         None,                     None),
+    ('D', "concrete",     Some(Fixture::Door),       None),
+    ('L', "concrete",     Some(Fixture::LockedDoor), None),
+    ('S', "concrete",     Some(Fixture::Safe),       None),
+    ('*', "concrete",     Some(Fixture::Item),       None),
+    ('C', "concrete",     None,                     Some(Mark::Camera)),
+    ('@', "concrete",     None,                     Some(Mark::Spawn))
+];
+
+// Something placed on a floor. Prefabs only say what kind: locks, codes and loot are rolled fresh
+// each time one's stamped, so no two copies are the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fixture {
+    Door,
+    LockedDoor,  // Opens with the key to the building it ends up in.
+    Safe,        // Code-locked, with some loot in it.
+    Item         // A random piece of loot.
+}
+
+// Something about a spot that isn't a tile or what's on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mark {
+    Camera,  // A security camera watches from here.
+    Spawn    // A guard's posted here.
+}
+
+// One tile of a prefab: the tile's sprite name, e.g. "brick_wall" or "concrete", what's on it and
+// what's marked there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub tile:    String,
+    pub fixture: Option<Fixture>,
+    pub mark:    Option<Mark>
+}
+
+// A handcrafted patch of tiles, made in the editor, to stamp into the world.
+//
+// Files have a "size <width> <height>" line, then "legend <char> <tile> [fixture] [mark]" lines
+// saying what each character stands for, then a "tiles" line followed by height rows of width
+// characters each. Lines starting with # before the tiles are comments. Fixtures are door,
+// locked_door, safe and item. Marks are camera and spawn.
+//
+// Prefabs in BUILDINGS_DIR are whole buildings for the generator, drawn with the front door on
+// the bottom edge. They can also have "lots <size>...", "districts <district>..." and
+// "alarmed <yes|no>" lines saying where they go and whether they're wired to the police. Left
+// out, they go anywhere and are alarmed at random.
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    pub name:      String,          // The file's name without .txt, or empty.
+    pub width:     usize,
+    pub height:    usize,
+    pub cells:     Vec<Cell>,       // Row by row from the top left.
+    pub lots:      Vec<LotSize>,    // Empty for any.
+    pub districts: Vec<District>,   // Empty for any.
+    pub alarmed:   Option<bool>
+}
+
+pub static BUILDINGS_DIR: &'static str = "assets/prefabs/buildings";
+
+impl Prefab {
+    // The tiles from min to max inclusive, on level z.
+    pub fn from_world(world: &World, min_x: usize, min_y: usize, max_x: usize, max_y: usize, z: usize) -> Prefab {
+        let mut cells = Vec::new();
+        for y in min_y..(max_y + 1) {
+            for x in min_x..(max_x + 1) {
+                let mut cell = cell_of(&world.tile_at(x, y, z));
+                if world.cameras.iter().any(|c| c.x == x && c.y == y && c.z == z) {
+                    cell.mark = Some(Mark::Camera);
+                } else if world.guards.iter().any(|g| g.x == x && g.y == y && g.z == z) {
+                    cell.mark = Some(Mark::Spawn);
+                }
+                cells.push(cell);
+            }
+        }
+        Prefab {
+            name: String::new(), width: max_x - min_x + 1, height: max_y - min_y + 1, cells: cells,
+            lots: Vec::new(), districts: Vec::new(), alarmed: None
+        }
+    }
+    
+    pub fn cell_at(&self, x: usize, y: usize) -> &Cell {
+        &self.cells[y * self.width + x]
+    }
+    
+    // Where the cells matching are, relative to the top left, row by row.
+    pub fn find<F: Fn(&Cell) -> bool>(&self, matches: F) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        for (i, cell) in self.cells.iter().enumerate() {
+            if matches(cell) {
+                found.push((i % self.width, i / self.width));
+            }
+        }
+        found
+    }
+    
+    // A copy mirrored left to right if asked, then turned clockwise a quarter turn at a time.
+    pub fn transformed(&self, quarter_turns: usize, mirror: bool) -> Prefab {
+        let mut result = self.clone();
+        if mirror {
+            let mut cells = Vec::with_capacity(result.cells.len());
+            for y in 0..result.height {
+                for x in 0..result.width {
+                    cells.push(result.cell_at(result.width - 1 - x, y).clone());
+                }
+            }
+            result.cells = cells;
+        }
+        for _ in 0..(quarter_turns % 4) {
+            // The left column becomes the top row.
+            let (width, height) = (result.height, result.width);
+            let mut cells = Vec::with_capacity(result.cells.len());
+            for y in 0..height {
+                for x in 0..width {
+                    cells.push(result.cell_at(y, width - 1 - x).clone());
+                }
+            }
+            result = Prefab { width: width, height: height, cells: cells, ..result };
+        }
+        result
+    }
+    
+    // Writes the prefab with its top left at (x, y) on level z, puts up its cameras and posts guards
+    // at its spawn points. Parts past the world's edge are left off.
+    pub fn stamp<R: Rng>(&self, world: &mut World, rng: &mut R, x: usize, y: usize, z: usize) {
+        for py in 0..self.height {
+            for px in 0..self.width {
+                let (wx, wy) = (x + px, y + py);
+                if !world.in_bounds(wx as i32, wy as i32) {
+                    continue;
+                }
+                let cell = self.cell_at(px, py);
+                let tile = cell_tile(world, rng, cell, wx, wy);
+                world.replace_tile(wx, wy, z, tile);
+                if cell.mark == Some(Mark::Camera) && !world.cameras.iter().any(|c| c.x == wx && c.y == wy && c.z == z) {
+                    world.cameras.push(SecurityCamera::new(wx, wy, z));
+                }
+                if cell.mark == Some(Mark::Spawn) && !world.guards.iter().any(|g| g.x == wx && g.y == wy && g.z == z) {
+                    world.guards.push(Guard::new(wx, wy, z));
+                }
+            }
+        }
+    }
+    
+    // Every prefab in the directory, in order of file name so generation doesn't depend on the
+    // order the OS lists them in. Fails on the first file that won't load, like load.
+    pub fn load_dir(dir: &Path) -> Result<Vec<Prefab>, String> {
+        let entries = match fs::read_dir(dir) {
+            Err(why) => return Err(format!("Couldn't read {}: {}", dir.display(), why.description())),
+            Ok(entries) => entries
+        };
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for entry in entries {
+            let path = match entry {
+                Err(why) => return Err(format!("Couldn't read {}: {}", dir.display(), why.description())),
+                Ok(entry) => entry.path()
+            };
+            if path.extension().map(|e| e == "txt").unwrap_or(false) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        let mut prefabs = Vec::with_capacity(paths.len());
+        for path in paths.iter() {
+            prefabs.push(try!(Prefab::load(path)));
+        }
+        Ok(prefabs)
+    }
+    
+    // Says what's wrong with the file, and where, rather than panicking, so the editor can show it.
+    pub fn load(path: &Path) -> Result<Prefab, String> {
+        let file = match File::open(path) {
+            Err(why) => return Err(format!("Couldn't open {}: {}", path.display(), why.description())),
+            Ok(file) => file
+        };
+        
+        let mut size = None;
+        let mut lots = Vec::new();
+        let mut districts = Vec::new();
+        let mut alarmed = None;
+        let mut legend: Vec<(char, Cell)> = Vec::new();
+        let mut rows: Option<Vec<String>> = None;
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = match line {
+                Err(why) => return Err(format!("Couldn't read {}: {}", path.display(), why.description())),
+                Ok(line) => line
+            };
+            if let Some(ref mut rows) = rows {
+                rows.push(line);
+                continue;
+            }
+            let words: Vec<&str> = line.split(' ').filter(|w| !w.is_empty()).collect();
+            if words.is_empty() || words[0].starts_with("#") {
+                continue;
+            }
+            match (words[0], words.len()) {
+                ("size", 3) => {
+                    match (words[1].parse(), words[2].parse()) {
+                        (Ok(w), Ok(h)) => { size = Some((w, h)); },
+                        _ => { return Err(format!("{} line {}: expected \"size <width> <height>\"", path.display(), i + 1)); }
+                    }
+                },
+                ("lots", n) if n > 1 => {
+                    for word in words[1..].iter() {
+                        match lot_size_from_name(word) {
+                            Some(lot) => { lots.push(lot); },
+                            None => { return Err(format!("{} line {}: no lot size \"{}\"", path.display(), i + 1, word)); }
+                        }
+                    }
+                },
+                ("districts", n) if n > 1 => {
+                    for word in words[1..].iter() {
+                        match district_from_name(word) {
+                            Some(district) => { districts.push(district); },
+                            None => { return Err(format!("{} line {}: no district \"{}\"", path.display(), i + 1, word)); }
+                        }
+                    }
+                },
+                ("alarmed", 2) => {
+                    match words[1] {
+                        "yes" => { alarmed = Some(true); },
+                        "no"  => { alarmed = Some(false); },
+                        _     => { return Err(format!("{} line {}: expected \"alarmed <yes|no>\"", path.display(), i + 1)); }
+                    }
+                },
+                ("legend", n) if n >= 3 && n <= 5 => {
+                    let c = words[1].chars().next().unwrap();
+                    if words[1].chars().count() != 1 || Tile::from_name(words[2]).is_none() {
+                        return Err(format!("{} line {}: expected \"legend <char> <tile> [fixture] [mark]\"", path.display(), i + 1));
+                    }
+                    let mut cell = Cell { tile: words[2].to_string(), fixture: None, mark: None };
+                    for word in words[3..].iter() {
+                        match (fixture_from_name(word), mark_from_name(word)) {
+                            (Some(fixture), _) => { cell.fixture = Some(fixture); },
+                            (_, Some(mark))    => { cell.mark = Some(mark); },
+                            _ => { return Err(format!("{} line {}: no fixture or mark named \"{}\"", path.display(), i + 1, word)); }
+                        }
+                    }
+                    legend.push((c, cell));
+                },
+                ("tiles", 1) => { rows = Some(Vec::new()); },
+                _ => { return Err(format!("{} line {}: expected size, lots, districts, alarmed, legend or tiles", path.display(), i + 1)); }
+            }
+        }
+        
+        let (width, height) = match size {
+            Some(size) => { size },
+            None       => { return Err(format!("{} doesn't give its size", path.display())) }
+        };
+        let rows = rows.unwrap_or(Vec::new());
+        if rows.len() < height {
+            return Err(format!("{} has {} rows of tiles, not {}", path.display(), rows.len(), height));
+        }
+        let mut cells = Vec::with_capacity(width * height);
+        for (y, row) in rows.iter().take(height).enumerate() {
+            if row.chars().count() != width {
+                return Err(format!("{}: row {} of the tiles isn't {} wide", path.display(), y + 1, width));
+            }
+            for c in row.chars() {
+                match legend.iter().find(|&&(l, _)| l == c) {
+                    Some(&(_, ref cell)) => { cells.push(cell.clone()); },
+                    None => { return Err(format!("{}: '{}' in row {} isn't in the legend", path.display(), c, y + 1)); }
+                }
+            }
+        }
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+        Ok(Prefab {
+            name: name, width: width, height: height, cells: cells,
+            lots: lots, districts: districts, alarmed: alarmed
+        })
+    }
+    
+    // Creates the directory if it isn't there.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            if let Err(why) = fs::create_dir_all(dir) {
+                return Err(format!("Couldn't create {}: {}", dir.display(), why.description()));
+            }
+        }
+        let mut file = match File::create(path) {
+            Err(why) => return Err(format!("Couldn't create {}: {}", path.display(), why.description())),
+            Ok(file) => file
+        };
+        
+        // Give each kind of cell a character, preferring the usual ones.
+        let mut legend: Vec<(char, Cell)> = Vec::new();
+        let mut spare = SPARE_CHARS.chars();
+        for cell in self.cells.iter() {
+            if legend.iter().any(|&(_, ref l)| l == cell) {
+                continue;
+            }
+            let default = DEFAULT_CHARS.iter()
+                .find(|&&(_, tile, fixture, mark)| tile == cell.tile && fixture == cell.fixture && mark == cell.mark)
+                .map(|&(c, _, _, _)| c);
+            let c = match default {
+                Some(c) => { c },
+                None    => {
+                    match spare.find(|&c| !DEFAULT_CHARS.iter().any(|&(d, _, _, _)| d == c)) {
+                        Some(c) => { c },
+                        None    => { return Err(format!("Too many kinds of tile to save {}", path.display())) }
+                    }
+                }
+            };
+            legend.push((c, cell.clone()));
+        }
+        
+        let mut text = format!("size {} {}\n", self.width, self.height);
+        if !self.lots.is_empty() {
+            let names: Vec<&str> = self.lots.iter().map(|&lot| lot_size_name(lot)).collect();
+            text.push_str(&format!("lots {}\n", names.join(" ")));
+        }
+        if !self.districts.is_empty() {
+            let names: Vec<&str> = self.districts.iter().map(|&d| district_name(d)).collect();
+            text.push_str(&format!("districts {}\n", names.join(" ")));
+        }
+        if let Some(alarmed) = self.alarmed {
+            text.push_str(&format!("alarmed {}\n", if alarmed { "yes" } else { "no" }));
+        }
+        for &(c, ref cell) in legend.iter() {
+            text.push_str(&format!("legend {} {}", c, cell.tile));
+            if let Some(fixture) = cell.fixture {
+                text.push_str(&format!(" {}", fixture_name(fixture)));
+            }
+            if let Some(mark) = cell.mark {
+                text.push_str(&format!(" {}", mark_name(mark)));
+            }
+            text.push('\n');
+        }
+        text.push_str("tiles\n");
+        for row in self.cells.chunks(self.width) {
+            for cell in row.iter() {
+                text.push(legend.iter().find(|&&(_, ref l)| l == cell).unwrap().0);
+            }
+            text.push('\n');
+        }
+        if let Err(why) = file.write_all(text.as_bytes()) {
+            return Err(format!("Couldn't write {}: {}", path.display(), why.description()));
+        }
+        Ok(())
+    }
+}
+
+// Occupants the prefab format doesn't know, like open doors, come out as their nearest fixture.
+pub fn cell_of(tile: &Tile) -> Cell {
+    let fixture = tile.occupant().map(|occ| {
+        match *occ.borrow() {
+            Occupant::Door(ref door) => {
+                if door.lock.is_some() { Fixture::LockedDoor } else { Fixture::Door }
+            },
+            Occupant::Container(_) => { Fixture::Safe },
+            Occupant::Item(_)      => { Fixture::Item }
+        }
+    });
+    Cell { tile: tile.without_occupant().sprite_name().to_string(), fixture: fixture, mark: None }
+}
+
+// A fresh tile for the cell at (x, y), rolling the fixture's lock and loot.
+pub fn cell_tile<R: Rng>(world: &mut World, rng: &mut R, cell: &Cell, x: usize, y: usize) -> Tile {
+    let floor = match Tile::from_name(&cell.tile) {
+        Some(tile) => { tile.without_occupant() },
+        None       => { panic!("No tile named \"{}\"", cell.tile) }
+    };
+    match cell.fixture {
+        Some(fixture) => { floor.with_occupant(Rc::new(RefCell::new(make_fixture(world, rng, fixture, x, y)))) },
+        None          => { floor }
+    }
+}
+
+pub fn make_fixture<R: Rng>(world: &mut World, rng: &mut R, fixture: Fixture, x: usize, y: usize) -> Occupant {
+    match fixture {
+        Fixture::Door => {
+            Occupant::Door(Door { lock: None, open: false })
+        },
+        Fixture::LockedDoor => {
+            let key = world.building_containing(x, y).unwrap_or(0) as u32;
+            Occupant::Door(Door { lock: Some(Lock::new(LockKind::Key(key), rng.gen_range(1, 4))), open: false })
+        },
+        Fixture::Safe => {
+            let code = format!("{:04}", rng.gen_range(0, 10000));
+            let mut contents = Vec::new();
+            for _ in 0..rng.gen_range(1, 4) {
+                contents.push(random_item(world, rng));
+            }
+            Occupant::Container(Container {
+                lock: Some(Lock::new(LockKind::Code(code), rng.gen_range(1, 4))),
+                open: false,
+                contents: contents
+            })
+        },
+        Fixture::Item => {
+            Occupant::Item(random_item(world, rng))
+        }
+    }
+}
+
+pub fn fixture_from_name(name: &str) -> Option<Fixture> {
+    match name {
+        "door"        => { Some(Fixture::Door) },
+        "locked_door" => { Some(Fixture::LockedDoor) },
+        "safe"        => { Some(Fixture::Safe) },
+        "item"        => { Some(Fixture::Item) },
+        _             => { None }
+    }
+}
+
+pub fn fixture_name(fixture: Fixture) -> &'static str {
+    match fixture {
+        Fixture::Door       => { "door" },
+        Fixture::LockedDoor => { "locked_door" },
+        Fixture::Safe       => { "safe" },
+        Fixture::Item       => { "item" }
+    }
+}
+
+pub fn mark_from_name(name: &str) -> Option<Mark> {
+    match name {
+        "camera" => { Some(Mark::Camera) },
+        "spawn"  => { Some(Mark::Spawn) },
+        _        => { None }
+    }
+}
+
+pub fn mark_name(mark: Mark) -> &'static str {
+    match mark {
+        Mark::Camera => { "camera" },
+        Mark::Spawn  => { "spawn" }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,153 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use gen::{self, Strategy};
+use input::{self, Frame};
+use sim::Sim;
+
+// A recorded game: the seed and generator, each tick's input and the state hash it ended on.
+// Replaying it runs the same ticks headlessly and should land on the same hash.
+//
+// Files start with "seed <n>", "generator <name>" and "hash <hex>" lines, followed by one line per
+// tick: the actions held, a |, then the actions pressed that tick. Files without a generator line
+// are from before there was a choice, and used the procedural one.
+pub struct Replay {
+    pub seed:     u32,
+    pub strategy: Strategy,
+    pub frames:   Vec<Frame>,
+    pub hash:     u64
+}
+
+impl Replay {
+    pub fn save(&self, path: &Path) {
+        let mut file = match File::create(path) {
+            Err(why) => panic!("Couldn't create {}: {}", path.display(), why.description()),
+            Ok(file) => file
+        };
+        let mut text = format!(
+            "seed {}\ngenerator {}\nhash {:016x}\n", self.seed, gen::strategy_name(self.strategy), self.hash
+        );
+        for frame in self.frames.iter() {
+            let held: Vec<&str> = frame.held.iter().map(|&a| input::action_name(a)).collect();
+            let pressed: Vec<&str> = frame.pressed.iter().map(|&a| input::action_name(a)).collect();
+            text.push_str(&format!("{} | {}\n", held.join(" "), pressed.join(" ")));
+        }
+        if let Err(why) = file.write_all(text.as_bytes()) {
+            panic!("Couldn't write {}: {}", path.display(), why.description());
+        }
+    }
+    
+    pub fn load(path: &Path) -> Replay {
+        let file = match File::open(path) {
+            Err(why) => panic!("Couldn't open {}: {}", path.display(), why.description()),
+            Ok(file) => file
+        };
+        
+        let mut seed = None;
+        let mut strategy = Strategy::Procedural;
+        let mut hash = None;
+        let mut frames = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = match line {
+                Err(why) => panic!("Couldn't read {}: {}", path.display(), why.description()),
+                Ok(line) => line
+            };
+            if line.starts_with("seed ") {
+                seed = line["seed ".len()..].parse().ok();
+            } else if line.starts_with("generator ") {
+                strategy = match gen::strategy_from_name(&line["generator ".len()..]) {
+                    Some(strategy) => { strategy },
+                    None => { panic!("{} line {}: no generator named \"{}\"", path.display(), i + 1, &line["generator ".len()..]) }
+                };
+            } else if line.starts_with("hash ") {
+                hash = u64::from_str_radix(&line["hash ".len()..], 16).ok();
+            } else {
+                let halves: Vec<&str> = line.split('|').collect();
+                if halves.len() != 2 {
+                    panic!("{} line {}: expected \"held | pressed\"", path.display(), i + 1);
+                }
+                frames.push(Frame {
+                    held:    parse_actions(halves[0], path, i),
+                    pressed: parse_actions(halves[1], path, i)
+                });
+            }
+        }
+        
+        match (seed, hash) {
+            (Some(seed), Some(hash)) => { Replay { seed: seed, strategy: strategy, frames: frames, hash: hash } },
+            _                        => { panic!("{} is missing its seed or hash", path.display()) }
+        }
+    }
+    
+    // Re-runs the game from the seed without a window and returns the state hash it ends on.
+    pub fn run(&self) -> u64 {
+        let mut sim = Sim::new(self.seed, self.strategy);
+        for frame in self.frames.iter() {
+            sim.tick(frame);
+        }
+        sim.state_hash()
+    }
+}
+
+fn parse_actions(text: &str, path: &Path, line: usize) -> Vec<input::Action> {
+    text.split(' ').filter(|w| !w.is_empty()).map(|name| {
+        match input::action_from_name(name) {
+            Some(action) => { action },
+            None         => { panic!("{} line {}: no action named \"{}\"", path.display(), line + 1, name) }
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    
+    use gen::Strategy;
+    use input::{Action, Frame};
+    use sim::Sim;
+    use super::Replay;
+    
+    const SEED: u32 = 7;
+    
+    // Five seconds of walking around in a square, trying whatever's in front now and then.
+    fn script() -> Vec<Frame> {
+        let dirs = [Action::MoveRight, Action::MoveDown, Action::MoveLeft, Action::MoveUp];
+        (0..300).map(|i| {
+            let dir = dirs[(i / 60) % 4];
+            let mut pressed = if i % 6 == 0 { vec![dir] } else { Vec::new() };
+            if i % 60 == 30 {
+                pressed.push(Action::Interact);
+            }
+            Frame { held: vec![dir], pressed: pressed }
+        }).collect()
+    }
+    
+    // Plays frames through from the start, as if there were a window, and returns the hash.
+    fn play(frames: &[Frame]) -> u64 {
+        let mut sim = Sim::new(SEED, Strategy::Procedural);
+        for frame in frames.iter() {
+            sim.tick(frame);
+        }
+        sim.state_hash()
+    }
+    
+    // Each Sim generates a whole city, so this plays through as few as it can.
+    #[test]
+    fn replay_matches_recording_and_nothing_else() {
+        let frames = script();
+        let recorded = Replay { seed: SEED, strategy: Strategy::Procedural, hash: play(&frames), frames: frames };
+        let path = env::temp_dir().join("capital_larceny_replay_test.txt");
+        recorded.save(&path);
+        
+        let replay = Replay::load(&path);
+        assert_eq!(replay.frames, recorded.frames);
+        assert_eq!(replay.hash, recorded.hash);
+        assert_eq!(replay.run(), recorded.hash);
+        
+        // Standing still the whole time ends somewhere else.
+        let idle: Vec<Frame> = recorded.frames.iter().map(|_| Frame { held: Vec::new(), pressed: Vec::new() }).collect();
+        assert!(play(&idle) != recorded.hash);
+    }
+}
\ No newline at end of file
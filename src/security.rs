@@ -0,0 +1,84 @@
+use world::World;
+
+// How far a security camera sees, in tiles. Cameras don't care how dark it is.
+pub const CAMERA_RANGE: f32 = 8.0;
+
+// Guards are inside, where the lights are on, but don't see as far as a camera.
+pub const GUARD_RANGE: f32 = 6.0;
+
+// A camera watching the inside of a building. Seeing the player trips the building's alarm, once.
+#[derive(Debug, Clone)]
+pub struct SecurityCamera {
+    pub x:       usize,
+    pub y:       usize,
+    pub z:       usize,
+    pub tripped: bool
+}
+
+impl SecurityCamera {
+    pub fn new(x: usize, y: usize, z: usize) -> SecurityCamera {
+        SecurityCamera { x: x, y: y, z: z, tripped: false }
+    }
+    
+    // Cameras only see their own level.
+    pub fn can_see(&self, world: &World, x: usize, y: usize, z: usize) -> bool {
+        let (dx, dy) = (self.x as f32 - x as f32, self.y as f32 - y as f32);
+        z == self.z && (dx * dx + dy * dy).sqrt() <= CAMERA_RANGE && world.line_of_sight((self.x, self.y), (x, y), z)
+    }
+}
+
+// Someone posted inside a building, where its prefab has a spawn point. Seeing the player raises
+// the alarm, once, and they stay put.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    pub x:       usize,
+    pub y:       usize,
+    pub z:       usize,
+    pub alerted: bool
+}
+
+impl Guard {
+    pub fn new(x: usize, y: usize, z: usize) -> Guard {
+        Guard { x: x, y: y, z: z, alerted: false }
+    }
+    
+    // Guards only see their own level.
+    pub fn can_see(&self, world: &World, x: usize, y: usize, z: usize) -> bool {
+        let (dx, dy) = (self.x as f32 - x as f32, self.y as f32 - y as f32);
+        z == self.z && (dx * dx + dy * dy).sqrt() <= GUARD_RANGE && world.line_of_sight((self.x, self.y), (x, y), z)
+    }
+}
+
+// Trips the alarm for every camera that can see (x, y, z) and hasn't gone off yet. Returns how
+// many did.
+pub fn watch(world: &mut World, x: usize, y: usize, z: usize) -> usize {
+    let mut tripped = Vec::new();
+    for (i, camera) in world.cameras.iter().enumerate() {
+        if !camera.tripped && camera.can_see(world, x, y, z) {
+            tripped.push(i);
+        }
+    }
+    for &i in tripped.iter() {
+        world.cameras[i].tripped = true;
+        let (cx, cy) = (world.cameras[i].x, world.cameras[i].y);
+        world.alarms.push((cx, cy));
+    }
+    tripped.len()
+}
+
+// Alerts every guard who can see (x, y, z) and hasn't been alerted yet, and has them raise the
+// alarm where they stand. Returns how many were.
+pub fn spot(world: &mut World, x: usize, y: usize, z: usize) -> usize {
+    let mut alerted = Vec::new();
+    for (i, guard) in world.guards.iter().enumerate() {
+        if !guard.alerted && guard.can_see(world, x, y, z) {
+            alerted.push(i);
+        }
+    }
+    for &i in alerted.iter() {
+        world.guards[i].alerted = true;
+        let (gx, gy) = (world.guards[i].x, world.guards[i].y);
+        world.alarms.push((gx, gy));
+    }
+    alerted.len()
+}
\ No newline at end of file
@@ -0,0 +1,324 @@
+use std::mem;
+use rand::XorShiftRng;
+
+use clock::Clock;
+use gen;
+use gen::street;
+use input::{Action, Frame};
+use mission::{Mission, MissionStatus};
+use player::Player;
+use police::Police;
+use security;
+use tile::{chunk, Chunk, Occupant, Tile};
+use traffic::{Traffic, VehicleState};
+use world::World;
+
+// The simulation always advances in steps of this many seconds, however fast frames come, so the
+// same input makes the same game.
+pub const TICK: f32 = 1.0 / 60.0;
+
+// Everything that plays out in the game, without anything to do with showing it. Given the same
+// seed, generator and frames of input, it always ends up in the same state.
+pub struct Sim {
+    pub seed:     u32,
+    pub strategy: gen::Strategy,  // How the city's buildings were generated.
+    pub ticks:    u64,
+    
+    pub world:    World,
+    pub player:   Player,
+    pub mission:  Option<Mission>,
+    pub clock:    Clock,
+    pub police:   Police,
+    pub traffic:  Traffic,
+    
+    // Everything that's happened worth telling the player, oldest first.
+    pub log:      Vec<String>,
+    
+    rng:          XorShiftRng
+}
+
+impl Sim {
+    pub fn new(seed: u32, strategy: gen::Strategy) -> Sim {
+        let mut world = World::new(seed, strategy);
+        // On the street, wherever the river or the sea left one.
+        let (start_x, start_y) = world.grid.first_open();
+        let (start_x, start_y) = street::center(start_x, start_y);
+        let mission = Mission::generate(&mut world, &mut gen::rng_from_seed(seed.wrapping_add(1)));
+        let traffic = Traffic::generate(&world, &mut gen::rng_from_seed(seed.wrapping_add(3)));
+        let mut log = Vec::new();
+        if let Some(ref mission) = mission {
+            log.push(format!("Mission briefing:\n{}", mission));
+        }
+        
+        Sim {
+            seed: seed, strategy: strategy, ticks: 0,
+            world: world, player: Player::new(start_x, start_y), mission: mission,
+            // Heists start in the evening.
+            clock: Clock::new(21, 0),
+            police: Police::new(),
+            traffic: traffic,
+            log: log,
+            rng: gen::rng_from_seed(seed.wrapping_add(2))
+        }
+    }
+    
+    // Advances by one TICK, acting on the frame's input.
+    pub fn tick(&mut self, frame: &Frame) {
+        let dt = TICK;
+        self.ticks = self.ticks + 1;
+        self.clock.advance(dt);
+        
+        // See assets/config/bindings.txt for what's bound to what. Behind the wheel, moving steers
+        // the car instead, and nothing can be picked up or dropped.
+        let on_foot = self.player.driving.is_none();
+        for &action in frame.pressed.iter() {
+            match action {
+                Action::MoveUp    if on_foot => { self.walk( 0, -1); },
+                Action::MoveDown  if on_foot => { self.walk( 0,  1); },
+                Action::MoveLeft  if on_foot => { self.walk(-1,  0); },
+                Action::MoveRight if on_foot => { self.walk( 1,  0); },
+                Action::Interact  => {
+                    if !self.get_in_or_out() {
+                        self.player.interact_facing(&mut self.world);
+                    }
+                },
+                Action::PickUp    if on_foot => { self.player.pick_up(&mut self.world); },
+                Action::Drop      if on_foot => {
+                    // Drops the last item picked up.
+                    let last = self.player.inventory.items.len();
+                    if last > 0 {
+                        self.player.drop_item(&mut self.world, last - 1);
+                    }
+                },
+                _ => {}
+            }
+        }
+        
+        if let Some(car) = self.player.driving {
+            let steer = [
+                (Action::MoveUp, (0, -1)), (Action::MoveDown, (0, 1)),
+                (Action::MoveLeft, (-1, 0)), (Action::MoveRight, (1, 0))
+            ].iter().find(|&&(action, _)| frame.held.contains(&action)).map(|&(_, dir)| dir);
+            self.traffic.steer(car, &self.world, steer, dt);
+            let (x, y) = self.traffic.vehicles[car].tile();
+            self.player.x = x;
+            self.player.y = y;
+            self.player.facing = self.traffic.vehicles[car].heading;
+        }
+        let on_foot = if self.player.driving.is_none() && self.player.z == 0 { Some((self.player.x, self.player.y)) } else { None };
+        self.traffic.update(&self.world, on_foot, dt, &mut self.rng);
+        
+        if let Some(haul) = self.player.update_location(&self.world) {
+            if !haul.items.is_empty() {
+                self.log.push(format!("{}", haul));
+            }
+            if let Some(ref mut mission) = self.mission {
+                if let Some(report) = mission.on_exit(&haul, &self.player) {
+                    self.log.push(format!("{}", report));
+                }
+            }
+        }
+        
+        self.player.update(&mut self.world, dt);
+        
+        if security::watch(&mut self.world, self.player.x, self.player.y, self.player.z) > 0 {
+            self.log.push("A security camera spotted you.".to_string());
+        }
+        if security::spot(&mut self.world, self.player.x, self.player.y, self.player.z) > 0 {
+            self.log.push("A guard spotted you.".to_string());
+        }
+        
+        let wanted_level = self.police.wanted_level();
+        self.police.update(&self.world, &self.player, &self.clock, dt, &mut self.rng);
+        if self.police.wanted_level() != wanted_level {
+            self.log.push(format!("Wanted level: {}", self.police.wanted_level()));
+        }
+        
+        if let Some(ref mut mission) = self.mission {
+            if !self.world.alarms.is_empty() {
+                mission.alarm_raised();
+            }
+            mission.update(&self.world, dt);
+            if mission.status == MissionStatus::InProgress && mission.time_left() == 0.0 {
+                self.log.push(format!("Out of time.\n{}", mission.abandon(&self.player)));
+            }
+        }
+        
+        // Everyone who listens for sounds and alarms has had their chance.
+        self.world.sounds.clear();
+        self.world.alarms.clear();
+    }
+    
+    // A fingerprint of the game's state, for checking replays end up where the original did.
+    // Covers the player, police, traffic, cameras, guards, clock, mission and every tile.
+    pub fn state_hash(&self) -> u64 {
+        let mut h = Fnv::new();
+        h.write_u64(self.ticks);
+        
+        h.write_u32(self.player.x as u32);
+        h.write_u32(self.player.y as u32);
+        h.write_u32(self.player.z as u32);
+        for item in self.player.inventory.items.iter() {
+            h.write_u32(item.id);
+        }
+        if let Some(ref action) = self.player.action {
+            h.write_f32(action.remaining);
+        }
+        
+        if let Some(car) = self.player.driving {
+            h.write_u32(car as u32);
+        }
+        
+        h.write_f32(self.police.heat);
+        for unit in self.police.units.iter() {
+            h.write_f32(unit.x);
+            h.write_f32(unit.y);
+        }
+        
+        for vehicle in self.traffic.vehicles.iter() {
+            h.write_f32(vehicle.x);
+            h.write_f32(vehicle.y);
+            h.write_u32(vehicle.heading.0 as u32);
+            h.write_u32(vehicle.heading.1 as u32);
+            h.write_f32(vehicle.speed);
+            h.write(&[match vehicle.state {
+                VehicleState::Driving    => { 0 },
+                VehicleState::Stopped(_) => { 1 },
+                VehicleState::Parked(_)  => { 2 },
+                VehicleState::Player     => { 3 }
+            }]);
+        }
+        
+        for camera in self.world.cameras.iter() {
+            h.write(&[camera.tripped as u8]);
+        }
+        for guard in self.world.guards.iter() {
+            h.write(&[guard.alerted as u8]);
+        }
+        
+        h.write_u32(self.clock.day);
+        h.write_f32(self.clock.seconds);
+        
+        if let Some(ref mission) = self.mission {
+            h.write_f32(mission.elapsed);
+            h.write(format!("{:?} {:?} {:?}", mission.status, mission.completed, mission.broken).as_bytes());
+        }
+        
+        for row in self.world.chunks.iter() {
+            for chunk in row.iter() {
+                hash_chunk(&mut h, &chunk.borrow());
+            }
+        }
+        for chunk in self.world.upper_chunks.values() {
+            hash_chunk(&mut h, &chunk.borrow());
+        }
+        
+        h.finish()
+    }
+    
+    // Steps the player one tile, unless there's a car or a guard in the way.
+    fn walk(&mut self, dx: i32, dy: i32) {
+        let (x, y) = (self.player.x as i32 + dx, self.player.y as i32 + dy);
+        let z = self.player.z;
+        let guarded = self.world.guards.iter().any(|g| g.x as i32 == x && g.y as i32 == y && g.z == z);
+        if guarded || (z == 0 && self.traffic.vehicle_at(x, y).is_some()) {
+            self.player.facing = (dx, dy);
+            return;
+        }
+        self.player.try_move(&mut self.world, dx, dy);
+    }
+    
+    // Gets out of the car the player's driving, or into the one in front of them. Returns false
+    // if they're on foot with no car in front of them.
+    fn get_in_or_out(&mut self) -> bool {
+        if let Some(car) = self.player.driving {
+            match self.traffic.get_out(car, &self.world, &self.player.keys) {
+                Some((x, y)) => {
+                    self.player.driving = None;
+                    self.player.x = x;
+                    self.player.y = y;
+                },
+                None => { self.log.push("There's nowhere to leave the car here.".to_string()); }
+            }
+            return true;
+        }
+        
+        let x = self.player.x as i32 + self.player.facing.0;
+        let y = self.player.y as i32 + self.player.facing.1;
+        let car = match self.traffic.vehicle_at(x, y) {
+            Some(car) if self.player.z == 0 => { car },
+            _                               => { return false; }
+        };
+        if self.traffic.get_in(car, &mut self.world.sounds) {
+            self.log.push("You broke into a car. Its alarm's going off.".to_string());
+            self.player.driving = Some(car);
+            self.player.action = None;
+            let (x, y) = self.traffic.vehicles[car].tile();
+            self.player.x = x;
+            self.player.y = y;
+        } else {
+            self.log.push("It's moving.".to_string());
+        }
+        true
+    }
+}
+
+fn hash_chunk(h: &mut Fnv, chunk: &Chunk) {
+    for y in chunk.min_y()..(chunk.min_y() + chunk::SIZE) {
+        for x in chunk.min_x()..(chunk.min_x() + chunk::SIZE) {
+            hash_tile(h, chunk.tile_at(x, y));
+        }
+    }
+}
+
+fn hash_tile(h: &mut Fnv, tile: &Tile) {
+    h.write(tile.sprite_name().as_bytes());
+    if let Some(occ) = tile.occupant() {
+        let occ = occ.borrow();
+        if let Some(lock) = occ.lock() {
+            h.write(&[lock.locked as u8]);
+        }
+        match *occ {
+            Occupant::Door(_)                  => {},
+            Occupant::Container(ref container) => {
+                for item in container.contents.iter() {
+                    h.write_u32(item.id);
+                }
+            },
+            Occupant::Item(ref item)           => { h.write_u32(item.id); }
+        }
+    }
+}
+
+// 64-bit FNV-1a. Unlike the standard library's hashers, it's guaranteed never to change between
+// builds, so hashes can be saved in replay files.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+    
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+    
+    fn write_u32(&mut self, n: u32) {
+        self.write(&[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]);
+    }
+    
+    fn write_u64(&mut self, n: u64) {
+        self.write_u32(n as u32);
+        self.write_u32((n >> 32) as u32);
+    }
+    
+    fn write_f32(&mut self, n: f32) {
+        self.write_u32(unsafe { mem::transmute(n) });
+    }
+    
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+// A noise made somewhere in the world. Anyone within loudness tiles of it can hear it.
+#[derive(Debug, Clone)]
+pub struct Sound {
+    pub x:        usize,
+    pub y:        usize,
+    pub loudness: f32
+}
+
+impl Sound {
+    pub fn new(x: usize, y: usize, loudness: f32) -> Sound {
+        Sound { x: x, y: y, loudness: loudness }
+    }
+    
+    pub fn audible_from(&self, x: usize, y: usize) -> bool {
+        let dx = self.x as f32 - x as f32;
+        let dy = self.y as f32 - y as f32;
+        (dx * dx + dy * dy).sqrt() <= self.loudness
+    }
+}
\ No newline at end of file
@@ -0,0 +1,89 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use image;
+use image::RgbaImage;
+
+// A monospaced bitmap font. The glyphs sit in a grid of equal cells, in character order.
+pub struct Font {
+    pub image:       RgbaImage,
+    pub cell_width:  u32,
+    pub cell_height: u32,
+    first_char:      u32,
+    columns:         u32
+}
+
+impl Font {
+    // Loads the glyph image and its metrics. See assets/fonts/mono.txt for the format.
+    pub fn load(image_path: &Path, metrics_path: &Path) -> Font {
+        let image = match image::open(image_path) {
+            Err(why) => panic!("Couldn't open {}: {}", image_path.display(), why.description()),
+            Ok(image) => image.to_rgba()
+        };
+        
+        let file = match File::open(metrics_path) {
+            Err(why) => panic!("Couldn't open {}: {}", metrics_path.display(), why.description()),
+            Ok(file) => file
+        };
+        
+        let mut font = Font { image: image, cell_width: 0, cell_height: 0, first_char: 0, columns: 0 };
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = match line {
+                Err(why) => panic!("Couldn't read {}: {}", metrics_path.display(), why.description()),
+                Ok(line) => line
+            };
+            let words: Vec<&str> = line.split(' ').filter(|w| !w.is_empty()).collect();
+            if words.is_empty() || words[0].starts_with("#") {
+                continue;
+            }
+            if words.len() != 2 {
+                panic!("{} line {}: expected \"name value\"", metrics_path.display(), i + 1);
+            }
+            let value = match words[1].parse() {
+                Ok(n)  => { n },
+                Err(_) => {
+                    panic!("{} line {}: \"{}\" isn't a number", metrics_path.display(), i + 1, words[1])
+                }
+            };
+            match words[0] {
+                "cell_width"  => { font.cell_width = value; },
+                "cell_height" => { font.cell_height = value; },
+                "first_char"  => { font.first_char = value; },
+                "columns"     => { font.columns = value; },
+                name          => {
+                    panic!("{} line {}: unknown metric \"{}\"", metrics_path.display(), i + 1, name);
+                }
+            }
+        }
+        if font.cell_width == 0 || font.cell_height == 0 || font.columns == 0 {
+            panic!("{} doesn't set the cell size and columns", metrics_path.display());
+        }
+        font
+    }
+    
+    // Texture coords of the glyph for c as (u min, v min, u max, v max). Characters the font
+    // doesn't have come out as a question mark.
+    pub fn uv(&self, c: char) -> (f32, f32, f32, f32) {
+        let (w, h) = self.image.dimensions();
+        let rows = h / self.cell_height;
+        let mut i = (c as u32).wrapping_sub(self.first_char);
+        if i >= rows * self.columns {
+            i = '?' as u32 - self.first_char;
+        }
+        let (col, row) = (i % self.columns, i / self.columns);
+        (
+            (col * self.cell_width) as f32 / w as f32,
+            (row * self.cell_height) as f32 / h as f32,
+            ((col + 1) * self.cell_width) as f32 / w as f32,
+            ((row + 1) * self.cell_height) as f32 / h as f32
+        )
+    }
+    
+    // Width and height of the text in pixels. Lines break at newlines.
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        (longest as u32 * self.cell_width, lines.len() as u32 * self.cell_height)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+mod font;
+mod program;
+mod renderer;
+
+pub use self::font::Font;
+pub use self::program::Program;
+pub use self::renderer::Renderer;
\ No newline at end of file
@@ -0,0 +1,31 @@
+use std::path::Path;
+use gl::types::*;
+
+use glutil;
+
+pub struct Program {
+    pub id:              GLuint,
+    
+    pub screen_size_idx: GLint,
+    pub font_idx:        GLint,
+    
+    pub position_idx:    GLuint,
+    pub uv_idx:          GLuint,
+    pub color_idx:       GLuint
+}
+
+impl Program {
+    pub fn new() -> Program {
+        let id = glutil::make_program(&Path::new("glsl/text.vert.glsl"), &Path::new("glsl/text.frag.glsl"));
+        Program {
+            id:              id,
+            
+            screen_size_idx: glutil::get_uniform_location(id, "screenSize"),
+            font_idx:        glutil::get_uniform_location(id, "font"),
+            
+            position_idx:    glutil::get_attrib_location( id, "position"),
+            uv_idx:          glutil::get_attrib_location( id, "uv"),
+            color_idx:       glutil::get_attrib_location( id, "color")
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,170 @@
+use std::ptr;
+use gl;
+use gl::types::*;
+use libc::c_void;
+
+use super::{Font, Program};
+
+// Floats per vertex in each buffer.
+const POSITION_FLOATS: usize = 2;
+const UV_FLOATS:       usize = 2;
+const COLOR_FLOATS:    usize = 4;
+
+// Draws text and flat rectangles on top of everything else, in screen coords: pixels from the
+// window's top left. Queue up the frame's text, then draw it all at once.
+pub struct Renderer {
+    pub font:        Font,
+    
+    vao:             GLuint,
+    position_buffer: GLuint,
+    uv_buffer:       GLuint,
+    color_buffer:    GLuint,
+    texture:         GLuint,
+    
+    positions:       Vec<f32>,
+    uvs:             Vec<f32>,
+    colors:          Vec<f32>
+}
+
+impl Renderer {
+    pub fn new(program: &Program, font: Font) -> Renderer {
+        let mut renderer = Renderer {
+            font: font,
+            vao: 0, position_buffer: 0, uv_buffer: 0, color_buffer: 0, texture: 0,
+            positions: Vec::new(), uvs: Vec::new(), colors: Vec::new()
+        };
+        
+        unsafe {
+            gl::GenVertexArrays(1, &mut renderer.vao);
+            gl::GenBuffers(1,      &mut renderer.position_buffer);
+            gl::GenBuffers(1,      &mut renderer.uv_buffer);
+            gl::GenBuffers(1,      &mut renderer.color_buffer);
+            
+            gl::BindVertexArray(renderer.vao);
+            for &(buffer, idx, floats) in [
+                (renderer.position_buffer, program.position_idx, POSITION_FLOATS),
+                (renderer.uv_buffer,       program.uv_idx,       UV_FLOATS),
+                (renderer.color_buffer,    program.color_idx,    COLOR_FLOATS)
+            ].iter() {
+                gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+                gl::EnableVertexAttribArray(idx);
+                gl::VertexAttribPointer(idx, floats as i32, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            }
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            
+            // Glyphs are drawn pixel for pixel, so nearest filtering keeps them sharp.
+            let (width, height) = renderer.font.image.dimensions();
+            gl::GenTextures(1, &mut renderer.texture);
+            gl::BindTexture(gl::TEXTURE_2D, renderer.texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::RGBA as GLint,
+                width as GLsizei, height as GLsizei, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE,
+                renderer.font.image.as_ptr() as *const c_void
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        renderer
+    }
+    
+    // Queues text with its top left at (x, y). Lines break at newlines.
+    pub fn queue(&mut self, x: f32, y: f32, text: &str, color: (f32, f32, f32, f32)) {
+        let (cell_w, cell_h) = (self.font.cell_width as f32, self.font.cell_height as f32);
+        let (mut pen_x, mut pen_y) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                pen_x = x;
+                pen_y = pen_y + cell_h;
+                continue;
+            }
+            if c != ' ' {
+                let (u0, v0, u1, v1) = self.font.uv(c);
+                let (x0, y0, x1, y1) = (pen_x, pen_y, pen_x + cell_w, pen_y + cell_h);
+                self.positions.push_all(&[x0, y0, x1, y0, x1, y1, x0, y0, x1, y1, x0, y1]);
+                self.uvs.push_all(&[u0, v0, u1, v0, u1, v1, u0, v0, u1, v1, u0, v1]);
+                for _ in 0..6 {
+                    self.colors.push_all(&[color.0, color.1, color.2, color.3]);
+                }
+            }
+            pen_x = pen_x + cell_w;
+        }
+    }
+    
+    // Queues a solid rectangle with its top left at (x, y). Rectangles and text draw in the order
+    // they're queued, so queue backgrounds first.
+    pub fn queue_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: (f32, f32, f32, f32)) {
+        let (x0, y0, x1, y1) = (x, y, x + width, y + height);
+        self.positions.push_all(&[x0, y0, x1, y0, x1, y1, x0, y0, x1, y1, x0, y1]);
+        // Negative texture coords tell the shader to skip the font.
+        for _ in 0..6 {
+            self.uvs.push_all(&[-1.0, -1.0]);
+            self.colors.push_all(&[color.0, color.1, color.2, color.3]);
+        }
+    }
+    
+    // Queues a line thickness pixels wide from (x0, y0) to (x1, y1), at any angle.
+    pub fn queue_line(
+        &mut self, x0: f32, y0: f32, x1: f32, y1: f32, thickness: f32, color: (f32, f32, f32, f32)
+    ) {
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        // Half the thickness either side, at right angles to the line.
+        let (nx, ny) = (-dy / length * thickness / 2.0, dx / length * thickness / 2.0);
+        self.positions.push_all(&[
+            x0 + nx, y0 + ny, x1 + nx, y1 + ny, x1 - nx, y1 - ny,
+            x0 + nx, y0 + ny, x1 - nx, y1 - ny, x0 - nx, y0 - ny
+        ]);
+        for _ in 0..6 {
+            self.uvs.push_all(&[-1.0, -1.0]);
+            self.colors.push_all(&[color.0, color.1, color.2, color.3]);
+        }
+    }
+    
+    // Draws everything queued since the last call, then clears the queue.
+    pub fn draw(&mut self, program: &Program, width: u16, height: u16) {
+        if self.positions.is_empty() {
+            return;
+        }
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::UseProgram(program.id);
+            gl::Uniform2f(program.screen_size_idx, width as f32, height as f32);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::Uniform1i(program.font_idx, 0);
+            
+            for &(buffer, data) in [
+                (self.position_buffer, &self.positions),
+                (self.uv_buffer,       &self.uvs),
+                (self.color_buffer,    &self.colors)
+            ].iter() {
+                gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    4 * data.len() as i64,
+                    data.as_ptr() as *const c_void,
+                    gl::STREAM_DRAW
+                );
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, (self.positions.len() / POSITION_FLOATS) as GLsizei);
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        self.positions.clear();
+        self.uvs.clear();
+        self.colors.clear();
+    }
+}
\ No newline at end of file
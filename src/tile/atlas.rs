@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use image;
+use image::RgbaImage;
+
+use super::autotile;
+
+// A texture holding every tile sprite, plus where in it each sprite is.
+pub struct Atlas {
+    pub image:       RgbaImage,
+    pub sprite_size: u32,
+    
+    // Each sprite's variants, as (column, row) in sprites.
+    sprites:         HashMap<String, Vec<(u32, u32)>>,
+    
+    // Where each autotile set starts. A set's sprites run left to right, wrapping onto the next
+    // row, in the order autotile::index_table gives.
+    autotiles:       HashMap<String, (u32, u32)>,
+    autotile_index:  Vec<usize>
+}
+
+impl Atlas {
+    // Loads the image and the sprite definitions. See assets/tiles/sprites.txt for the format.
+    pub fn load(image_path: &Path, sprites_path: &Path) -> Atlas {
+        let image = match image::open(image_path) {
+            Err(why) => panic!("Couldn't open {}: {}", image_path.display(), why.description()),
+            Ok(image) => image.to_rgba()
+        };
+        
+        let file = match File::open(sprites_path) {
+            Err(why) => panic!("Couldn't open {}: {}", sprites_path.display(), why.description()),
+            Ok(file) => file
+        };
+        
+        let mut sprite_size = 0;
+        let mut sprites: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+        let mut autotiles: HashMap<String, (u32, u32)> = HashMap::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = match line {
+                Err(why) => panic!("Couldn't read {}: {}", sprites_path.display(), why.description()),
+                Ok(line) => line
+            };
+            let words: Vec<&str> = line.split(' ').filter(|w| !w.is_empty()).collect();
+            if words.is_empty() || words[0].starts_with("#") {
+                continue;
+            }
+            match (words[0], words.len()) {
+                ("sprite_size", 2) => {
+                    sprite_size = parse_number(words[1], sprites_path, i);
+                },
+                ("autotile", 4) => {
+                    let col = parse_number(words[2], sprites_path, i);
+                    let row = parse_number(words[3], sprites_path, i);
+                    autotiles.insert(words[1].to_string(), (col, row));
+                },
+                (name, 3) => {
+                    let col = parse_number(words[1], sprites_path, i);
+                    let row = parse_number(words[2], sprites_path, i);
+                    sprites.entry(name.to_string()).or_insert(Vec::new()).push((col, row));
+                },
+                _ => {
+                    panic!("{} line {}: expected \"name column row\"", sprites_path.display(), i + 1);
+                }
+            }
+        }
+        if sprite_size == 0 {
+            panic!("{} doesn't set sprite_size", sprites_path.display());
+        }
+        
+        Atlas {
+            image: image, sprite_size: sprite_size, sprites: sprites,
+            autotiles: autotiles, autotile_index: autotile::index_table()
+        }
+    }
+    
+    // Texture coords of the named sprite as (u min, v min, u max, v max), picking a variant by
+    // the tile's position.
+    pub fn uv(&self, name: &str, x: usize, y: usize) -> (f32, f32, f32, f32) {
+        let variants = match self.sprites.get(name) {
+            Some(variants) => { variants },
+            None           => { panic!("No sprite named \"{}\" in the atlas", name) }
+        };
+        let (col, row) = variants[position_hash(x, y) as usize % variants.len()];
+        self.sprite_uv(col, row)
+    }
+    
+    pub fn has_autotile(&self, name: &str) -> bool {
+        self.autotiles.contains_key(name)
+    }
+    
+    // Texture coords of the sprite in the named autotile set for a reduced neighbour mask.
+    pub fn autotile_uv(&self, name: &str, mask: u8) -> (f32, f32, f32, f32) {
+        let (start_col, start_row) = match self.autotiles.get(name) {
+            Some(&start) => { start },
+            None         => { panic!("No autotile set named \"{}\" in the atlas", name) }
+        };
+        let per_row = self.image.dimensions().0 / self.sprite_size;
+        let i = start_row * per_row + start_col + self.autotile_index[mask as usize] as u32;
+        self.sprite_uv(i % per_row, i / per_row)
+    }
+    
+    fn sprite_uv(&self, col: u32, row: u32) -> (f32, f32, f32, f32) {
+        // Inset a hair so neighbouring sprites never bleed in.
+        let (w, h) = self.image.dimensions();
+        let s = self.sprite_size as f32;
+        let inset = 0.01;
+        (
+            (col as f32 * s + inset)       / w as f32,
+            (row as f32 * s + inset)       / h as f32,
+            ((col + 1) as f32 * s - inset) / w as f32,
+            ((row + 1) as f32 * s - inset) / h as f32
+        )
+    }
+}
+
+fn parse_number(word: &str, path: &Path, line: usize) -> u32 {
+    match word.parse() {
+        Ok(n)  => { n },
+        Err(_) => { panic!("{} line {}: \"{}\" isn't a number", path.display(), line + 1, word) }
+    }
+}
+
+// Scrambles a tile position into a well-mixed number, for picking variants.
+pub fn position_hash(x: usize, y: usize) -> u32 {
+    let mut h = (x as u32).wrapping_mul(73856093) ^ (y as u32).wrapping_mul(19349663);
+    h = (h ^ (h >> 13)).wrapping_mul(0x5bd1e995);
+    h ^ (h >> 15)
+}
\ No newline at end of file
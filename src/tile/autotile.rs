@@ -0,0 +1,76 @@
+use super::{Tile, Occupant};
+use super::Tile::*;
+use super::FloorTileType::*;
+
+// Bits of a neighbour mask, one per neighbour a tile joins up with.
+pub const N:  u8 = 1;
+pub const NE: u8 = 2;
+pub const E:  u8 = 4;
+pub const SE: u8 = 8;
+pub const S:  u8 = 16;
+pub const SW: u8 = 32;
+pub const W:  u8 = 64;
+pub const NW: u8 = 128;
+
+// Joined on every side. Tiles like this are drawn with their ordinary sprites.
+pub const ALL: u8 = 255;
+
+// Offsets to each neighbour, in the order of the bits above.
+pub static OFFSETS: [(i32, i32); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+// How many distinct masks there are after reduce, so how many sprites an autotile set needs.
+pub const SET_SIZE: usize = 47;
+
+// Whether the tile's sprite depends on its neighbours.
+pub fn is_autotiled(tile: &Tile) -> bool {
+    match *tile {
+        WallTile(_)                 => { true },
+        FloorTile(GrassFloor, None) => { true },
+        FloorTile(CurbFloor, None)  => { true },
+        _                           => { false }
+    }
+}
+
+// Whether tile joins up with its neighbour. Walls run into doors as well as other walls, and curbs
+// only break where they meet the road.
+pub fn joins(tile: &Tile, neighbour: &Tile) -> bool {
+    match (tile, neighbour) {
+        (&WallTile(_), &WallTile(_))                             => { true },
+        (&WallTile(_), &FloorTile(_, Some(ref occ)))             => {
+            match *occ.borrow() {
+                Occupant::Door(_) => { true },
+                _                 => { false }
+            }
+        },
+        (&FloorTile(GrassFloor, _), &FloorTile(GrassFloor, _))   => { true },
+        (&FloorTile(CurbFloor, _), &FloorTile(CurbFloor, _))     => { true },
+        (&FloorTile(CurbFloor, _), &FloorTile(SidewalkFloor, _)) => { true },
+        _                                                        => { false }
+    }
+}
+
+// Drops the corners that can't show. A corner only matters when both sides next to it are joined,
+// since otherwise the edge along that side covers it anyway.
+pub fn reduce(mask: u8) -> u8 {
+    let mut reduced = mask & (N | E | S | W);
+    for &(corner, a, b) in [(NE, N, E), (SE, S, E), (SW, S, W), (NW, N, W)].iter() {
+        if mask & corner != 0 && mask & a != 0 && mask & b != 0 {
+            reduced = reduced | corner;
+        }
+    }
+    reduced
+}
+
+// Maps each reduced mask to its sprite's position in an autotile set. Sets list their sprites in
+// order of the masks they're for, from 0 up to ALL. Other masks map to 0.
+pub fn index_table() -> Vec<usize> {
+    let mut table = vec![0; 256];
+    let mut next = 0;
+    for mask in 0..256 {
+        if reduce(mask as u8) == mask as u8 {
+            table[mask] = next;
+            next = next + 1;
+        }
+    }
+    table
+}
\ No newline at end of file
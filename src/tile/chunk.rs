@@ -1,55 +1,39 @@
-use std::ptr;
-use std::mem;
 use std::default::Default;
 use image::{Pixel, RgbImage};
-use gl;
-use gl::types::*;
-use libc::c_void;
 
-use super::Tile;
-use super::Program;
-use camera::Camera;
+use super::{autotile, Atlas, Tile};
+use super::Tile::EmptyTile;
+use light::Light;
+use world::World;
 
 pub const SIZE: usize = 20;
 
 pub struct Chunk {
     min_x:           usize,
     min_y:           usize,
+    z:               usize,  // The level, from 0 at the ground up.
     
     tiles:           Vec<Tile>,
     
-    vao:             GLuint,
-    position_buffer: GLuint,
-    color_buffer:    GLuint,
-    index_buffer:    GLuint,
-    
-    index_count:     i32
+    // Lights positioned in this chunk, and how brightly lit each tile is by lights here and in
+    // neighbouring chunks. The world computes the latter.
+    pub lights:      Vec<Light>,
+    pub light_map:   Vec<f32>
 }
 
 // 32 x 32 tiles.
 impl Chunk {
-    pub fn new(program: &Program, min_x: usize, min_y: usize) -> Chunk {
-        let mut chunk = Chunk {
-            min_x: min_x, min_y: min_y,
+    pub fn new(min_x: usize, min_y: usize, z: usize) -> Chunk {
+        Chunk {
+            min_x: min_x, min_y: min_y, z: z,
             tiles: Vec::with_capacity(SIZE * SIZE),
-            vao: 0, position_buffer: 0, color_buffer: 0, index_buffer: 0, index_count: 0
-        };
-        
-        unsafe {
-            gl::GenVertexArrays(1, &mut chunk.vao);
-            gl::GenBuffers(1,      &mut chunk.position_buffer);
-            gl::GenBuffers(1,      &mut chunk.color_buffer);
-            gl::GenBuffers(1,      &mut chunk.index_buffer);
+            lights: Vec::new(), light_map: vec![0.0; SIZE * SIZE]
         }
-        
-        chunk.configure_vao(program);
-        
-        chunk
     }
     
-    // Warning: This doesn't buffer to the GPU. Call buffer yourself on the returned chunk.
-    pub fn blank(program: &Program, min_x: usize, min_y: usize) -> Chunk {
-        let mut chunk = Chunk::new(program, min_x, min_y);
+    // Warning: This doesn't buffer to the GPU. Have the renderer upload the returned chunk.
+    pub fn blank(min_x: usize, min_y: usize) -> Chunk {
+        let mut chunk = Chunk::new(min_x, min_y, 0);
         
         for _ in 0..SIZE {
             for _ in 0..SIZE {
@@ -60,14 +44,24 @@ impl Chunk {
         chunk
     }
     
+    // All open air, for a level above the ground.
+    pub fn empty(min_x: usize, min_y: usize, z: usize) -> Chunk {
+        let mut chunk = Chunk::new(min_x, min_y, z);
+        
+        for _ in 0..(SIZE * SIZE) {
+            chunk.tiles.push(EmptyTile);
+        }
+        
+        chunk
+    }
+    
     #[allow(dead_code)]
     pub fn from_image_buffer(
-        program: &Program,
         chunk_min_x: usize, chunk_min_y: usize,
         image_buf: &RgbImage,
         img_min_x: usize, img_min_y: usize
     ) -> Chunk {
-        let mut chunk = Chunk::new(program, chunk_min_x, chunk_min_y);
+        let mut chunk = Chunk::new(chunk_min_x, chunk_min_y, 0);
         
         let img_max_x = img_min_x + SIZE;
         let img_max_y = img_min_y + SIZE;
@@ -79,90 +73,69 @@ impl Chunk {
           }
         }
         
-        chunk.buffer();
-        
         chunk
     }
     
-    pub fn buffer(&mut self) {
-        let chunk_size_sq = SIZE * SIZE;
-        // 32 tiles x 32 tiles x 4 vertices x 2 floats.
-        let mut positions: Vec<f32> = Vec::with_capacity(8 * chunk_size_sq);
-        // 32 tiles x 32 tiles x 4 vertices x 3 floats.
-        let mut colors: Vec<f32> = Vec::with_capacity(12 * chunk_size_sq);
-        // 32 tiles x 32 tiles x 6 indices.
-        let mut indices: Vec<u16> = Vec::with_capacity(6 * chunk_size_sq);
-        
+    // Appends this chunk's vertex data. Every chunk has the same number of vertices in the same
+    // order, so the renderer can share one index buffer between them all. The world is for
+    // autotiling tiles along the edges against the neighbouring chunks.
+    pub fn mesh(
+        &self, world: &World, atlas: &Atlas,
+        positions: &mut Vec<f32>, colors: &mut Vec<f32>, uvs: &mut Vec<f32>, lights: &mut Vec<f32>
+    ) {
         for y in 0..SIZE {
             for x in 0..SIZE {
                 let tile = &self.tiles[(y * SIZE + x)];
+                let (world_x, world_y) = (x + self.min_x, y + self.min_y);
+                let mask = if autotile::is_autotiled(tile) {
+                    self.autotile_mask(world, tile, world_x, world_y)
+                } else {
+                    autotile::ALL
+                };
                 tile.buffer(
-                    &mut positions, &mut colors, &mut indices,
-                    x + self.min_x, y + self.min_y
+                    atlas, positions, colors, uvs, lights,
+                    world_x, world_y, self.light_map[y * SIZE + x], mask
                 );
             }
         }
-        
-        self.index_count = indices.len() as i32;
-        
-        unsafe {
-          gl::BindBuffer(gl::ARRAY_BUFFER, self.position_buffer);
-          gl::BufferData(
-              gl::ARRAY_BUFFER,
-              4 * positions.len() as i64,
-              positions.as_ptr() as *const c_void,
-              gl::DYNAMIC_DRAW
-          );
-          
-          gl::BindBuffer(gl::ARRAY_BUFFER, self.color_buffer);
-          gl::BufferData(
-              gl::ARRAY_BUFFER,
-              4 * colors.len() as i64,
-              colors.as_ptr() as *const c_void,
-              gl::DYNAMIC_DRAW
-          );
-          
-          gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-          
-          gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_buffer);
-          gl::BufferData(
-              gl::ELEMENT_ARRAY_BUFFER,
-              2 * positions.len() as i64,
-              indices.as_ptr() as *const c_void,
-              gl::DYNAMIC_DRAW
-          );
-          gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-        }
     }
     
-    fn configure_vao(&mut self, program: &Program) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.position_buffer);
-            gl::EnableVertexAttribArray(program.position_idx);
-            gl::VertexAttribPointer(program.position_idx, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
-            
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.color_buffer);
-            gl::EnableVertexAttribArray(program.color_idx);
-            gl::VertexAttribPointer(program.color_idx, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
-            
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
+    // Which of the tile's neighbours it joins up with, reduced. Tiles past the world's edge count
+    // as joined, so nothing looks cut off there.
+    fn autotile_mask(&self, world: &World, tile: &Tile, x: usize, y: usize) -> u8 {
+        let mut mask = 0;
+        for (bit, &(dx, dy)) in autotile::OFFSETS.iter().enumerate() {
+            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+            let joined = if !world.in_bounds(nx, ny) {
+                true
+            } else if self.contains(nx as usize, ny as usize) {
+                autotile::joins(tile, self.tile_at(nx as usize, ny as usize))
+            } else {
+                autotile::joins(tile, &world.tile_at(nx as usize, ny as usize, self.z))
+            };
+            if joined {
+                mask = mask | (1 << bit);
+            }
         }
+        autotile::reduce(mask)
     }
     
-    pub fn draw(&self, program: &Program, camera: &Camera) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_buffer);
-            gl::UseProgram(program.id);
-            gl::UniformMatrix4fv(program.model_view_idx, 1, gl::FALSE, mem::transmute(&camera.model_view));
-            gl::UniformMatrix4fv(program.projection_idx, 1, gl::FALSE, mem::transmute(&camera.projection));
-            gl::DrawElements(gl::TRIANGLES, self.index_count, gl::UNSIGNED_SHORT, ptr::null());
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
-        }
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.min_x && x < self.min_x + SIZE && y >= self.min_y && y < self.min_y + SIZE
+    }
+    
+    pub fn min_x(&self) -> usize {
+        self.min_x
+    }
+    
+    pub fn min_y(&self) -> usize {
+        self.min_y
+    }
+    
+    pub fn tile_at(&self, x: usize, y: usize) -> &Tile {
+        let rel_x = x - self.min_x;
+        let rel_y = y - self.min_y;
+        &self.tiles[rel_y * SIZE + rel_x]
     }
     
     pub fn replace_tile(&mut self, x: usize, y: usize, tile: Tile) {
@@ -1,7 +1,12 @@
 mod tile;
+mod atlas;
+pub mod autotile;
 pub mod chunk;
 mod program;
+mod renderer;
 
-pub use self::tile::{Tile, FloorTileType, WallTileType};
+pub use self::tile::{Tile, FloorTileType, WallTileType, Occupant, OptOccupant, Door, Container};
+pub use self::atlas::Atlas;
 pub use self::program::Program;
-pub use self::chunk::Chunk;
\ No newline at end of file
+pub use self::chunk::Chunk;
+pub use self::renderer::Renderer;
\ No newline at end of file
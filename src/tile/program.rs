@@ -8,9 +8,16 @@ pub struct Program {
     
     pub model_view_idx: GLint,
     pub projection_idx: GLint,
+    pub ambient_idx:    GLint,
+    pub atlas_idx:      GLint,
+    pub hovered_idx:    GLint,
+    pub selected_idx:   GLint,
+    pub dim_idx:        GLint,
     
     pub position_idx:   GLuint,
-    pub color_idx:      GLuint
+    pub color_idx:      GLuint,
+    pub uv_idx:         GLuint,
+    pub light_idx:      GLuint
 }
 
 impl Program {
@@ -21,9 +28,16 @@ impl Program {
             
             model_view_idx: glutil::get_uniform_location(id, "model"),
             projection_idx: glutil::get_uniform_location(id, "projection"),
+            ambient_idx:    glutil::get_uniform_location(id, "ambient"),
+            atlas_idx:      glutil::get_uniform_location(id, "atlas"),
+            hovered_idx:    glutil::get_uniform_location(id, "hovered"),
+            selected_idx:   glutil::get_uniform_location(id, "selected"),
+            dim_idx:        glutil::get_uniform_location(id, "dim"),
             
             position_idx:   glutil::get_attrib_location( id, "position"),
-            color_idx:      glutil::get_attrib_location( id, "color")
+            color_idx:      glutil::get_attrib_location( id, "color"),
+            uv_idx:         glutil::get_attrib_location( id, "uv"),
+            light_idx:      glutil::get_attrib_location( id, "light")
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::ptr;
+use std::mem;
+use gl;
+use gl::types::*;
+use libc::c_void;
+
+use super::{Atlas, Program};
+use super::chunk;
+use camera::Camera;
+use world::World;
+
+// Every chunk's mesh has exactly this many vertices and indices: 4 and 6 per tile.
+pub const VERTICES_PER_CHUNK: usize = 4 * chunk::SIZE * chunk::SIZE;
+pub const INDICES_PER_CHUNK:  usize = 6 * chunk::SIZE * chunk::SIZE;
+
+// Floats per vertex in each buffer.
+const POSITION_FLOATS: usize = 2;
+const COLOR_FLOATS:    usize = 3;
+const UV_FLOATS:       usize = 2;
+const LIGHT_FLOATS:    usize = 1;
+
+// Room left over for chunks above the ground made after the renderer, e.g. in the editor. Running
+// out grows the buffers by this much again.
+const SPARE_SLOTS: usize = 64;
+
+// How much each level below the player's is darkened by. Two levels down is this squared.
+pub const LOWER_LEVEL_DIM: f32 = 0.6;
+
+// Draws the whole world's tiles. Every chunk's mesh lives in one set of shared buffers, each
+// chunk in its own fixed-size slot, so all the visible chunks on a level go out in a single draw
+// call.
+pub struct Renderer {
+    vao:              GLuint,
+    position_buffer:  GLuint,
+    color_buffer:     GLuint,
+    uv_buffer:        GLuint,
+    light_buffer:     GLuint,
+    index_buffer:     GLuint,
+    
+    atlas:            Atlas,
+    texture:          GLuint,
+    
+    chunks_per_side:  usize,
+    
+    // Slots of chunks above the ground, by chunk indices (x, y, z). They come after the ground's,
+    // which are laid out row by row.
+    upper_slots:      HashMap<(usize, usize, usize), usize>,
+    
+    // How many chunks the buffers have room for.
+    capacity:         usize,
+    
+    // Tiles to highlight on the level being drawn, in world coords.
+    pub hovered:      Option<(usize, usize)>,
+    pub selected:     Option<(usize, usize)>,
+    
+    // Stats from the last calls to draw and remesh, for profiling.
+    pub draw_calls:      u32,
+    pub chunks_drawn:    u32,
+    pub chunks_remeshed: u32
+}
+
+impl Renderer {
+    // Allocates slots for every chunk in the world and uploads them all, along with the atlas.
+    pub fn new(program: &Program, world: &World, atlas: Atlas) -> Renderer {
+        let chunks_per_side = world.chunks.len();
+        let mut renderer = Renderer {
+            vao: 0, position_buffer: 0, color_buffer: 0, uv_buffer: 0, light_buffer: 0, index_buffer: 0,
+            atlas: atlas, texture: 0,
+            chunks_per_side: chunks_per_side, upper_slots: HashMap::new(),
+            capacity: chunks_per_side * chunks_per_side + world.upper_chunks.len() + SPARE_SLOTS,
+            hovered: None, selected: None,
+            draw_calls: 0, chunks_drawn: 0, chunks_remeshed: 0
+        };
+        
+        // The same indices serve every chunk. Draws offset them to the chunk's slot.
+        let mut indices: Vec<u16> = Vec::with_capacity(INDICES_PER_CHUNK);
+        for tile in 0..(chunk::SIZE * chunk::SIZE) {
+            let o = (tile * 4) as u16;
+            indices.push_all(&[
+                o + 0, o + 1, o + 3, // NW, NE, SW.
+                o + 1, o + 2, o + 3  // NE, SE, SW.
+            ]);
+        }
+        
+        unsafe {
+            gl::GenVertexArrays(1, &mut renderer.vao);
+            gl::GenBuffers(1,      &mut renderer.position_buffer);
+            gl::GenBuffers(1,      &mut renderer.color_buffer);
+            gl::GenBuffers(1,      &mut renderer.uv_buffer);
+            gl::GenBuffers(1,      &mut renderer.light_buffer);
+            gl::GenBuffers(1,      &mut renderer.index_buffer);
+        }
+        renderer.allocate();
+        
+        unsafe {
+            gl::BindVertexArray(renderer.vao);
+            
+            gl::BindBuffer(gl::ARRAY_BUFFER, renderer.position_buffer);
+            gl::EnableVertexAttribArray(program.position_idx);
+            gl::VertexAttribPointer(program.position_idx, POSITION_FLOATS as i32, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            
+            gl::BindBuffer(gl::ARRAY_BUFFER, renderer.color_buffer);
+            gl::EnableVertexAttribArray(program.color_idx);
+            gl::VertexAttribPointer(program.color_idx, COLOR_FLOATS as i32, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            
+            gl::BindBuffer(gl::ARRAY_BUFFER, renderer.uv_buffer);
+            gl::EnableVertexAttribArray(program.uv_idx);
+            gl::VertexAttribPointer(program.uv_idx, UV_FLOATS as i32, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            
+            gl::BindBuffer(gl::ARRAY_BUFFER, renderer.light_buffer);
+            gl::EnableVertexAttribArray(program.light_idx);
+            gl::VertexAttribPointer(program.light_idx, LIGHT_FLOATS as i32, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            
+            // The element buffer binding is part of the VAO's state, so it stays bound with it.
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, renderer.index_buffer);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                2 * indices.len() as i64,
+                indices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW
+            );
+            
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            
+            // Nearest filtering keeps the pixel art crisp when zoomed in.
+            let (width, height) = renderer.atlas.image.dimensions();
+            gl::GenTextures(1, &mut renderer.texture);
+            gl::BindTexture(gl::TEXTURE_2D, renderer.texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexImage2D(
+                gl::TEXTURE_2D, 0, gl::RGBA as GLint,
+                width as GLsizei, height as GLsizei, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE,
+                renderer.atlas.image.as_ptr() as *const c_void
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        
+        for chunk_y in 0..renderer.chunks_per_side {
+            for chunk_x in 0..renderer.chunks_per_side {
+                renderer.upload(world, chunk_x, chunk_y, 0);
+            }
+        }
+        for &(chunk_x, chunk_y, z) in world.upper_chunks.keys() {
+            renderer.upload(world, chunk_x, chunk_y, z);
+        }
+        
+        renderer
+    }
+    
+    // Sizes the vertex buffers to capacity slots. Whatever was in them is lost.
+    fn allocate(&self) {
+        unsafe {
+            for &(buffer, floats) in [
+                (self.position_buffer, POSITION_FLOATS),
+                (self.color_buffer,    COLOR_FLOATS),
+                (self.uv_buffer,       UV_FLOATS),
+                (self.light_buffer,    LIGHT_FLOATS)
+            ].iter() {
+                gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (4 * floats * VERTICES_PER_CHUNK * self.capacity) as i64,
+                    ptr::null(),
+                    gl::DYNAMIC_DRAW
+                );
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+    
+    // None for chunks above the ground that haven't been uploaded yet.
+    fn slot(&self, chunk_x: usize, chunk_y: usize, z: usize) -> Option<usize> {
+        if z == 0 {
+            Some(chunk_y * self.chunks_per_side + chunk_x)
+        } else {
+            self.upper_slots.get(&(chunk_x, chunk_y, z)).cloned()
+        }
+    }
+    
+    // Gives a chunk above the ground the next slot. Growing the buffers to make room means
+    // uploading everything again.
+    fn add_slot(&mut self, world: &World, chunk_x: usize, chunk_y: usize, z: usize) -> usize {
+        let slot = self.chunks_per_side * self.chunks_per_side + self.upper_slots.len();
+        if slot >= self.capacity {
+            self.capacity = self.capacity + SPARE_SLOTS;
+            self.allocate();
+            for chunk_y in 0..self.chunks_per_side {
+                for chunk_x in 0..self.chunks_per_side {
+                    self.upload(world, chunk_x, chunk_y, 0);
+                }
+            }
+            let uploaded: Vec<(usize, usize, usize)> = self.upper_slots.keys().cloned().collect();
+            for &(chunk_x, chunk_y, z) in uploaded.iter() {
+                self.upload(world, chunk_x, chunk_y, z);
+            }
+        }
+        self.upper_slots.insert((chunk_x, chunk_y, z), slot);
+        slot
+    }
+    
+    // Rebuilds the chunk's mesh and copies it into its slot. Does nothing if there's no such
+    // chunk.
+    pub fn upload(&mut self, world: &World, chunk_x: usize, chunk_y: usize, z: usize) {
+        let chunk = match world.chunk_at(chunk_x, chunk_y, z) {
+            Some(chunk) => { chunk },
+            None        => { return; }
+        };
+        let slot = match self.slot(chunk_x, chunk_y, z) {
+            Some(slot) => { slot },
+            None       => { self.add_slot(world, chunk_x, chunk_y, z) }
+        };
+        
+        let mut positions: Vec<f32> = Vec::with_capacity(POSITION_FLOATS * VERTICES_PER_CHUNK);
+        let mut colors:    Vec<f32> = Vec::with_capacity(COLOR_FLOATS    * VERTICES_PER_CHUNK);
+        let mut uvs:       Vec<f32> = Vec::with_capacity(UV_FLOATS       * VERTICES_PER_CHUNK);
+        let mut lights:    Vec<f32> = Vec::with_capacity(LIGHT_FLOATS    * VERTICES_PER_CHUNK);
+        chunk.borrow().mesh(
+            world, &self.atlas, &mut positions, &mut colors, &mut uvs, &mut lights
+        );
+        
+        let first_vertex = slot * VERTICES_PER_CHUNK;
+        unsafe {
+            for &(buffer, floats, data) in [
+                (self.position_buffer, POSITION_FLOATS, &positions),
+                (self.color_buffer,    COLOR_FLOATS,    &colors),
+                (self.uv_buffer,       UV_FLOATS,       &uvs),
+                (self.light_buffer,    LIGHT_FLOATS,    &lights)
+            ].iter() {
+                gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    (4 * floats * first_vertex) as i64,
+                    4 * data.len() as i64,
+                    data.as_ptr() as *const c_void
+                );
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+    
+    // Re-meshes and uploads every chunk the world has marked dirty since last time. Call once per
+    // frame, before drawing. Returns the chunks it re-meshed, for anything else that shows them.
+    pub fn remesh(&mut self, world: &mut World) -> Vec<(usize, usize, usize)> {
+        let dirty: Vec<(usize, usize, usize)> = world.dirty_chunks.iter().cloned().collect();
+        world.dirty_chunks.clear();
+        for &(chunk_x, chunk_y, z) in dirty.iter() {
+            self.upload(world, chunk_x, chunk_y, z);
+        }
+        self.chunks_remeshed = dirty.len() as u32;
+        dirty
+    }
+    
+    // Draws the given chunks on every level from the ground up to level, one draw call per level
+    // with anything to show, so each covers the one below. Levels below are darkened, and only
+    // level itself shows the highlights. ambient is the color the time of day tints everything
+    // not lit by a light.
+    pub fn draw(
+        &mut self, program: &Program, camera: &Camera, ambient: (f32, f32, f32),
+        chunks: &[(usize, usize)], level: usize
+    ) {
+        self.draw_calls = 0;
+        self.chunks_drawn = 0;
+        if chunks.is_empty() {
+            return;
+        }
+        
+        unsafe {
+            gl::UseProgram(program.id);
+            gl::UniformMatrix4fv(program.model_view_idx, 1, gl::FALSE, mem::transmute(&camera.model_view));
+            gl::UniformMatrix4fv(program.projection_idx, 1, gl::FALSE, mem::transmute(&camera.projection));
+            gl::Uniform3f(program.ambient_idx, ambient.0, ambient.1, ambient.2);
+            
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::Uniform1i(program.atlas_idx, 0);
+            gl::BindVertexArray(self.vao);
+        }
+        
+        for z in 0..(level + 1) {
+            let base_vertices: Vec<GLint> = chunks.iter()
+                .filter_map(|&(chunk_x, chunk_y)| self.slot(chunk_x, chunk_y, z))
+                .map(|slot| (slot * VERTICES_PER_CHUNK) as GLint)
+                .collect();
+            if base_vertices.is_empty() {
+                continue;
+            }
+            let counts: Vec<GLsizei> = base_vertices.iter().map(|_| INDICES_PER_CHUNK as GLsizei).collect();
+            let offsets: Vec<*const c_void> = base_vertices.iter().map(|_| ptr::null()).collect();
+            
+            unsafe {
+                gl::Uniform1f(program.dim_idx, LOWER_LEVEL_DIM.powi((level - z) as i32));
+                
+                // The shader takes negative coords to mean no tile.
+                for &(idx, tile) in [
+                    (program.hovered_idx,  self.hovered),
+                    (program.selected_idx, self.selected)
+                ].iter() {
+                    match tile {
+                        Some((x, y)) if z == level => { gl::Uniform2f(idx, x as f32, y as f32); },
+                        _                          => { gl::Uniform2f(idx, -1.0, -1.0); }
+                    }
+                }
+                
+                gl::MultiDrawElementsBaseVertex(
+                    gl::TRIANGLES,
+                    counts.as_ptr(),
+                    gl::UNSIGNED_SHORT,
+                    offsets.as_ptr(),
+                    base_vertices.len() as GLsizei,
+                    base_vertices.as_ptr()
+                );
+            }
+            self.draw_calls = self.draw_calls + 1;
+            self.chunks_drawn = self.chunks_drawn + base_vertices.len() as u32;
+        }
+        
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+}
\ No newline at end of file
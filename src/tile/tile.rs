@@ -2,6 +2,10 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::default::Default;
 
+use lock::{Lock, KeyRing};
+use inventory::Item;
+use super::atlas::Atlas;
+use super::autotile;
 use self::Tile::*;
 use self::WallTileType::*;
 use self::FloorTileType::*;
@@ -11,7 +15,8 @@ use self::FloorTileType::*;
 #[derive(Debug, Clone)]
 pub enum Tile {
     WallTile(WallTileType),
-    FloorTile(FloorTileType, OptOccupant)
+    FloorTile(FloorTileType, OptOccupant),
+    EmptyTile  // Open air, on the levels above the ground where no building reaches.
 }
 
 #[derive(Debug, Clone)]
@@ -23,16 +28,60 @@ pub enum WallTileType {
 pub enum FloorTileType {
     ConcreteFloor,
     AsphaltFloor,
-    GrassFloor
+    GrassFloor,
+    SidewalkFloor,
+    CurbFloor,         // The edge of a sidewalk, where it drops to the road.
+    LaneMarkingFloor,  // Painted road. Lines and dashes are made of these.
+    CrosswalkFloor,    // Painted road. Crosswalk stripes alternate these with asphalt.
+    StairsUpFloor,     // Stepping on takes you to the stairs down on the level above.
+    StairsDownFloor,   // Stepping on takes you to the stairs up on the level below.
+    ElevatorFloor,     // Rides to the next level with an elevator in the same place.
+    WaterFloor,        // Rivers and the sea. Nobody can walk on it.
+    BridgeFloor,       // Streets where they cross water.
+    HillFloor          // Rough ground, where the city's built around the hills rather than on them.
 }
 
 // Occupants of tiles.
 
-type OptOccupant = Option<Rc<RefCell<Occupant>>>;
+pub type OptOccupant = Option<Rc<RefCell<Occupant>>>;
 
 #[derive(Debug)]
 pub enum Occupant {
-    //MacGuffin
+    Door(Door),
+    Container(Container),
+    Item(Item)
+}
+
+#[derive(Debug)]
+pub struct Door {
+    pub lock: Option<Lock>,
+    pub open: bool
+}
+
+// Safes, lockers, display cases and the like.
+#[derive(Debug)]
+pub struct Container {
+    pub lock:     Option<Lock>,
+    pub open:     bool,
+    pub contents: Vec<Item>
+}
+
+impl Occupant {
+    pub fn lock(&self) -> Option<&Lock> {
+        match *self {
+            Occupant::Door(ref door)           => { door.lock.as_ref() },
+            Occupant::Container(ref container) => { container.lock.as_ref() },
+            Occupant::Item(_)                  => { None }
+        }
+    }
+    
+    pub fn lock_mut(&mut self) -> Option<&mut Lock> {
+        match *self {
+            Occupant::Door(ref mut door)           => { door.lock.as_mut() },
+            Occupant::Container(ref mut container) => { container.lock.as_mut() },
+            Occupant::Item(_)                      => { None }
+        }
+    }
 }
 
 impl Tile {
@@ -41,12 +90,173 @@ impl Tile {
             (255, 255, 255) => { WallTile(BrickWall) },
             (0,   0,   0  ) => { FloorTile(AsphaltFloor, None) },
             (128, 128, 128) => { FloorTile(ConcreteFloor, None) },
+            (0,   0,   255) => { FloorTile(WaterFloor, None) },
             _               => { FloorTile(GrassFloor, None) }
         }
     }
     
-    pub fn buffer(&self, positions: &mut Vec<f32>, colors: &mut Vec<f32>, indices: &mut Vec<u16>, x: usize, y: usize) {
-        let o: u16 = positions.len() as u16 / 2;
+    // The tile drawn with the named sprite, e.g. "asphalt" or "door". Doors come unlocked and
+    // closed. Returns None for sprites that aren't a tile by themselves, like items.
+    pub fn from_name(name: &str) -> Option<Tile> {
+        let door = || Rc::new(RefCell::new(Occupant::Door(Door { lock: None, open: false })));
+        match name {
+            "brick_wall"   => { Some(WallTile(BrickWall)) },
+            "asphalt"      => { Some(FloorTile(AsphaltFloor, None)) },
+            "grass"        => { Some(FloorTile(GrassFloor, None)) },
+            "concrete"     => { Some(FloorTile(ConcreteFloor, None)) },
+            "sidewalk"     => { Some(FloorTile(SidewalkFloor, None)) },
+            "curb"         => { Some(FloorTile(CurbFloor, None)) },
+            "lane_marking" => { Some(FloorTile(LaneMarkingFloor, None)) },
+            "crosswalk"    => { Some(FloorTile(CrosswalkFloor, None)) },
+            "stairs_up"    => { Some(FloorTile(StairsUpFloor, None)) },
+            "stairs_down"  => { Some(FloorTile(StairsDownFloor, None)) },
+            "elevator"     => { Some(FloorTile(ElevatorFloor, None)) },
+            "water"        => { Some(FloorTile(WaterFloor, None)) },
+            "bridge"       => { Some(FloorTile(BridgeFloor, None)) },
+            "hill"         => { Some(FloorTile(HillFloor, None)) },
+            "empty"        => { Some(EmptyTile) },
+            "door"         => { Some(FloorTile(ConcreteFloor, Some(door()))) },
+            _              => { None }
+        }
+    }
+    
+    pub fn occupant(&self) -> OptOccupant {
+        match *self {
+            WallTile(_)                => { None },
+            FloorTile(_, ref occupant) => { occupant.clone() },
+            EmptyTile                  => { None }
+        }
+    }
+    
+    // Same floor, nothing on it. Walls and open air are returned unchanged.
+    pub fn without_occupant(&self) -> Tile {
+        match *self {
+            WallTile(ref wall_type)      => { WallTile(wall_type.clone()) },
+            FloorTile(ref floor_type, _) => { FloorTile(floor_type.clone(), None) },
+            EmptyTile                    => { EmptyTile }
+        }
+    }
+    
+    // Same floor, with occ on it. Walls and open air can't hold occupants and are returned
+    // unchanged.
+    pub fn with_occupant(&self, occ: Rc<RefCell<Occupant>>) -> Tile {
+        match *self {
+            WallTile(ref wall_type)      => { WallTile(wall_type.clone()) },
+            FloorTile(ref floor_type, _) => { FloorTile(floor_type.clone(), Some(occ)) },
+            EmptyTile                    => { EmptyTile }
+        }
+    }
+    
+    // Whether it blocks line of sight. Walls and closed doors do.
+    pub fn is_opaque(&self) -> bool {
+        match *self {
+            WallTile(_)                 => { true },
+            EmptyTile                   => { false },
+            FloorTile(_, None)          => { false },
+            FloorTile(_, Some(ref occ)) => {
+                match *occ.borrow() {
+                    Occupant::Door(ref door) => { !door.open },
+                    _                        => { false }
+                }
+            }
+        }
+    }
+    
+    // Whoever holds keys can walk through this tile, opening any door on it along the way.
+    pub fn is_passable_for(&self, keys: &KeyRing) -> bool {
+        match *self {
+            WallTile(_)                   => { false },
+            EmptyTile                     => { false },
+            FloorTile(WaterFloor, _)      => { false },
+            FloorTile(_, None)            => { true },
+            FloorTile(_, Some(ref occ))   => {
+                match *occ.borrow() {
+                    Occupant::Door(ref door)   => { door.open || keys.has_access(door.lock.as_ref()) },
+                    Occupant::Container(_)     => { false },
+                    Occupant::Item(_)          => { true }
+                }
+            }
+        }
+    }
+    
+    // Whether a car fits on this tile: any floor but water, with nothing bigger than an item on it.
+    pub fn is_drivable(&self) -> bool {
+        match *self {
+            FloorTile(WaterFloor, _)    => { false },
+            FloorTile(_, None)          => { true },
+            FloorTile(_, Some(ref occ)) => {
+                match *occ.borrow() {
+                    Occupant::Item(_) => { true },
+                    _                 => { false }
+                }
+            },
+            WallTile(_) | EmptyTile     => { false }
+        }
+    }
+    
+    // Which sprite in the atlas this tile is drawn with. An occupant hides the floor under it.
+    pub fn sprite_name(&self) -> &'static str {
+        match *self {
+            FloorTile(_, Some(ref occ))    => {
+                match *occ.borrow() {
+                    Occupant::Door(ref door)           => { if door.open { "door_open" } else { "door" } },
+                    Occupant::Container(ref container) => { if container.open { "safe_open" } else { "safe" } },
+                    Occupant::Item(_)                  => { "item" }
+                }
+            },
+            WallTile(_)                    => { "brick_wall" },
+            FloorTile(AsphaltFloor, _)     => { "asphalt" },
+            FloorTile(GrassFloor, _)       => { "grass" },
+            FloorTile(ConcreteFloor, _)    => { "concrete" },
+            FloorTile(SidewalkFloor, _)    => { "sidewalk" },
+            FloorTile(CurbFloor, _)        => { "curb" },
+            FloorTile(LaneMarkingFloor, _) => { "lane_marking" },
+            FloorTile(CrosswalkFloor, _)   => { "crosswalk" },
+            FloorTile(StairsUpFloor, _)    => { "stairs_up" },
+            FloorTile(StairsDownFloor, _)  => { "stairs_down" },
+            FloorTile(ElevatorFloor, _)    => { "elevator" },
+            FloorTile(WaterFloor, _)       => { "water" },
+            FloorTile(BridgeFloor, _)      => { "bridge" },
+            FloorTile(HillFloor, _)        => { "hill" },
+            EmptyTile                      => { "empty" }
+        }
+    }
+    
+    // The level stepping onto the tile takes you to from level z, if it's stairs. Elevators
+    // take riding, so don't count.
+    pub fn stairs_to(&self, z: usize) -> Option<usize> {
+        match *self {
+            FloorTile(StairsUpFloor, _)            => { Some(z + 1) },
+            FloorTile(StairsDownFloor, _) if z > 0 => { Some(z - 1) },
+            _                                      => { None }
+        }
+    }
+    
+    pub fn is_elevator(&self) -> bool {
+        match *self {
+            FloorTile(ElevatorFloor, _) => { true },
+            _                           => { false }
+        }
+    }
+    
+    // Pushes 4 vertices: NW, NE, SE, SW. See tile::renderer for the indices that go with them.
+    // mask says which neighbours this tile joins up with. See tile::autotile.
+    pub fn buffer(
+        &self, atlas: &Atlas,
+        positions: &mut Vec<f32>, colors: &mut Vec<f32>, uvs: &mut Vec<f32>, lights: &mut Vec<f32>,
+        x: usize, y: usize, light: f32, mask: u8
+    ) {
+        // Open air still takes its 4 vertices, all in one spot, so nothing's drawn and whatever's
+        // on the level below shows through.
+        if let EmptyTile = *self {
+            for _ in 0u8..4u8 {
+                positions.push_all(&[x as f32, y as f32]);
+                colors.push_all(&[1.0, 1.0, 1.0]);
+                uvs.push_all(&[0.0, 0.0]);
+                lights.push(light);
+            }
+            return;
+        }
         
         positions.push_all(&[
             x as f32 - 0.5, y as f32 - 0.5, // NW.
@@ -55,20 +265,25 @@ impl Tile {
             x as f32 - 0.5, y as f32 + 0.5  // SW.
         ]);
         
-        indices.push_all(&[
-            o + 0, o + 1, o + 3, // NW, NE, SW.
-            o + 1, o + 2, o + 3  // NE, SE, SW.
+        // Row 0 of the atlas is its top, and y grows down the screen, so v runs with y.
+        // Tiles joined on every side keep their ordinary sprites, variants and all.
+        let name = self.sprite_name();
+        let (u0, v0, u1, v1) = if mask != autotile::ALL && atlas.has_autotile(name) {
+            atlas.autotile_uv(name, mask)
+        } else {
+            atlas.uv(name, x, y)
+        };
+        uvs.push_all(&[
+            u0, v0, // NW.
+            u1, v0, // NE.
+            u1, v1, // SE.
+            u0, v1  // SW.
         ]);
         
-        let (r, g, b) = match *self {
-            WallTile(_)                 => { (1.00,  1.00,  1.00) },
-            FloorTile(AsphaltFloor, _)  => { (0.10,  0.10,  0.10) },
-            FloorTile(GrassFloor, _)    => { (0.09,  0.37,  0.18) }
-            FloorTile(ConcreteFloor, _) => { (0.50,  0.50,  0.50) }
-        };
-                
+        // The sprites carry the color now, so the tint is left white.
         for _ in 0u8..4u8 {
-            colors.push_all(&[r, g, b]);
+            colors.push_all(&[1.0, 1.0, 1.0]);
+            lights.push(light);
         }
     }
 }
@@ -0,0 +1,575 @@
+use rand::Rng;
+
+use gen::street::{self, STREET_WIDTH, BLOCK_WIDTH, CROSSWALK_LENGTH};
+use lock::KeyRing;
+use sound::Sound;
+use world::World;
+
+pub const TRAFFIC_CARS: usize = 40;  // Cars driving around the city at the start.
+pub const PARKED_CARS:  usize = 60;  // Cars parked along the streets at the start, for good.
+pub const CAR_LENGTH:   usize = 2;   // In tiles. Cars are one tile wide.
+
+// Traffic drives on the right. Lanes are measured from the middle of the street, in tiles.
+pub const LANE_OFFSET:    usize = 2;
+pub const PARKING_OFFSET: usize = 4;  // The parking lane, along the curb.
+
+pub const TRAFFIC_SPEED: f32 = 8.0;   // Tiles per second.
+pub const STOP_SECONDS:  f32 = 1.0;   // How long traffic waits at every intersection.
+pub const PARK_CHANCE:   u32 = 10;    // One in this many streets driven down, traffic parks partway.
+pub const MIN_PARK_SECONDS: f32 = 20.0;
+pub const MAX_PARK_SECONDS: f32 = 90.0;
+
+// Held up this long partway across an intersection, traffic lets others across.
+pub const GIVE_WAY_SECONDS: f32 = 3.0;
+
+// The player's driving. Speeds in tiles per second, and changes in speed per second.
+pub const MAX_DRIVE_SPEED: f32 = 20.0;
+pub const ACCELERATION:    f32 = 10.0;
+pub const BRAKING:         f32 = 30.0;  // Holding the way back.
+pub const COASTING:        f32 = 8.0;   // Holding nothing.
+
+// Breaking into a car sets its alarm off.
+pub const ALARM_LOUDNESS: f32 = 30.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VehicleState {
+    Driving,              // Following its waypoints. It may be held up by whatever's ahead.
+    Stopped(f32),         // At an intersection. Holds the seconds left to wait.
+    Parked(Option<f32>),  // Holds the seconds until it pulls out again, or None if it never will.
+    Player                // The player's driving it.
+}
+
+pub struct Vehicle {
+    pub x:       f32,         // The front of the car, in world coords.
+    pub y:       f32,
+    pub heading: (i32, i32),  // One of the four directions along the grid.
+    pub speed:   f32,         // Tiles per second.
+    pub state:   VehicleState,
+    
+    // The intersections the car's driving between, in grid coords, and the points it's driving
+    // through to get to the end, in world coords.
+    leg:         ((usize, usize), (usize, usize)),
+    waypoints:   Vec<(f32, f32)>,
+    parking:     bool,  // Whether the car parks once it's through its waypoints.
+    
+    // The intersection the car's driving across, if any. Only one car crosses each at a time.
+    crossing:    Option<(usize, usize)>,
+    held_up:     f32,  // Seconds the car's been waiting on whatever's in its way.
+    
+    // How far the player's car has gone towards the next tile.
+    progress:    f32
+}
+
+// Cars on the ground, driven by no one, by the traffic or by the player. Cars are never taken
+// away, so their indices stay good.
+pub struct Traffic {
+    pub vehicles: Vec<Vehicle>
+}
+
+impl Vehicle {
+    // The tile the front of the car is on.
+    pub fn tile(&self) -> (usize, usize) {
+        (self.x.round() as usize, self.y.round() as usize)
+    }
+    
+    // Front first.
+    pub fn tiles(&self) -> Vec<(i32, i32)> {
+        footprint(self.x.round() as i32, self.y.round() as i32, self.heading)
+    }
+    
+    // Whether the car's on tile (x, y). The same as tiles().contains, without making the list,
+    // since traffic asks it of every car every tick.
+    pub fn covers(&self, x: i32, y: i32) -> bool {
+        let (fx, fy) = (self.x.round() as i32, self.y.round() as i32);
+        (0..CAR_LENGTH as i32).any(|i| fx - self.heading.0 * i == x && fy - self.heading.1 * i == y)
+    }
+    
+    // Where the front of the car would be and which way it'd face after driving toward its
+    // waypoints for dt, and how many of them it'd reach on the way. Leaves the car where it is.
+    fn advance(&self, dt: f32) -> (f32, f32, (i32, i32), usize) {
+        let (mut x, mut y, mut heading) = (self.x, self.y, self.heading);
+        let mut step = TRAFFIC_SPEED * dt;
+        let mut reached = 0;
+        while step > 0.0 && reached < self.waypoints.len() {
+            let (tx, ty) = self.waypoints[reached];
+            let (dx, dy) = (tx - x, ty - y);
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist <= step {
+                x = tx;
+                y = ty;
+                reached = reached + 1;
+                step = step - dist;
+            } else {
+                heading = heading_toward(dx, dy);
+                x = x + dx / dist * step;
+                y = y + dy / dist * step;
+                step = 0.0;
+            }
+        }
+        (x, y, heading, reached)
+    }
+    
+    // Drives toward the next waypoint. Reaching one means the car's across any intersection it
+    // was crossing.
+    fn follow(&mut self, dt: f32) {
+        let (x, y, heading, reached) = self.advance(dt);
+        self.x = x;
+        self.y = y;
+        self.heading = heading;
+        for _ in 0..reached {
+            self.waypoints.remove(0);
+            self.crossing = None;
+        }
+    }
+}
+
+impl Traffic {
+    pub fn new() -> Traffic {
+        Traffic { vehicles: Vec::new() }
+    }
+    
+    // Puts cars on random streets, some driving and some parked. Spots that turn out to be taken
+    // or off the road are left empty.
+    pub fn generate<R: Rng>(world: &World, rng: &mut R) -> Traffic {
+        let mut traffic = Traffic::new();
+        let open: Vec<(usize, usize)> = world.grid.intersections.iter().flat_map(|row| row.iter()).filter_map(|inter| {
+            let inter = inter.borrow();
+            if inter.is_open() { Some((inter.x, inter.y)) } else { None }
+        }).collect();
+        if open.is_empty() {
+            return traffic;
+        }
+        
+        let margin = STREET_WIDTH / 2 + CROSSWALK_LENGTH + CAR_LENGTH + 2;
+        for i in 0..(TRAFFIC_CARS + PARKED_CARS) {
+            let from = *rng.choose(&open).unwrap();
+            let to = *rng.choose(&world.grid.intersections[from.1][from.0].borrow().neighbors()).unwrap();
+            let parked = i >= TRAFFIC_CARS;
+            let (d, right) = directions(from, to);
+            let along = rng.gen_range(margin, STREET_WIDTH + BLOCK_WIDTH - margin) as i32;
+            let offset = (if parked { PARKING_OFFSET } else { LANE_OFFSET }) as i32;
+            let (cx, cy) = street::center(from.0, from.1);
+            let (x, y) = (cx as i32 + d.0 * along + right.0 * offset, cy as i32 + d.1 * along + right.1 * offset);
+            
+            let index = traffic.vehicles.len();
+            if !traffic.is_clear(world, index, &footprint(x, y, d)) {
+                continue;
+            }
+            traffic.vehicles.push(Vehicle {
+                x: x as f32, y: y as f32, heading: d, speed: 0.0,
+                state: if parked { VehicleState::Parked(None) } else { VehicleState::Driving },
+                leg: (from, to),
+                waypoints: if parked { Vec::new() } else { vec![stop_line(from, to)] },
+                parking: false, crossing: None, held_up: 0.0, progress: 0.0
+            });
+        }
+        traffic
+    }
+    
+    pub fn vehicle_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.vehicles.iter().position(|v| v.covers(x, y))
+    }
+    
+    // Whether any car but the one at index skip is on any of tiles.
+    fn is_blocked(&self, skip: usize, tiles: &[(i32, i32)]) -> bool {
+        self.vehicles.iter().enumerate().any(|(i, v)| {
+            i != skip && tiles.iter().any(|&(x, y)| v.covers(x, y))
+        })
+    }
+    
+    // Whether the car at index skip could be on tiles: they're all in the world, drivable and free
+    // of other cars.
+    fn is_clear(&self, world: &World, skip: usize, tiles: &[(i32, i32)]) -> bool {
+        tiles.iter().all(|&(x, y)| world.in_bounds(x, y) && world.tile_at(x as usize, y as usize, 0).is_drivable()) &&
+            !self.is_blocked(skip, tiles)
+    }
+    
+    // Moves the traffic along. Cars wait for anything in their way, including the player on foot
+    // at on_foot, and take turns at intersections.
+    pub fn update<R: Rng>(&mut self, world: &World, on_foot: Option<(usize, usize)>, dt: f32, rng: &mut R) {
+        for i in 0..self.vehicles.len() {
+            let state = self.vehicles[i].state.clone();
+            match state {
+                VehicleState::Driving => {
+                    // Every tile the car would cover after driving on for dt, turning or not, and
+                    // the two in front of it there, front first. They all have to be clear.
+                    let (x, y, heading, _) = self.vehicles[i].advance(dt);
+                    let (x, y) = (x.round() as i32, y.round() as i32);
+                    let mut next = [(0, 0); CAR_LENGTH + 2];
+                    for (j, tile) in next.iter_mut().enumerate() {
+                        let along = 2 - j as i32;
+                        *tile = (x + heading.0 * along, y + heading.1 * along);
+                    }
+                    let held_up = self.is_blocked(i, &next) ||
+                        on_foot.map_or(false, |(px, py)| next.contains(&(px as i32, py as i32)));
+                    let car = &mut self.vehicles[i];
+                    car.speed = if held_up { 0.0 } else { TRAFFIC_SPEED };
+                    if held_up {
+                        // Stuck partway across, it stops holding up everyone waiting to cross.
+                        car.held_up = car.held_up + dt;
+                        if car.held_up >= GIVE_WAY_SECONDS {
+                            car.crossing = None;
+                        }
+                    } else {
+                        car.held_up = 0.0;
+                        car.follow(dt);
+                    }
+                    if car.waypoints.is_empty() {
+                        car.speed = 0.0;
+                        car.state = if car.parking {
+                            VehicleState::Parked(Some(rng.gen_range(MIN_PARK_SECONDS, MAX_PARK_SECONDS)))
+                        } else {
+                            VehicleState::Stopped(STOP_SECONDS)
+                        };
+                    }
+                },
+                VehicleState::Stopped(left) => {
+                    // Goes once it's waited its turn and nobody else is crossing, anywhere but
+                    // back the way it came if it can help it.
+                    let left = left - dt;
+                    let (came_from, at) = self.vehicles[i].leg;
+                    if left > 0.0 || self.vehicles.iter().any(|v| v.crossing == Some(at)) {
+                        self.vehicles[i].state = VehicleState::Stopped(left);
+                        continue;
+                    }
+                    let mut choices = world.grid.intersections[at.1][at.0].borrow().neighbors();
+                    if choices.len() > 1 {
+                        choices.retain(|&next| next != came_from);
+                    }
+                    let to = *rng.choose(&choices).unwrap();
+                    let parking = rng.gen_weighted_bool(PARK_CHANCE);
+                    let waypoints = leg_waypoints(at, to, parking, rng);
+                    let car = &mut self.vehicles[i];
+                    car.leg = (at, to);
+                    car.waypoints = waypoints;
+                    car.parking = parking;
+                    car.crossing = Some(at);
+                    car.state = VehicleState::Driving;
+                },
+                VehicleState::Parked(Some(left)) => {
+                    let left = left - dt;
+                    let car = &mut self.vehicles[i];
+                    if left > 0.0 {
+                        car.state = VehicleState::Parked(Some(left));
+                        continue;
+                    }
+                    // Pulls out into the lane and carries on to the end of the street.
+                    let (from, to) = car.leg;
+                    let (d, right) = directions(from, to);
+                    let shift = (PARKING_OFFSET - LANE_OFFSET) as f32;
+                    car.waypoints = vec![
+                        (car.x + d.0 as f32 * 4.0 - right.0 as f32 * shift, car.y + d.1 as f32 * 4.0 - right.1 as f32 * shift),
+                        stop_line(from, to)
+                    ];
+                    car.parking = false;
+                    car.state = VehicleState::Driving;
+                },
+                VehicleState::Parked(None) | VehicleState::Player => {}
+            }
+        }
+    }
+    
+    // Drives the player's car at index for dt seconds. Holding the way it's facing speeds up and
+    // the way back brakes, then turns it around once it's stopped. Holding to either side turns it
+    // on the spot, if there's room. Driving into anything stops it dead.
+    pub fn steer(&mut self, index: usize, world: &World, steer: Option<(i32, i32)>, dt: f32) {
+        let (mut x, mut y) = (self.vehicles[index].x as i32, self.vehicles[index].y as i32);
+        let mut heading = self.vehicles[index].heading;
+        let mut speed = self.vehicles[index].speed;
+        let mut progress = self.vehicles[index].progress;
+        
+        match steer {
+            Some(dir) if dir == heading => {
+                speed = (speed + ACCELERATION * dt).min(MAX_DRIVE_SPEED);
+            },
+            Some(dir) if dir == (-heading.0, -heading.1) && speed > 0.0 => {
+                speed = (speed - BRAKING * dt).max(0.0);
+            },
+            Some(dir) => {
+                if self.is_clear(world, index, &footprint(x, y, dir)) {
+                    heading = dir;
+                }
+            },
+            None => {
+                speed = (speed - COASTING * dt).max(0.0);
+            }
+        }
+        
+        progress = progress + speed * dt;
+        while progress >= 1.0 {
+            let (nx, ny) = (x + heading.0, y + heading.1);
+            if !self.is_clear(world, index, &footprint(nx, ny, heading)) {
+                speed = 0.0;
+                progress = 0.0;
+                break;
+            }
+            x = nx;
+            y = ny;
+            progress = progress - 1.0;
+        }
+        
+        let car = &mut self.vehicles[index];
+        car.x = x as f32;
+        car.y = y as f32;
+        car.heading = heading;
+        car.speed = speed;
+        car.progress = progress;
+    }
+    
+    // The player takes the car at index, if it isn't on the move, setting its alarm off. Returns
+    // false if they can't.
+    pub fn get_in(&mut self, index: usize, sounds: &mut Vec<Sound>) -> bool {
+        let car = &mut self.vehicles[index];
+        if car.state == VehicleState::Player || car.speed > 0.0 {
+            return false;
+        }
+        car.x = car.x.round();
+        car.y = car.y.round();
+        car.state = VehicleState::Player;
+        car.waypoints.clear();
+        car.crossing = None;
+        car.held_up = 0.0;
+        car.progress = 0.0;
+        let (x, y) = car.tile();
+        sounds.push(Sound::new(x, y, ALARM_LOUDNESS));
+        true
+    }
+    
+    // The player leaves the car at index parked where it is, stepping out to the side, or else out
+    // the back or front. Parked cars never move again, so the player can't leave one in a lane or
+    // an intersection, where it'd hold traffic up for good. Returns where the player ends up, or
+    // None if there's nowhere to leave it or no room to get out.
+    pub fn get_out(&mut self, index: usize, world: &World, keys: &KeyRing) -> Option<(usize, usize)> {
+        let tiles = self.vehicles[index].tiles();
+        if tiles.iter().any(|&(x, y)| in_traffic(x, y)) {
+            return None;
+        }
+        let h = self.vehicles[index].heading;
+        let right = (-h.1, h.0);
+        let mut spots = Vec::new();
+        for &(x, y) in tiles.iter() {
+            spots.push((x + right.0, y + right.1));
+            spots.push((x - right.0, y - right.1));
+        }
+        let (tail, head) = (tiles[tiles.len() - 1], tiles[0]);
+        spots.push((tail.0 - h.0, tail.1 - h.1));
+        spots.push((head.0 + h.0, head.1 + h.1));
+        
+        let spot = spots.into_iter().find(|&(sx, sy)| {
+            world.in_bounds(sx, sy) && world.tile_at(sx as usize, sy as usize, 0).is_passable_for(keys) &&
+                self.vehicle_at(sx, sy).is_none()
+        });
+        if let Some((sx, sy)) = spot {
+            let car = &mut self.vehicles[index];
+            car.state = VehicleState::Parked(None);
+            car.speed = 0.0;
+            car.progress = 0.0;
+            return Some((sx as usize, sy as usize));
+        }
+        None
+    }
+}
+
+// The tiles a car covers with its front at (x, y), front first.
+fn footprint(x: i32, y: i32, heading: (i32, i32)) -> Vec<(i32, i32)> {
+    (0..CAR_LENGTH as i32).map(|i| (x - heading.0 * i, y - heading.1 * i)).collect()
+}
+
+// The world coord of the middle of the street nearest a, across the streets running the other way.
+fn nearest_middle(a: i32) -> i32 {
+    let period = (STREET_WIDTH + BLOCK_WIDTH) as i32;
+    let first = (STREET_WIDTH / 2) as i32;
+    (a - first + period / 2).max(0) / period * period + first
+}
+
+// Whether a car on tile (x, y) would be in traffic's way: in an intersection, or on a street
+// short of its parking lanes.
+fn in_traffic(x: i32, y: i32) -> bool {
+    let (across_x, across_y) = ((x - nearest_middle(x)).abs(), (y - nearest_middle(y)).abs());
+    let half = (STREET_WIDTH / 2) as i32;
+    (across_x <= half && across_y <= half) || across_x < PARKING_OFFSET as i32 || across_y < PARKING_OFFSET as i32
+}
+
+// The nearest of the four directions along the grid.
+fn heading_toward(dx: f32, dy: f32) -> (i32, i32) {
+    if dx.abs() >= dy.abs() {
+        (if dx < 0.0 { -1 } else { 1 }, 0)
+    } else {
+        (0, if dy < 0.0 { -1 } else { 1 })
+    }
+}
+
+// The way along a street from intersection from to intersection to, and the way to the right
+// of that, where traffic drives. Streets only run along the grid.
+fn directions(from: (usize, usize), to: (usize, usize)) -> ((i32, i32), (i32, i32)) {
+    let d = ((to.0 as i32 - from.0 as i32).signum(), (to.1 as i32 - from.1 as i32).signum());
+    (d, (-d.1, d.0))
+}
+
+// The point offset tiles to the right of the middle of the street, along tiles from from's centre.
+fn lane_point(from: (usize, usize), to: (usize, usize), along: usize, offset: usize) -> (f32, f32) {
+    let (d, right) = directions(from, to);
+    let (cx, cy) = street::center(from.0, from.1);
+    (
+        (cx as i32 + d.0 * along as i32 + right.0 * offset as i32) as f32,
+        (cy as i32 + d.1 * along as i32 + right.1 * offset as i32) as f32
+    )
+}
+
+// Where traffic waits before driving into intersection to, short of the crosswalk.
+fn stop_line(from: (usize, usize), to: (usize, usize)) -> (f32, f32) {
+    let along = STREET_WIDTH + BLOCK_WIDTH - (STREET_WIDTH / 2 + CROSSWALK_LENGTH + 2);
+    lane_point(from, to, along, LANE_OFFSET)
+}
+
+// Out of intersection from into the lane, then on to the stop line at the far end, or to a random
+// spot in the parking lane partway along.
+fn leg_waypoints<R: Rng>(from: (usize, usize), to: (usize, usize), parking: bool, rng: &mut R) -> Vec<(f32, f32)> {
+    let start = lane_point(from, to, STREET_WIDTH / 2 + 1, LANE_OFFSET);
+    if parking {
+        let along = rng.gen_range(STREET_WIDTH + CROSSWALK_LENGTH + 10, BLOCK_WIDTH - CROSSWALK_LENGTH - 10);
+        vec![start, lane_point(from, to, along, PARKING_OFFSET)]
+    } else {
+        vec![start, stop_line(from, to)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gen;
+    use lock::KeyRing;
+    use tile::Tile::WallTile;
+    use tile::WallTileType::BrickWall;
+    use world::World;
+    use super::{Traffic, Vehicle, VehicleState};
+    use super::{footprint, heading_toward, directions, nearest_middle, in_traffic, stop_line};
+    
+    // Streets run through the middle of tiles 0 to 14 across, 200 to 214 and so on. Lanes are 2
+    // tiles either side of the middle and parking lanes 4.
+    
+    // A car with nowhere to go.
+    fn car(x: i32, y: i32, heading: (i32, i32), speed: f32, state: VehicleState) -> Vehicle {
+        Vehicle {
+            x: x as f32, y: y as f32, heading: heading, speed: speed, state: state,
+            leg: ((0, 0), (1, 0)), waypoints: Vec::new(), parking: false,
+            crossing: None, held_up: 0.0, progress: 0.0
+        }
+    }
+    
+    fn traffic(vehicles: Vec<Vehicle>) -> Traffic {
+        Traffic { vehicles: vehicles }
+    }
+    
+    #[test]
+    fn footprints_run_back_from_the_front() {
+        assert_eq!(footprint(10, 10, (1, 0)), vec![(10, 10), (9, 10)]);
+        assert_eq!(footprint(10, 10, (0, -1)), vec![(10, 10), (10, 11)]);
+        let car = car(10, 10, (0, 1), 0.0, VehicleState::Parked(None));
+        assert!(car.covers(10, 9) && car.covers(10, 10) && !car.covers(10, 11));
+    }
+    
+    #[test]
+    fn headings_are_the_nearest_way_along_the_grid() {
+        assert_eq!(heading_toward(3.0, 1.0), (1, 0));
+        assert_eq!(heading_toward(-0.5, 2.0), (0, 1));
+        assert_eq!(heading_toward(0.0, -1.0), (0, -1));
+        assert_eq!(heading_toward(-1.0, 1.0), (-1, 0));
+    }
+    
+    #[test]
+    fn traffic_keeps_right() {
+        // East, with south on the right, and north, with east on the right.
+        assert_eq!(directions((0, 0), (1, 0)), ((1, 0), (0, 1)));
+        assert_eq!(directions((1, 1), (1, 0)), ((0, -1), (1, 0)));
+        
+        // Short of the crosswalk at the far end, in the right-hand lane.
+        assert_eq!(stop_line((0, 0), (1, 0)), (195.0, 9.0));
+        assert_eq!(stop_line((1, 0), (0, 0)), (19.0, 5.0));
+    }
+    
+    #[test]
+    fn in_traffic_means_intersections_and_lanes() {
+        assert_eq!(nearest_middle(0), 7);
+        assert_eq!(nearest_middle(106), 7);
+        assert_eq!(nearest_middle(108), 207);
+        
+        assert!(in_traffic(7, 7));      // The middle of an intersection,
+        assert!(in_traffic(14, 14));    // and its corner.
+        assert!(in_traffic(100, 9));    // An east-bound lane.
+        assert!(in_traffic(9, 100));    // A south-bound one.
+        assert!(!in_traffic(100, 11));  // The parking lane beside it.
+        assert!(!in_traffic(100, 50));  // Off the street.
+    }
+    
+    #[test]
+    fn steering_stops_at_walls_and_cars() {
+        let mut world = World::blank();
+        world.replace_tile(110, 11, 0, WallTile(BrickWall));
+        let mut traffic = traffic(vec![
+            car(100, 11, (1, 0), 0.0, VehicleState::Player),
+            car(106, 31, (1, 0), 0.0, VehicleState::Parked(None)),
+            car(100, 31, (1, 0), 0.0, VehicleState::Player)
+        ]);
+        for _ in 0..120 {
+            traffic.steer(0, &world, Some((1, 0)), 1.0 / 60.0);
+            traffic.steer(2, &world, Some((1, 0)), 1.0 / 60.0);
+        }
+        assert_eq!((traffic.vehicles[0].x, traffic.vehicles[0].y), (109.0, 11.0));
+        assert_eq!((traffic.vehicles[2].x, traffic.vehicles[2].y), (104.0, 31.0));
+        
+        // Turning pivots on the front, and there's no room to swing the back round into a wall.
+        world.replace_tile(109, 10, 0, WallTile(BrickWall));
+        traffic.steer(0, &world, Some((0, 1)), 1.0 / 60.0);
+        assert_eq!(traffic.vehicles[0].heading, (1, 0));
+    }
+    
+    #[test]
+    fn moving_cars_cant_be_got_into() {
+        let mut traffic = traffic(vec![
+            car(100, 9, (1, 0), 5.0, VehicleState::Driving),
+            car(100, 11, (1, 0), 0.0, VehicleState::Parked(None))
+        ]);
+        let mut sounds = Vec::new();
+        assert!(!traffic.get_in(0, &mut sounds));
+        assert_eq!(traffic.vehicles[0].state, VehicleState::Driving);
+        assert!(sounds.is_empty());
+        
+        assert!(traffic.get_in(1, &mut sounds));
+        assert_eq!(traffic.vehicles[1].state, VehicleState::Player);
+        assert_eq!(sounds.len(), 1);
+    }
+    
+    #[test]
+    fn cars_cant_be_left_in_traffic() {
+        let world = World::blank();
+        let mut traffic = traffic(vec![
+            car(100, 9, (1, 0), 0.0, VehicleState::Player),
+            car(100, 11, (1, 0), 0.0, VehicleState::Player)
+        ]);
+        assert_eq!(traffic.get_out(0, &world, &KeyRing::new()), None);
+        assert_eq!(traffic.vehicles[0].state, VehicleState::Player);
+        
+        // Out the right-hand side, onto the curb, with the car where it was.
+        assert_eq!(traffic.get_out(1, &world, &KeyRing::new()), Some((100, 12)));
+        assert_eq!(traffic.vehicles[1].state, VehicleState::Parked(None));
+        assert_eq!((traffic.vehicles[1].x, traffic.vehicles[1].y), (100.0, 11.0));
+    }
+    
+    #[test]
+    fn traffic_waits_for_the_player() {
+        let world = World::blank();
+        let mut rng = gen::rng_from_seed(1);
+        let mut driving = car(100, 9, (1, 0), 0.0, VehicleState::Driving);
+        driving.waypoints = vec![(150.0, 9.0)];
+        let mut traffic = traffic(vec![driving]);
+        
+        // In front of it, then level with where its front's about to be.
+        traffic.update(&world, Some((102, 9)), 1.0 / 60.0, &mut rng);
+        assert_eq!(traffic.vehicles[0].x, 100.0);
+        traffic.update(&world, Some((100, 9)), 1.0 / 60.0, &mut rng);
+        assert_eq!(traffic.vehicles[0].speed, 0.0);
+        
+        traffic.update(&world, Some((100, 12)), 1.0 / 60.0, &mut rng);
+        assert!(traffic.vehicles[0].x > 100.0);
+    }
+}
\ No newline at end of file
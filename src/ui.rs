@@ -0,0 +1,366 @@
+use input::Action;
+use text;
+
+// In pixels.
+pub const PADDING: f32 = 8.0;   // Inside panels and widgets.
+pub const SPACING: f32 = 4.0;   // Between widgets.
+
+const PANEL_COLOR:   (f32, f32, f32, f32) = (0.08, 0.08, 0.1, 0.92);
+const WIDGET_COLOR:  (f32, f32, f32, f32) = (0.18, 0.18, 0.22, 1.0);
+const HOVER_COLOR:   (f32, f32, f32, f32) = (0.26, 0.26, 0.32, 1.0);
+const FOCUS_COLOR:   (f32, f32, f32, f32) = (0.3, 0.3, 0.5, 1.0);
+const TEXT_COLOR:    (f32, f32, f32, f32) = (1.0, 1.0, 1.0, 1.0);
+const TITLE_COLOR:   (f32, f32, f32, f32) = (1.0, 0.85, 0.4, 1.0);
+
+// A list's selected row and the first row showing. Kept by whoever owns the list.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ListState {
+    pub selected: usize,
+    pub scroll:   usize
+}
+
+// An immediate-mode UI. Each frame, lay out the open menu by calling widget methods, which draw
+// themselves and return what the player did to them. The only thing kept between frames is which
+// widget has focus, so a menu is just the code that lays it out.
+//
+// Each frame: feed it the window's mouse events with on_cursor, on_click, on_scroll and on_char,
+// call begin with the input system's menu presses, open a panel and lay out widgets, then call
+// end. Widgets stack top to bottom in the panel. Up and down move the focus between them, and
+// confirm or a click activates one.
+pub struct Ui {
+    pub text:   text::Renderer,
+    width:      f32,
+    height:     f32,
+    
+    // Input since the last frame.
+    cursor:     (f32, f32),
+    clicked:    bool,
+    scrolled:   f32,   // Notches of the scroll wheel, positive up.
+    typed:      String,
+    actions:    Vec<Action>,
+    
+    // Focus is an index into the frame's focusable widgets, in the order they're laid out.
+    focus:      usize,
+    focusables: usize,  // How many have been laid out so far this frame.
+    
+    // The open panel's inside edges, and where the next widget goes.
+    left:       f32,
+    right:      f32,
+    pen_y:      f32
+}
+
+impl Ui {
+    pub fn new(text: text::Renderer) -> Ui {
+        Ui {
+            text: text, width: 0.0, height: 0.0,
+            cursor: (0.0, 0.0), clicked: false, scrolled: 0.0, typed: String::new(), actions: Vec::new(),
+            focus: 0, focusables: 0,
+            left: 0.0, right: 0.0, pen_y: 0.0
+        }
+    }
+    
+    pub fn on_cursor(&mut self, x: f32, y: f32) {
+        self.cursor = (x, y);
+    }
+    
+    pub fn on_click(&mut self) {
+        self.clicked = true;
+    }
+    
+    pub fn on_scroll(&mut self, amount: f32) {
+        self.scrolled = self.scrolled + amount;
+    }
+    
+    pub fn on_char(&mut self, c: char) {
+        self.typed.push(c);
+    }
+    
+    pub fn begin(&mut self, width: u16, height: u16, actions: Vec<Action>) {
+        self.width = width as f32;
+        self.height = height as f32;
+        self.actions = actions;
+    }
+    
+    // Moves the focus by whatever up and down presses the widgets didn't use, and forgets this
+    // frame's input.
+    pub fn end(&mut self) {
+        if self.focusables == 0 {
+            self.focus = 0;
+        } else {
+            let mut focus = (self.focus % self.focusables) as i32;
+            for &action in self.actions.iter() {
+                match action {
+                    Action::MenuUp   => { focus = focus - 1; },
+                    Action::MenuDown => { focus = focus + 1; },
+                    _                => {}
+                }
+            }
+            let n = self.focusables as i32;
+            self.focus = (((focus % n) + n) % n) as usize;
+        }
+        
+        self.focusables = 0;
+        self.clicked = false;
+        self.scrolled = 0.0;
+        self.typed.clear();
+        self.actions.clear();
+    }
+    
+    // Call when switching menus, so the new one starts focused on its first widget.
+    pub fn reset_focus(&mut self) {
+        self.focus = 0;
+    }
+    
+    // Gives the focus to the index-th focusable widget laid out this frame.
+    pub fn focus_on(&mut self, index: usize) {
+        self.focus = index;
+    }
+    
+    pub fn draw(&mut self, program: &text::Program) {
+        let (width, height) = (self.width as u16, self.height as u16);
+        self.text.draw(program, width, height);
+    }
+    
+    // In pixels.
+    pub fn screen_size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+    
+    // True if the action was pressed this frame and no widget has used it.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.actions.contains(&action)
+    }
+    
+    // Like pressed, but uses the press up so nothing else sees it.
+    fn take(&mut self, action: Action) -> bool {
+        match self.actions.iter().position(|&a| a == action) {
+            Some(i) => { self.actions.remove(i); true },
+            None    => { false }
+        }
+    }
+    
+    fn line_height(&self) -> f32 {
+        self.text.font.cell_height as f32
+    }
+    
+    // How many characters fit across the open panel.
+    pub fn columns(&self) -> usize {
+        ((self.right - self.left) / self.text.font.cell_width as f32) as usize
+    }
+    
+    // Opens a panel of the given size in pixels in the middle of the screen, with the title along
+    // the top.
+    pub fn begin_panel(&mut self, title: &str, width: f32, height: f32) {
+        let width = width.min(self.width);
+        let height = height.min(self.height);
+        let (x, y) = (((self.width - width) / 2.0).floor(), ((self.height - height) / 2.0).floor());
+        self.begin_panel_at(title, x, y, width, height);
+    }
+    
+    // Like begin_panel, but with its top left at (x, y).
+    pub fn begin_panel_at(&mut self, title: &str, x: f32, y: f32, width: f32, height: f32) {
+        self.text.queue_rect(x, y, width, height, PANEL_COLOR);
+        self.left = x + PADDING;
+        self.right = x + width - PADDING;
+        self.pen_y = y + PADDING;
+        
+        self.text.queue(self.left, self.pen_y, title, TITLE_COLOR);
+        self.pen_y = self.pen_y + self.line_height() + PADDING;
+    }
+    
+    // Takes the next height pixels of the panel for a widget. Returns its rectangle as
+    // (x, y, width, height).
+    fn next_row(&mut self, height: f32) -> (f32, f32, f32, f32) {
+        let row = (self.left, self.pen_y, self.right - self.left, height);
+        self.pen_y = self.pen_y + height + SPACING;
+        row
+    }
+    
+    // Registers a focusable widget covering the rectangle. Clicking it gives it the focus. Returns
+    // whether it has the focus, and whether the cursor is over it.
+    fn focusable(&mut self, rect: (f32, f32, f32, f32)) -> (bool, bool) {
+        let index = self.focusables;
+        self.focusables = self.focusables + 1;
+        let (x, y, w, h) = rect;
+        let hovered = self.cursor.0 >= x && self.cursor.0 < x + w && self.cursor.1 >= y && self.cursor.1 < y + h;
+        if hovered && self.clicked {
+            self.focus = index;
+        }
+        (self.focus == index, hovered)
+    }
+    
+    fn queue_background(&mut self, rect: (f32, f32, f32, f32), focused: bool, hovered: bool) {
+        let color = if focused { FOCUS_COLOR } else if hovered { HOVER_COLOR } else { WIDGET_COLOR };
+        self.text.queue_rect(rect.0, rect.1, rect.2, rect.3, color);
+    }
+    
+    // Text wrapped to the panel's width.
+    pub fn label(&mut self, text: &str) {
+        let lines = wrap(text, self.columns());
+        let height = lines.len() as f32 * self.line_height();
+        let (x, y, _, _) = self.next_row(height);
+        self.text.queue(x, y, &lines.join("\n"), TEXT_COLOR);
+    }
+    
+    // Returns true on the frame it's clicked, or confirmed while it has the focus.
+    pub fn button(&mut self, label: &str) -> bool {
+        let height = self.line_height() + PADDING;
+        let rect = self.next_row(height);
+        let (focused, hovered) = self.focusable(rect);
+        self.queue_background(rect, focused, hovered);
+        self.text.queue(rect.0 + PADDING, rect.1 + PADDING / 2.0, label, TEXT_COLOR);
+        (hovered && self.clicked) || (focused && self.take(Action::Confirm))
+    }
+    
+    // Shows visible rows at a time, scrolling to keep the selected one in view. While it has the
+    // focus, up and down move the selection, and past either end move the focus out. Returns true
+    // on the frame a row's clicked or confirmed.
+    pub fn list(&mut self, rows: &[String], visible: usize, state: &mut ListState) -> bool {
+        let row_height = self.line_height();
+        let rect = self.next_row(visible as f32 * row_height + PADDING);
+        let (focused, hovered) = self.focusable(rect);
+        self.queue_background(rect, focused, false);
+        if rows.is_empty() {
+            return false;
+        }
+        
+        let mut activated = false;
+        if focused {
+            if state.selected > 0 && self.take(Action::MenuUp) {
+                state.selected = state.selected - 1;
+            }
+            if state.selected + 1 < rows.len() && self.take(Action::MenuDown) {
+                state.selected = state.selected + 1;
+            }
+            activated = self.take(Action::Confirm);
+        }
+        if hovered && self.clicked {
+            let row = ((self.cursor.1 - rect.1 - PADDING / 2.0) / row_height).floor();
+            if row >= 0.0 && state.scroll + (row as usize) < rows.len() {
+                state.selected = state.scroll + row as usize;
+                activated = true;
+            }
+        }
+        if hovered && self.scrolled != 0.0 {
+            state.scroll = scroll_by(state.scroll, -self.scrolled, rows.len(), visible);
+        }
+        
+        // Keep the selection in view.
+        state.selected = state.selected.min(rows.len() - 1);
+        if state.selected < state.scroll {
+            state.scroll = state.selected;
+        } else if state.selected >= state.scroll + visible {
+            state.scroll = state.selected + 1 - visible;
+        }
+        
+        let columns = self.columns();
+        for (i, row) in rows.iter().enumerate().skip(state.scroll).take(visible) {
+            let y = rect.1 + PADDING / 2.0 + (i - state.scroll) as f32 * row_height;
+            if i == state.selected {
+                self.text.queue_rect(rect.0, y, rect.2, row_height, HOVER_COLOR);
+            }
+            let shown: String = row.chars().take(columns.saturating_sub(1)).collect();
+            self.text.queue(rect.0 + PADDING, y, &shown, TEXT_COLOR);
+        }
+        activated
+    }
+    
+    // A one-line text field. While it has the focus, typing adds to value, up to max_len
+    // characters, and erase takes off the last one. Returns true when confirmed.
+    pub fn text_input(&mut self, value: &mut String, max_len: usize) -> bool {
+        let height = self.line_height() + PADDING;
+        let rect = self.next_row(height);
+        let (focused, hovered) = self.focusable(rect);
+        self.queue_background(rect, focused, hovered);
+        
+        let mut confirmed = false;
+        if focused {
+            for c in self.typed.chars() {
+                // The font only has printable ASCII.
+                if c >= ' ' && c <= '~' && value.chars().count() < max_len {
+                    value.push(c);
+                }
+            }
+            while self.take(Action::Erase) {
+                value.pop();
+            }
+            confirmed = self.take(Action::Confirm);
+        }
+        
+        let shown = if focused { format!("{}_", value) } else { value.clone() };
+        self.text.queue(rect.0 + PADDING, rect.1 + PADDING / 2.0, &shown, TEXT_COLOR);
+        confirmed
+    }
+    
+    // Read-only text, wrapped to the panel's width, visible lines at a time. scroll is the first
+    // line showing: pass usize::MAX to start at the bottom. The scroll wheel scrolls it, and so do
+    // up and down while it has the focus, until it hits either end.
+    pub fn log(&mut self, entries: &[String], visible: usize, scroll: &mut usize) {
+        let row_height = self.line_height();
+        let rect = self.next_row(visible as f32 * row_height + PADDING);
+        let (focused, hovered) = self.focusable(rect);
+        self.queue_background(rect, focused, false);
+        
+        let columns = self.columns().saturating_sub(1);
+        let mut lines = Vec::new();
+        for entry in entries.iter() {
+            lines.extend(wrap(entry, columns).into_iter());
+        }
+        let last = lines.len().saturating_sub(visible);
+        *scroll = (*scroll).min(last);
+        
+        if focused {
+            if *scroll > 0 && self.take(Action::MenuUp) {
+                *scroll = *scroll - 1;
+            }
+            if *scroll < last && self.take(Action::MenuDown) {
+                *scroll = *scroll + 1;
+            }
+        }
+        if hovered && self.scrolled != 0.0 {
+            *scroll = scroll_by(*scroll, -self.scrolled, lines.len(), visible);
+        }
+        
+        let shown: Vec<String> = lines.into_iter().skip(*scroll).take(visible).collect();
+        self.text.queue(rect.0 + PADDING, rect.1 + PADDING / 2.0, &shown.join("\n"), TEXT_COLOR);
+    }
+}
+
+// Scrolls by some notches of the scroll wheel, three lines each, without going past either end.
+fn scroll_by(scroll: usize, notches: f32, lines: usize, visible: usize) -> usize {
+    let last = lines.saturating_sub(visible) as i32;
+    let scrolled = scroll as i32 + (notches * 3.0).round() as i32;
+    if scrolled < 0 { 0 } else if scrolled > last { last as usize } else { scrolled as usize }
+}
+
+// Breaks text into lines of at most columns characters, at spaces where it can. Keeps the
+// newlines already in it.
+pub fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let columns = columns.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.trim_right().split('\n') {
+        let mut line = String::new();
+        // Splitting on every space, rather than runs of them, keeps indents.
+        for (i, word) in paragraph.split(' ').enumerate() {
+            let (line_len, word_len) = (line.chars().count(), word.chars().count());
+            if i > 0 {
+                if line_len + 1 + word_len > columns {
+                    lines.push(line);
+                    line = String::new();
+                } else {
+                    line.push(' ');
+                }
+            }
+            // Words too long for a line get cut up.
+            let mut rest: Vec<char> = word.chars().collect();
+            while rest.len() > columns {
+                let tail = rest.split_off(columns);
+                lines.push(rest.into_iter().collect());
+                rest = tail;
+            }
+            line.extend(rest.into_iter());
+        }
+        lines.push(line);
+    }
+    lines
+}
\ No newline at end of file
@@ -1,40 +1,326 @@
 use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::rc::Rc;
 
-use tile;
 use tile::{Chunk, Tile};
+use tile::Tile::EmptyTile;
 use tile::chunk;
 use gen;
-use gen::street::{STREET_WIDTH, BLOCK_WIDTH, GRID_SIZE};
+use gen::street::{Grid, STREET_WIDTH, BLOCK_WIDTH, GRID_SIZE};
+use sound::Sound;
+use building::Building;
+use light::{self, Light};
+use camera::Rect;
+use security::{SecurityCamera, Guard};
 
 pub struct World {
+    // The ground level, which covers the whole city.
     pub chunks: Vec<Vec<Rc<RefCell<Chunk>>>>,
+    
+    // The levels above the ground, only where something's built, by chunk indices (x, y, z). z
+    // starts at 1.
+    pub upper_chunks: BTreeMap<(usize, usize, usize), Rc<RefCell<Chunk>>>,
+    
+    pub buildings: Vec<Building>,
+    
+    // The street graph the city was generated from.
+    pub grid: Grid,
+    
+    // In grid coords.
+    pub police_stations: Vec<(usize, usize)>,
+    
+    // Sounds made and alarms tripped since the last tick. Whoever listens for them clears them.
+    pub sounds: Vec<Sound>,
+    pub alarms: Vec<(usize, usize)>,
+    
+    pub cameras: Vec<SecurityCamera>,
+    pub guards:  Vec<Guard>,
+    
+    // Chunks whose meshes are out of date, as chunk indices (x, y, z). The renderer re-meshes and
+    // clears them.
+    pub dirty_chunks: HashSet<(usize, usize, usize)>,
+    
+    next_item_id: u32
 }
 
 impl World {
-    pub fn new(tile_program: &tile::Program) -> World {
+    // Generates the city. Needs no GL context: hand the result to a tile::Renderer to draw it.
+    pub fn new(seed: u32, strategy: gen::Strategy) -> World {
+        let mut world = World::blank();
+        gen::generate(&mut world, seed, strategy);
+        
+        // A new renderer uploads every chunk anyway.
+        world.dirty_chunks.clear();
+        world
+    }
+    
+    // A city's worth of grass with nothing built on it.
+    pub fn blank() -> World {
         let num_chunks = (STREET_WIDTH + BLOCK_WIDTH) * GRID_SIZE / chunk::SIZE;
         let mut chunks = Vec::with_capacity(num_chunks);
         for y in 0..num_chunks {
             let mut row = Vec::with_capacity(num_chunks);
             for x in 0..num_chunks {
-                let chunk = Chunk::blank(tile_program, x * chunk::SIZE, y * chunk::SIZE);
+                let chunk = Chunk::blank(x * chunk::SIZE, y * chunk::SIZE);
                 row.push(Rc::new(RefCell::new(chunk)));
             }
             chunks.push(row);
         }
-        let mut world = World { chunks: chunks };
-        gen::generate(&mut world);
-        world
+        World {
+            chunks: chunks, upper_chunks: BTreeMap::new(), buildings: Vec::new(), grid: Grid::empty(), police_stations: Vec::new(),
+            sounds: Vec::new(), alarms: Vec::new(), cameras: Vec::new(), guards: Vec::new(), dirty_chunks: HashSet::new(), next_item_id: 0
+        }
+    }
+    
+    // Width and height in tiles.
+    pub fn size(&self) -> usize {
+        self.chunks.len() * chunk::SIZE
+    }
+    
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.size() && (y as usize) < self.size()
+    }
+    
+    // How many levels there are, counting the ground.
+    pub fn levels(&self) -> usize {
+        self.upper_chunks.keys().fold(1, |levels, &(_, _, z)| levels.max(z + 1))
+    }
+    
+    // None for chunks above the ground where nothing's built.
+    pub fn chunk_at(&self, chunk_x: usize, chunk_y: usize, z: usize) -> Option<&Rc<RefCell<Chunk>>> {
+        if z == 0 {
+            Some(&self.chunks[chunk_y][chunk_x])
+        } else {
+            self.upper_chunks.get(&(chunk_x, chunk_y, z))
+        }
+    }
+    
+    pub fn chunk_containing(&self, x: usize, y: usize, z: usize) -> Option<&Rc<RefCell<Chunk>>> {
+        self.chunk_at(x / chunk::SIZE, y / chunk::SIZE, z)
+    }
+    
+    // Makes an empty chunk for the tile if there isn't one yet.
+    fn chunk_for(&mut self, x: usize, y: usize, z: usize) -> Rc<RefCell<Chunk>> {
+        let (chunk_x, chunk_y) = (x / chunk::SIZE, y / chunk::SIZE);
+        if z == 0 {
+            return self.chunks[chunk_y][chunk_x].clone();
+        }
+        self.upper_chunks.entry((chunk_x, chunk_y, z)).or_insert_with(|| {
+            Rc::new(RefCell::new(Chunk::empty(chunk_x * chunk::SIZE, chunk_y * chunk::SIZE, z)))
+        }).clone()
+    }
+    
+    // Chunk indices (x, y) of every chunk at least partly inside rect. Tiles are centred on their
+    // coords, so a chunk covers half a tile past its first and last rows and columns.
+    pub fn chunks_in_rect(&self, rect: &Rect) -> Vec<(usize, usize)> {
+        let size = chunk::SIZE as f32;
+        let last = self.chunks.len() as i32 - 1;
+        let clamp = |i: i32| if i < 0 { 0 } else if i > last { last } else { i };
+        
+        if rect.max_x < -0.5 || rect.max_y < -0.5 ||
+           rect.min_x > self.size() as f32 - 0.5 || rect.min_y > self.size() as f32 - 0.5 {
+            return Vec::new();
+        }
+        
+        let min_cx = clamp(((rect.min_x + 0.5) / size).floor() as i32);
+        let min_cy = clamp(((rect.min_y + 0.5) / size).floor() as i32);
+        let max_cx = clamp(((rect.max_x + 0.5) / size).floor() as i32);
+        let max_cy = clamp(((rect.max_y + 0.5) / size).floor() as i32);
+        
+        let mut visible = Vec::new();
+        for cy in min_cy..(max_cy + 1) {
+            for cx in min_cx..(max_cx + 1) {
+                visible.push((cx as usize, cy as usize));
+            }
+        }
+        visible
+    }
+    
+    pub fn building_containing(&self, x: usize, y: usize) -> Option<usize> {
+        self.buildings.iter().position(|b| b.contains(x, y))
+    }
+    
+    // True if nothing opaque lies on the line between the two tiles on level z. The endpoints
+    // themselves don't block.
+    pub fn line_of_sight(&self, from: (usize, usize), to: (usize, usize), z: usize) -> bool {
+        let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+        let (x1, y1) = (to.0 as i32, to.1 as i32);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        
+        // Bresenham.
+        loop {
+            if x == x1 && y == y1 {
+                return true;
+            }
+            if (x, y) != (from.0 as i32, from.1 as i32) && self.tile_at(x as usize, y as usize, z).is_opaque() {
+                return false;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy { err = err + dy; x = x + sx; }
+            if e2 <= dx { err = err + dx; y = y + sy; }
+        }
     }
     
-    pub fn chunk_containing(&self, x: usize, y: usize) -> &Rc<RefCell<Chunk>> {
-        let chunk_x: usize = x / chunk::SIZE;
-        let chunk_y: usize = y / chunk::SIZE;
-        &self.chunks[chunk_y][chunk_x]
+    // Lights only reach the level they're on.
+    pub fn add_light(&mut self, light: Light, z: usize) {
+        self.chunk_for(light.x, light.y, z).borrow_mut().lights.push(light);
+    }
+    
+    // How brightly lit the tile is by lights, from 0 to 1. Daylight isn't counted.
+    pub fn light_at(&self, x: usize, y: usize, z: usize) -> f32 {
+        let chunk_x = (x / chunk::SIZE) as i32;
+        let chunk_y = (y / chunk::SIZE) as i32;
+        let reach = (light::MAX_RADIUS / chunk::SIZE as f32).ceil() as i32;
+        let mut brightness: f32 = 0.0;
+        for cy in (chunk_y - reach)..(chunk_y + reach + 1) {
+            for cx in (chunk_x - reach)..(chunk_x + reach + 1) {
+                if cx < 0 || cy < 0 || cx as usize >= self.chunks.len() || cy as usize >= self.chunks.len() {
+                    continue;
+                }
+                if let Some(chunk) = self.chunk_at(cx as usize, cy as usize, z) {
+                    for l in chunk.borrow().lights.iter() {
+                        brightness = brightness.max(l.brightness_at(x, y));
+                    }
+                }
+            }
+        }
+        brightness
+    }
+    
+    // How visible someone standing on the tile is, from 0 to 1, given the daylight.
+    pub fn visibility_at(&self, x: usize, y: usize, z: usize, daylight: f32) -> f32 {
+        daylight.max(self.light_at(x, y, z))
+    }
+    
+    // Recomputes how brightly lit every tile in the chunk is. Does nothing if there's no such
+    // chunk.
+    pub fn relight_chunk(&mut self, chunk_x: usize, chunk_y: usize, z: usize) {
+        let chunk = match self.chunk_at(chunk_x, chunk_y, z) {
+            Some(chunk) => { chunk.clone() },
+            None        => { return; }
+        };
+        let (min_x, min_y) = (chunk_x * chunk::SIZE, chunk_y * chunk::SIZE);
+        let mut light_map = Vec::with_capacity(chunk::SIZE * chunk::SIZE);
+        for y in min_y..(min_y + chunk::SIZE) {
+            for x in min_x..(min_x + chunk::SIZE) {
+                light_map.push(self.light_at(x, y, z));
+            }
+        }
+        chunk.borrow_mut().light_map = light_map;
+        self.dirty_chunks.insert((chunk_x, chunk_y, z));
+    }
+    
+    // Every item in the world gets a unique id, so it can be told apart from identical ones.
+    pub fn next_item_id(&mut self) -> u32 {
+        self.next_item_id = self.next_item_id + 1;
+        self.next_item_id
+    }
+    
+    // Open air above the ground where nothing's built.
+    pub fn tile_at(&self, x: usize, y: usize, z: usize) -> Tile {
+        match self.chunk_containing(x, y, z) {
+            Some(chunk) => { chunk.borrow().tile_at(x, y).clone() },
+            None        => { EmptyTile }
+        }
+    }
+    
+    pub fn replace_tile(&mut self, x: usize, y: usize, z: usize, tile: Tile) {
+        self.chunk_for(x, y, z).borrow_mut().replace_tile(x, y, tile);
+        self.mark_dirty(x, y, z);
+    }
+    
+    // Call after changing a tile in place, e.g. opening a door. Marks the chunks of the tile and
+    // its neighbours, since autotiled neighbours may look different now too.
+    pub fn mark_dirty(&mut self, x: usize, y: usize, z: usize) {
+        for dy in -1..2 {
+            for dx in -1..2 {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if self.in_bounds(nx, ny) && self.chunk_containing(nx as usize, ny as usize, z).is_some() {
+                    self.dirty_chunks.insert((nx as usize / chunk::SIZE, ny as usize / chunk::SIZE, z));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector2;
+    
+    use camera::Camera;
+    use super::World;
+    
+    // The city is 100 x 100 chunks of 20 x 20 tiles. A camera shows width / zoom tiles either side
+    // of the middle across, and height / zoom up and down.
+    
+    // The camera centred on (x, y) in world coords.
+    fn camera_on(width: u16, height: u16, zoom: f32, x: f32, y: f32) -> Camera {
+        let mut camera = Camera::new(width, height, zoom);
+        camera.translate(Vector2::new(-x, -y));
+        camera.snap();
+        camera
+    }
+    
+    // Every chunk from min to max inclusive, row by row.
+    fn chunks(min: (usize, usize), max: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut chunks = Vec::new();
+        for cy in min.1..(max.1 + 1) {
+            for cx in min.0..(max.0 + 1) {
+                chunks.push((cx, cy));
+            }
+        }
+        chunks
+    }
+    
+    #[test]
+    fn centred() {
+        let world = World::blank();
+        // Tiles 970 to 1050 across and 990 to 1030 down.
+        let camera = camera_on(400, 200, 10.0, 1010.0, 1010.0);
+        assert_eq!(world.chunks_in_rect(&camera.visible_rect()), chunks((48, 49), (52, 51)));
+    }
+    
+    #[test]
+    fn off_every_edge() {
+        let world = World::blank();
+        
+        // Tiles -40 to 40 across and -20 to 20 down, off the top and left.
+        let camera = camera_on(400, 200, 10.0, 0.0, 0.0);
+        assert_eq!(world.chunks_in_rect(&camera.visible_rect()), chunks((0, 0), (2, 1)));
+        
+        // Tiles 1959 to 2039 across and 1979 to 2019 down, off the bottom and right.
+        let camera = camera_on(400, 200, 10.0, 1999.0, 1999.0);
+        assert_eq!(world.chunks_in_rect(&camera.visible_rect()), chunks((97, 98), (99, 99)));
+        
+        // Tiles -3000 to 5000 both ways, off all four at once.
+        let camera = camera_on(400, 400, 0.1, 1000.0, 1000.0);
+        assert_eq!(world.chunks_in_rect(&camera.visible_rect()), chunks((0, 0), (99, 99)));
+    }
+    
+    #[test]
+    fn fully_off_the_world() {
+        let world = World::blank();
+        for &(x, y) in [(-100.0, 500.0), (2100.0, 500.0), (500.0, -100.0), (500.0, 2100.0)].iter() {
+            let camera = camera_on(400, 200, 10.0, x, y);
+            assert_eq!(world.chunks_in_rect(&camera.visible_rect()), Vec::new());
+        }
     }
     
-    pub fn replace_tile(&mut self, x: usize, y: usize, tile: Tile) {
-        let mut chunk = self.chunk_containing(x, y).borrow_mut().replace_tile(x, y, tile);
+    #[test]
+    fn resized_window() {
+        let world = World::blank();
+        let mut camera = camera_on(400, 200, 10.0, 1010.0, 1010.0);
+        
+        // Twice as wide: tiles 930 to 1090 across, and still 990 to 1030 down.
+        camera.resize(800, 200);
+        assert_eq!(world.chunks_in_rect(&camera.visible_rect()), chunks((46, 49), (54, 51)));
+        
+        // Then twice as tall too: tiles 970 to 1050 down.
+        camera.resize(800, 400);
+        assert_eq!(world.chunks_in_rect(&camera.visible_rect()), chunks((46, 48), (54, 52)));
     }
 }
\ No newline at end of file